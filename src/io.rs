@@ -9,6 +9,7 @@ pub mod mzml;
 #[cfg(feature = "mzmlb")]
 pub mod mzmlb;
 mod offset_index;
+pub mod prelude;
 mod shorthand;
 pub(crate) mod traits;
 mod utils;
@@ -16,7 +17,7 @@ mod utils;
 pub(crate) mod compression;
 
 pub use crate::io::infer_format::{
-    infer_format, infer_from_path, infer_from_stream, MZReader, MZReaderType,
+    infer_format, infer_from_path, infer_from_stream, open, MZReader, MZReaderType,
     MassSpectrometryFormat, MassSpectrometryReadWriteProcess, Sink, Source,
 };
 pub use crate::io::mgf::{MGFError, MGFReader, MGFWriter};
@@ -27,16 +28,22 @@ pub use crate::io::mzml::{MzMLParserError, MzMLReader, MzMLWriter};
 pub use crate::io::mzmlb::{MzMLbError, MzMLbReader};
 pub use crate::io::offset_index::OffsetIndex;
 pub use crate::io::traits::{
-    BorrowedGeneric3DIonMobilityFrameSource, ChromatogramIterator, ChromatogramSource,
-    Generic3DIonMobilityFrameSource, IonMobilityFrameAccessError, IonMobilityFrameGrouping,
-    IonMobilityFrameIterator, IonMobilityFrameSource, MZFileReader, MemorySpectrumSource,
-    RandomAccessIonMobilityFrameIterator, RandomAccessSpectrumGroupingIterator,
-    RandomAccessSpectrumIterator, RandomAccessSpectrumSource, SpectrumAccessError,
-    SpectrumGrouping, SpectrumIterator, SpectrumReceiver, SpectrumSource,
-    SpectrumSourceWithMetadata, SpectrumWriter, StreamingSpectrumIterator,
+    split_by_ms_level, BorrowedGeneric3DIonMobilityFrameSource, ChromatogramIterator,
+    ChromatogramSource, Generic3DIonMobilityFrameSource, IonMobilityFrameAccessError,
+    IonMobilityFrameGrouping, IonMobilityFrameIterator, IonMobilityFrameSource, MZFileReader,
+    MemorySpectrumSource, RandomAccessIonMobilityFrameIterator,
+    RandomAccessSpectrumGroupingIterator, RandomAccessSpectrumIterator, RandomAccessSpectrumSource,
+    RunStatistics, ScanWriter, SpectrumAccessError, SpectrumGrouping, SpectrumIterator,
+    SpectrumReceiver, SpectrumSource, SpectrumSourceWithMetadata, SpectrumWriter,
+    StreamingSpectrumIterator,
+};
+pub use crate::io::utils::{
+    checksum_file, ArrayLengthValidation, DetailLevel, ErrorPolicy, PreBufferedStream,
+};
+pub use compression::{
+    MaybeCompressed, RestartableBzDecoder, RestartableDecoder, RestartableGzDecoder,
+    RestartableStreamDecoder, StreamDecompressor,
 };
-pub use crate::io::utils::{checksum_file, DetailLevel, PreBufferedStream};
-pub use compression::RestartableGzDecoder;
 
 #[cfg(feature = "thermorawfilereader")]
 pub mod thermo;
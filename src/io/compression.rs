@@ -1,6 +1,8 @@
+use std::marker::PhantomData;
 use std::mem::swap;
 use std::{io, path};
 
+use bzip2::bufread::MultiBzDecoder;
 use flate2::bufread::MultiGzDecoder;
 use std::io::prelude::*;
 
@@ -8,6 +10,10 @@ pub fn is_gzipped(header: &[u8]) -> bool {
     header.starts_with(b"\x1f\x8b")
 }
 
+pub fn is_bzipped(header: &[u8]) -> bool {
+    header.starts_with(b"BZh")
+}
+
 pub fn is_gzipped_extension(path: path::PathBuf) -> (bool, path::PathBuf) {
     if let Some(ext) = path.extension() {
         if ext.to_ascii_lowercase() == "gz" {
@@ -20,21 +26,73 @@ pub fn is_gzipped_extension(path: path::PathBuf) -> (bool, path::PathBuf) {
     }
 }
 
-/// A [`flate2::bufread::MultiGzDecoder`] that implements [`std::io::Seek`] by
+pub fn is_bzipped_extension(path: path::PathBuf) -> (bool, path::PathBuf) {
+    if let Some(ext) = path.extension() {
+        if ext.to_ascii_lowercase() == "bz2" {
+            (true, path.with_extension(""))
+        } else {
+            (false, path)
+        }
+    } else {
+        (false, path)
+    }
+}
+
+/// A streaming decompressor that can be rebuilt over its underlying reader, so that
+/// [`RestartableStreamDecoder`] can rewind it to the start of the stream.
+pub trait StreamDecompressor<R: BufRead>: Read {
+    /// Start decompressing `handle` from its current position
+    fn new(handle: R) -> Self;
+
+    /// Recover the underlying reader
+    fn into_inner(self) -> R;
+}
+
+impl<R: BufRead> StreamDecompressor<R> for MultiGzDecoder<R> {
+    fn new(handle: R) -> Self {
+        MultiGzDecoder::new(handle)
+    }
+
+    fn into_inner(self) -> R {
+        MultiGzDecoder::into_inner(self)
+    }
+}
+
+impl<R: BufRead> StreamDecompressor<R> for MultiBzDecoder<R> {
+    fn new(handle: R) -> Self {
+        MultiBzDecoder::new(handle)
+    }
+
+    fn into_inner(self) -> R {
+        MultiBzDecoder::into_inner(self)
+    }
+}
+
+/// A [`StreamDecompressor`] that implements [`std::io::Seek`] by
 /// by incrementally reading ahead, or rewinding to the beginning of the file and
 /// doing the same.
 ///
 /// Not intended to be efficient, but provides a workable interface.
-pub struct RestartableGzDecoder<R: BufRead + Seek> {
-    handle: Option<MultiGzDecoder<R>>,
+pub struct RestartableStreamDecoder<R: BufRead + Seek, D: StreamDecompressor<R>> {
+    handle: Option<D>,
     offset: u64,
+    _reader: PhantomData<R>,
 }
 
-impl<R: BufRead + Seek> RestartableGzDecoder<R> {
+/// A [`flate2::bufread::MultiGzDecoder`] that implements [`std::io::Seek`], see
+/// [`RestartableStreamDecoder`]
+pub type RestartableGzDecoder<R> = RestartableStreamDecoder<R, MultiGzDecoder<R>>;
+
+/// A [`bzip2::bufread::MultiBzDecoder`] that implements [`std::io::Seek`], see
+/// [`RestartableStreamDecoder`]
+pub type RestartableBzDecoder<R> = RestartableStreamDecoder<R, MultiBzDecoder<R>>;
+
+impl<R: BufRead + Seek, D: StreamDecompressor<R>> RestartableStreamDecoder<R, D> {
     pub fn new(handle: R) -> Self {
         Self {
-            handle: Some(MultiGzDecoder::new(handle)),
+            handle: Some(D::new(handle)),
             offset: 0,
+            _reader: PhantomData,
         }
     }
 
@@ -44,13 +102,13 @@ impl<R: BufRead + Seek> RestartableGzDecoder<R> {
         let handle = handle.unwrap();
         let mut inner = handle.into_inner();
         let res = inner.seek(io::SeekFrom::Start(0));
-        self.handle = Some(MultiGzDecoder::new(inner));
+        self.handle = Some(D::new(inner));
         self.offset = 0;
         res
     }
 }
 
-impl<R: BufRead + Seek> Read for RestartableGzDecoder<R> {
+impl<R: BufRead + Seek, D: StreamDecompressor<R>> Read for RestartableStreamDecoder<R, D> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         let handle = self.handle.as_mut().unwrap();
         match handle.read(buf) {
@@ -63,7 +121,7 @@ impl<R: BufRead + Seek> Read for RestartableGzDecoder<R> {
     }
 }
 
-impl<R: BufRead + Seek> Seek for RestartableGzDecoder<R> {
+impl<R: BufRead + Seek, D: StreamDecompressor<R>> Seek for RestartableStreamDecoder<R, D> {
     fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
         match pos {
             io::SeekFrom::Start(o) => {
@@ -74,7 +132,7 @@ impl<R: BufRead + Seek> Seek for RestartableGzDecoder<R> {
             }
             io::SeekFrom::End(_) => Err(io::Error::new(
                 io::ErrorKind::Unsupported,
-                "Cannot seek relative to end of a gzip stream",
+                "Cannot seek relative to end of a compressed stream",
             )),
             io::SeekFrom::Current(o) => {
                 match o {
@@ -85,7 +143,7 @@ impl<R: BufRead + Seek> Seek for RestartableGzDecoder<R> {
                         if o.unsigned_abs() > self.offset {
                             Err(io::Error::new(
                                 io::ErrorKind::Unsupported,
-                                "Cannot earlier than the start of the stream",
+                                "Cannot seek earlier than the start of the stream",
                             ))
                         } else {
                             self.seek(io::SeekFrom::Start((self.offset as i64 + o) as u64))
@@ -101,3 +159,97 @@ impl<R: BufRead + Seek> Seek for RestartableGzDecoder<R> {
         }
     }
 }
+
+/// A seekable decompressing reader that picks between [`RestartableGzDecoder`] and
+/// [`RestartableBzDecoder`] by looking at the magic bytes at the start of the stream.
+pub enum RestartableDecoder<R: BufRead + Seek> {
+    Gzip(RestartableGzDecoder<R>),
+    Bzip2(RestartableBzDecoder<R>),
+}
+
+impl<R: BufRead + Seek> RestartableDecoder<R> {
+    /// Wrap `handle` in the decoder matching its compression format, defaulting
+    /// to GZIP if the header is not recognized.
+    pub fn new(mut handle: R) -> io::Result<Self> {
+        if is_bzipped(handle.fill_buf()?) {
+            Ok(Self::Bzip2(RestartableBzDecoder::new(handle)))
+        } else {
+            Ok(Self::Gzip(RestartableGzDecoder::new(handle)))
+        }
+    }
+}
+
+impl<R: BufRead + Seek> Read for RestartableDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Gzip(handle) => handle.read(buf),
+            Self::Bzip2(handle) => handle.read(buf),
+        }
+    }
+}
+
+impl<R: BufRead + Seek> Seek for RestartableDecoder<R> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::Gzip(handle) => handle.seek(pos),
+            Self::Bzip2(handle) => handle.seek(pos),
+        }
+    }
+}
+
+/// A reader over a file which may be compressed, reading it as-is or through a
+/// [`RestartableDecoder`] when it starts with the gzip or bzip2 magic bytes.
+///
+/// An uncompressed file can seek freely. A compressed one can only be rewound to the
+/// start or asked for its current position, so a reader over it can iterate and reset,
+/// but not jump to an arbitrary spectrum.
+pub enum MaybeCompressed<R: Read + Seek> {
+    Plain(R),
+    Compressed(RestartableDecoder<io::BufReader<R>>),
+}
+
+impl<R: Read + Seek> MaybeCompressed<R> {
+    /// Wrap `handle`, checking the magic bytes at its current position for a
+    /// compression format
+    pub fn new(handle: R) -> io::Result<Self> {
+        let mut handle = io::BufReader::new(handle);
+        let header = handle.fill_buf()?;
+        if is_gzipped(header) || is_bzipped(header) {
+            Ok(Self::Compressed(RestartableDecoder::new(handle)?))
+        } else {
+            let start = handle.stream_position()?;
+            let mut inner = handle.into_inner();
+            inner.seek(io::SeekFrom::Start(start))?;
+            Ok(Self::Plain(inner))
+        }
+    }
+
+    /// Whether the stream is read through a decompressor
+    pub fn is_compressed(&self) -> bool {
+        matches!(self, Self::Compressed(_))
+    }
+}
+
+impl<R: Read + Seek> Read for MaybeCompressed<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(handle) => handle.read(buf),
+            Self::Compressed(handle) => handle.read(buf),
+        }
+    }
+}
+
+impl<R: Read + Seek> Seek for MaybeCompressed<R> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::Plain(handle) => handle.seek(pos),
+            Self::Compressed(handle) => match pos {
+                io::SeekFrom::Start(0) | io::SeekFrom::Current(0) => handle.seek(pos),
+                _ => Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Cannot seek within a compressed stream, only rewind it to the start",
+                )),
+            },
+        }
+    }
+}
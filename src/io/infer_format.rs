@@ -7,6 +7,7 @@ use std::path::{self, Path, PathBuf};
 use std::sync::mpsc::{Receiver, Sender, SyncSender};
 
 
+use bzip2::read::BzDecoder;
 use flate2::{bufread::GzDecoder, write::GzEncoder};
 use mzpeaks::{CentroidLike, CentroidPeak, DeconvolutedCentroidLike, DeconvolutedPeak};
 
@@ -15,7 +16,9 @@ use crate::params::ControlledVocabulary;
 #[cfg(feature = "mzmlb")]
 pub use crate::io::mzmlb::{MzMLbReaderType, MzMLbWriterBuilder};
 
-use crate::io::compression::{is_gzipped, is_gzipped_extension, RestartableGzDecoder};
+use crate::io::compression::{
+    is_bzipped, is_bzipped_extension, is_gzipped, is_gzipped_extension, RestartableDecoder,
+};
 use crate::io::mgf::{is_mgf, MGFReaderType, MGFWriterType};
 use crate::io::mzml::{is_mzml, MzMLReaderType, MzMLWriterType};
 use crate::io::traits::{RandomAccessSpectrumIterator, SpectrumSource, SpectrumWriter, MZFileReader};
@@ -369,7 +372,16 @@ impl<C: CentroidLike + Default + From<CentroidPeak> + BuildFromArrayMap,
      D: DeconvolutedCentroidLike + Default + From<DeconvolutedPeak> + BuildFromArrayMap> MZFileReader<C, D, MultiLayerSpectrum<C, D>> for MZReaderType<fs::File, C, D> {
 
     fn construct_index_from_stream(&mut self) -> u64 {
-        msfmt_dispatch!(self, reader, reader.construct_index_from_stream())
+        match self {
+            // The MGF reader over a plain file is indexed directly, since `MZFileReader` opens
+            // MGF files through a decompressor
+            MZReaderType::MGF(reader) => reader.build_index(),
+            MZReaderType::MzML(reader) => reader.construct_index_from_stream(),
+            #[cfg(feature = "thermorawfilereader")]
+            MZReaderType::ThermoRaw(reader) => reader.construct_index_from_stream(),
+            #[cfg(feature = "mzmlb")]
+            MZReaderType::MzMLb(reader) => reader.construct_index_from_stream(),
+        }
     }
 
     fn open_path<P>(path: P) -> io::Result<Self>
@@ -379,12 +391,12 @@ impl<C: CentroidLike + Default + From<CentroidPeak> + BuildFromArrayMap,
         if is_gzipped {
             return Err(io::Error::new(
                 io::ErrorKind::Unsupported,
-                "Gzipped files are not supported",
+                "Compressed files are not supported",
             ))
         }
         match format {
             MassSpectrometryFormat::MGF => {
-                let reader = MGFReaderType::open_path(path)?;
+                let reader = MGFReaderType::new_indexed(fs::File::open(path.into())?);
                 Ok(Self::MGF(reader))
             }
             MassSpectrometryFormat::MzML => {
@@ -415,12 +427,12 @@ impl<C: CentroidLike + Default + From<CentroidPeak> + BuildFromArrayMap,
         if is_gzipped {
             return Err(io::Error::new(
                 io::ErrorKind::Unsupported,
-                "Gzipped files are not supported",
+                "Compressed files are not supported",
             ))
         }
         match format {
             MassSpectrometryFormat::MGF => {
-                let reader = MGFReaderType::open_file(source)?;
+                let reader = MGFReaderType::new(source);
                 Ok(Self::MGF(reader))
             }
             MassSpectrometryFormat::MzML => {
@@ -567,10 +579,12 @@ impl<C: CentroidLike + Default + From<CentroidPeak> + BuildFromArrayMap,
 }
 
 /// Given a path, infer the file format and whether or not the file at that path is
-/// GZIP compressed
+/// GZIP or BZIP2 compressed
 pub fn infer_from_path<P: Into<path::PathBuf>>(path: P) -> (MassSpectrometryFormat, bool) {
     let path: path::PathBuf = path.into();
     let (is_gzipped, path) = is_gzipped_extension(path);
+    let (is_bzipped, path) = is_bzipped_extension(path);
+    let is_gzipped = is_gzipped || is_bzipped;
     if let Some(ext) = path.extension() {
         if let Some(ext) = ext.to_ascii_lowercase().to_str() {
             let form = match ext {
@@ -592,7 +606,7 @@ pub fn infer_from_path<P: Into<path::PathBuf>>(path: P) -> (MassSpectrometryForm
 }
 
 /// Given a stream of bytes, infer the file format and whether or not the
-/// stream is GZIP compressed. This assumes the stream is seekable.
+/// stream is GZIP or BZIP2 compressed. This assumes the stream is seekable.
 pub fn infer_from_stream<R: Read + Seek>(
     stream: &mut R,
 ) -> io::Result<(MassSpectrometryFormat, bool)> {
@@ -605,6 +619,7 @@ pub fn infer_from_stream<R: Read + Seek>(
     let bytes_read = stream.read(buf.as_mut_slice())?;
     buf.shrink_to(bytes_read);
     let is_stream_gzipped = is_gzipped(buf.as_slice());
+    let is_stream_bzipped = is_bzipped(buf.as_slice());
     if is_stream_gzipped {
         let mut decompressed_buf = Vec::new();
         // In the worst case, we can't have fewer bytes than those that were read in (minus the size of the gzip header)
@@ -616,19 +631,30 @@ pub fn infer_from_stream<R: Read + Seek>(
         decoder.read_exact(&mut decompressed_buf)?;
         buf = decompressed_buf;
     }
+    if is_stream_bzipped {
+        // bzip2 compresses whole blocks at once, so the bytes already read in may not be enough
+        // to decode anything. Instead, decode from the start of the stream until we have as many
+        // bytes as we would otherwise have read.
+        stream.seek(io::SeekFrom::Start(current_pos))?;
+        let mut decompressed_buf = Vec::with_capacity(500);
+        let decoder = BzDecoder::new(&mut *stream);
+        decoder.take(500).read_to_end(&mut decompressed_buf)?;
+        buf = decompressed_buf;
+    }
+    let is_compressed = is_stream_gzipped || is_stream_bzipped;
     stream.seek(io::SeekFrom::Start(current_pos))?;
 
     match &buf {
-        _ if is_mzml(&buf) => Ok((MassSpectrometryFormat::MzML, is_stream_gzipped)),
-        _ if is_mgf(&buf) => Ok((MassSpectrometryFormat::MGF, is_stream_gzipped)),
+        _ if is_mzml(&buf) => Ok((MassSpectrometryFormat::MzML, is_compressed)),
+        _ if is_mgf(&buf) => Ok((MassSpectrometryFormat::MGF, is_compressed)),
         #[cfg(feature = "thermorawfilereader")]
-        _ if is_thermo_raw_prefix(&buf) => Ok((MassSpectrometryFormat::ThermoRaw, is_stream_gzipped)),
-        _ => Ok((MassSpectrometryFormat::Unknown, is_stream_gzipped))
+        _ if is_thermo_raw_prefix(&buf) => Ok((MassSpectrometryFormat::ThermoRaw, is_compressed)),
+        _ => Ok((MassSpectrometryFormat::Unknown, is_compressed))
     }
 }
 
 /// Given a path, infer the file format and whether or not the file at that path is
-/// GZIP compressed, using both the file name and by trying to open and read the file
+/// GZIP or BZIP2 compressed, using both the file name and by trying to open and read the file
 /// header
 pub fn infer_format<P: Into<path::PathBuf>>(path: P) -> io::Result<(MassSpectrometryFormat, bool)> {
     let path: path::PathBuf = path.into();
@@ -645,6 +671,36 @@ pub fn infer_format<P: Into<path::PathBuf>>(path: P) -> io::Result<(MassSpectrom
     }
 }
 
+/// Open the mass spectrometry data file at `path`, inferring its format and compression,
+/// and return a reader over its spectra without having to name the concrete reader type.
+///
+/// Uncompressed files are opened with [`MZReader`]. Compressed MGF and mzML files are
+/// read through a [`RestartableDecoder`] and indexed, so they support random access too,
+/// although seeking may re-read the file from the start.
+pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn SpectrumSource>> {
+    let path = path.as_ref();
+    let (format, is_gzipped) = infer_format(path)?;
+    if !is_gzipped {
+        let reader = MZReader::open_path(path)?;
+        return Ok(Box::new(reader));
+    }
+    match format {
+        MassSpectrometryFormat::MGF => {
+            let handle = RestartableDecoder::new(io::BufReader::new(fs::File::open(path)?))?;
+            let reader: MGFReaderType<_> = MGFReaderType::new_indexed(handle);
+            Ok(Box::new(reader))
+        }
+        MassSpectrometryFormat::MzML => {
+            let handle = RestartableDecoder::new(io::BufReader::new(fs::File::open(path)?))?;
+            let reader: MzMLReaderType<_> = MzMLReaderType::new_indexed(handle);
+            Ok(Box::new(reader))
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Compressed {} files are not supported", format),
+        )),
+    }
+}
 
 /// An abstraction over different ways to get a [`SpectrumSource`] from a file path,
 /// buffer, or pipe.
@@ -874,7 +930,7 @@ pub trait MassSpectrometryReadWriteProcess<
                     MassSpectrometryFormat::MGF => {
                         let handle = fs::File::open(read_path)?;
                         if is_gzipped {
-                            let fh = RestartableGzDecoder::new(io::BufReader::new(handle));
+                            let fh = RestartableDecoder::new(io::BufReader::new(handle))?;
                             let reader = StreamingSpectrumIterator::new(MGFReaderType::new(fh));
                             let reader = self.transform_reader(reader, format)?;
                             self.open_writer(reader, format, write_path)?;
@@ -889,7 +945,7 @@ pub trait MassSpectrometryReadWriteProcess<
                         let handle = fs::File::open(read_path)?;
 
                         if is_gzipped {
-                            let fh = RestartableGzDecoder::new(io::BufReader::new(handle));
+                            let fh = RestartableDecoder::new(io::BufReader::new(handle))?;
                             let reader = StreamingSpectrumIterator::new(MzMLReaderType::new(fh));
                             let reader = self.transform_reader(reader, format)?;
                             self.open_writer(reader, format, write_path)?;
@@ -972,7 +1028,7 @@ pub trait MassSpectrometryReadWriteProcess<
                     MassSpectrometryFormat::MGF => {
                         if compressed {
                             let reader = StreamingSpectrumIterator::new(MGFReaderType::new(
-                                RestartableGzDecoder::new(io::BufReader::new(buffered)),
+                                RestartableDecoder::new(io::BufReader::new(buffered))?,
                             ));
                             let reader = self.transform_reader(reader, ms_format)?;
                             self.open_writer(reader, ms_format, write_path)?;
@@ -986,7 +1042,7 @@ pub trait MassSpectrometryReadWriteProcess<
                     MassSpectrometryFormat::MzML => {
                         if compressed {
                             let reader = StreamingSpectrumIterator::new(MzMLReaderType::new(
-                                RestartableGzDecoder::new(io::BufReader::new(buffered)),
+                                RestartableDecoder::new(io::BufReader::new(buffered))?,
                             ));
                             let reader = self.transform_reader(reader, ms_format)?;
                             self.open_writer(reader, ms_format, write_path)?;
@@ -1235,6 +1291,48 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_open() -> io::Result<()> {
+        let mut reader = open("./test/data/small.mzML")?;
+        assert_eq!(reader.len(), 48);
+        let spec = reader.get_spectrum_by_index(10).unwrap();
+        assert_eq!(spec.id(), "controllerType=0 controllerNumber=1 scan=11");
+
+        let mut reader = open("./test/data/small.mgf")?;
+        assert_eq!(reader.len(), 34);
+        let spec = reader.get_spectrum_by_index(0).unwrap();
+        assert_eq!(spec.id(), "small.3.3.");
+
+        let mut reader = open("./test/data/small.mzML.gz")?;
+        assert_eq!(reader.len(), 48);
+        let spec = reader.get_spectrum_by_index(10).unwrap();
+        assert_eq!(spec.id(), "controllerType=0 controllerNumber=1 scan=11");
+        assert_eq!(reader.count(), 48);
+
+        let mut reader = open("./test/data/small.mgf.gz")?;
+        assert_eq!(reader.len(), 34);
+        let spec = reader.get_spectrum_by_index(0).unwrap();
+        assert_eq!(spec.id(), "small.3.3.");
+        assert_eq!(reader.count(), 34);
+        Ok(())
+    }
+
+    #[test]
+    fn infer_bzipped() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML.bz2");
+        assert!(path.exists());
+        let (fmt, zipped) = infer_from_path(path);
+        assert_eq!(fmt, MassSpectrometryFormat::MzML);
+        assert!(zipped);
+
+        let mut handle = fs::File::open(path)?;
+        let (fmt, zipped) = infer_from_stream(&mut handle)?;
+        assert_eq!(fmt, MassSpectrometryFormat::MzML);
+        assert!(zipped);
+        assert_eq!(handle.stream_position()?, 0);
+        Ok(())
+    }
+
     #[test]
     fn test_source_conv() -> io::Result<()> {
         let s = Source::<CentroidPeak, DeconvolutedPeak>::from("text/path".as_ref());
@@ -1,17 +1,20 @@
 /*!
 Read and write [MGF](https://www.matrixscience.com/help/data_file_help.html#GEN) files.
 Supports random access when reading from a source that supports [`io::Seek`].
+Gzip or bzip2-compressed files opened with [`MZFileReader::open_path`] are read
+through a decompressor, in order only.
 */
 
 use std::collections::HashMap;
 use std::convert::TryInto;
+use std::fmt::Display;
 use std::fs;
 use std::io::{self, prelude::*, BufWriter, SeekFrom};
 use std::marker::PhantomData;
 use std::mem;
 use std::str;
 
-use log::warn;
+use log::{debug, trace, warn};
 use thiserror::Error;
 
 use lazy_static::lazy_static;
@@ -21,6 +24,7 @@ use mzpeaks::{
 };
 use regex::Regex;
 
+use super::compression::MaybeCompressed;
 use super::traits::ChromatogramSource;
 use super::{
     offset_index::OffsetIndex,
@@ -28,7 +32,7 @@ use super::{
         MZFileReader, RandomAccessSpectrumIterator, SeekRead, SpectrumAccessError, SpectrumSource,
         SpectrumWriter,
     },
-    utils::DetailLevel,
+    utils::{cache_spectrum_summaries, truncate_to_most_intense, DetailLevel, ErrorPolicy},
 };
 
 use crate::meta::{
@@ -44,12 +48,13 @@ use crate::spectrum::{
         BuildFromArrayMap, DataArray,
     },
     spectrum_types::{
-        CentroidPeakAdapting, CentroidSpectrumType, DeconvolutedPeakAdapting, MultiLayerSpectrum,
+        CentroidPeakAdapting, CentroidSpectrumType, DeconvolutedPeakAdapting,
+        DeconvolutedSpectrumType, MultiLayerSpectrum,
     },
     IonProperties, Precursor, PrecursorSelection, RefPeakDataLevel, SelectedIon, SignalContinuity,
     SpectrumDescription, SpectrumLike,
 };
-use crate::utils::neutral_mass;
+use crate::utils::{minutes_to_seconds, neutral_mass, seconds_to_minutes};
 
 #[derive(PartialEq, Debug)]
 pub enum MGFParserState {
@@ -72,6 +77,8 @@ pub enum MGFError {
     MalformedHeaderLine,
     #[error("Too many columns for peak line encountered")]
     NotEnoughColumnsForPeakLine,
+    #[error("Random access is not supported on a compressed MGF file, it can only be read in order")]
+    RandomAccessUnsupported,
     #[error("Encountered an IO error: {0}")]
     IOError(
         #[from]
@@ -80,6 +87,15 @@ pub enum MGFError {
     ),
 }
 
+impl From<MGFError> for io::Error {
+    fn from(value: MGFError) -> Self {
+        match value {
+            MGFError::IOError(e) => e,
+            _ => io::Error::new(io::ErrorKind::InvalidData, value),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct SpectrumBuilder<
     C: CentroidPeakAdapting = CentroidPeak,
@@ -171,14 +187,7 @@ impl<C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> SpectrumBuilder<C, D>
                 self.mz_array
                     .into_iter()
                     .zip(self.intensity_array)
-                    .map(|(mz, inten)| {
-                        CentroidPeak {
-                            mz,
-                            intensity: inten,
-                            ..Default::default()
-                        }
-                        .into()
-                    })
+                    .map(|(mz, inten)| CentroidPeak::new(mz, inten, 0).into())
                     .collect(),
             )
         }
@@ -219,6 +228,7 @@ pub struct MGFReaderType<
 > {
     pub handle: io::BufReader<R>,
     pub state: MGFParserState,
+    /// The number of bytes consumed from the stream so far
     pub offset: usize,
     pub error: Option<MGFError>,
     index: OffsetIndex,
@@ -229,6 +239,28 @@ pub struct MGFReaderType<
     data_processings: Vec<DataProcessing>,
     run: MassSpectrometryRun,
     pub detail_level: DetailLevel,
+    /// The character separating the columns of a peak line. When [`None`], columns are
+    /// separated by any run of whitespace.
+    pub peak_separator: Option<char>,
+    /// Whether to accept `,` as a decimal separator in peak lines, as written by some locales.
+    /// This should not be combined with a `,` [`MGFReaderType::peak_separator`].
+    pub comma_decimal_separator: bool,
+    /// What to do when a spectrum fails to parse during iteration
+    pub error_policy: ErrorPolicy,
+    /// Whether to compute the total ion current, base peak and m/z range of each spectrum
+    /// and store them in its parameters while parsing
+    pub cache_summaries: bool,
+    /// The most peaks to keep in each spectrum, see [`MGFReaderType::set_max_peaks_per_spectrum`]
+    max_peaks_per_spectrum: Option<usize>,
+    /// The number of `BEGIN IONS` blocks started so far, used to identify spectra without
+    /// a `TITLE`
+    blocks_started: usize,
+    /// The MS level given to spectra whose block has no `MSLEVEL` header
+    default_ms_level: u8,
+    /// The byte offset of the `BEGIN IONS` line of the spectrum most recently started
+    spectrum_offset: Option<u64>,
+    /// Whether the file is read through a decompressor, which cannot seek to a spectrum
+    compressed: bool,
     centroid_type: PhantomData<C>,
     deconvoluted_type: PhantomData<D>,
 }
@@ -238,7 +270,29 @@ lazy_static! {
     static ref PEAK_SEPERATOR: Regex = Regex::new(r"\t|\s+").unwrap();
 }
 
+/// The identifier given to the `ordinal`th spectrum block of an MGF file when it
+/// has no `TITLE`
+fn untitled_spectrum_id(ordinal: usize) -> String {
+    format!("index={ordinal}")
+}
+
+/// Normalize the value of a `TITLE=` line into a spectrum identifier. Only the
+/// surrounding whitespace and line terminator are removed, so titles containing `=`
+/// are kept whole. This must be used by both the parser and the indexer so their
+/// identifiers agree.
+fn normalize_title(value: &str) -> &str {
+    value.trim()
+}
+
 impl<R: io::Read, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MGFReaderType<R, C, D> {
+    fn parse_peak_token<T: str::FromStr>(&self, token: &str) -> Option<T> {
+        if self.comma_decimal_separator && token.contains(',') {
+            token.replace(',', ".").parse().ok()
+        } else {
+            token.parse().ok()
+        }
+    }
+
     fn parse_peak_from_line(
         &mut self,
         line: &str,
@@ -247,7 +301,14 @@ impl<R: io::Read, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MGFReade
         let mut chars = line.chars();
         let first = chars.next().unwrap();
         if first.is_numeric() {
-            let mut it = line.split_ascii_whitespace();
+            let separator = self.peak_separator;
+            let mut it = line
+                .split(|c: char| match separator {
+                    Some(sep) => c == sep,
+                    None => c.is_ascii_whitespace(),
+                })
+                .map(|token| token.trim())
+                .filter(|token| !token.is_empty());
             let mz_token = it.next().unwrap();
             let mut intensity_token = "";
             let mut charge_token_opt = None;
@@ -269,13 +330,29 @@ impl<R: io::Read, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MGFReade
                 return None;
             }
             if !matches!(builder.detail_level, DetailLevel::MetadataOnly) {
-                let mz: f64 = mz_token.parse().unwrap();
-                let intensity: f32 = intensity_token.parse().unwrap();
+                let (mz, intensity) = match (
+                    self.parse_peak_token::<f64>(mz_token),
+                    self.parse_peak_token::<f32>(intensity_token),
+                ) {
+                    (Some(mz), Some(intensity)) => (mz, intensity),
+                    _ => {
+                        self.state = MGFParserState::Error;
+                        self.error = Some(MGFError::MalformedPeakLine);
+                        return None;
+                    }
+                };
                 builder.mz_array.push(mz);
                 builder.intensity_array.push(intensity);
 
                 if nparts == 3 {
-                    let charge = charge_token_opt.unwrap().parse().unwrap();
+                    let charge = match charge_token_opt.unwrap().parse() {
+                        Ok(charge) => charge,
+                        Err(_) => {
+                            self.state = MGFParserState::Error;
+                            self.error = Some(MGFError::MalformedPeakLine);
+                            return None;
+                        }
+                    };
                     builder.charge_array.push(charge);
                     builder.has_charge += 1;
                 } else {
@@ -297,25 +374,44 @@ impl<R: io::Read, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MGFReade
         } else if line == "END IONS" {
             self.state = MGFParserState::Between;
             true
-        } else if line.contains('=') {
-            let (key, value) = line.split_once('=').unwrap();
+        } else if let Some((key, value)) = line.split_once('=') {
             builder.empty_metadata = false;
             match key {
-                "TITLE" => builder.description.id = value.to_string(),
+                "TITLE" => builder.description.id = normalize_title(value).to_string(),
                 "RTINSECONDS" => {
+                    let time = match value.trim().parse::<f64>() {
+                        Ok(time) => time,
+                        Err(_) => return self.malformed_header(line),
+                    };
                     let scan_ev = builder
                         .description
                         .acquisition
                         .first_scan_mut()
                         .expect("Automatically adds scan event");
-                    scan_ev.start_time = value.parse::<f64>().unwrap() / 60.0
+                    scan_ev.start_time = seconds_to_minutes(time)
                 }
+                "MSLEVEL" => match value.trim().parse() {
+                    Ok(level) => builder.description.ms_level = level,
+                    Err(_) => {
+                        warn!("Failed to parse MSLEVEL {value}");
+                    }
+                },
                 "PEPMASS" => {
                     let mut parts = value.split_ascii_whitespace();
-                    let mz: f64 = parts.next().unwrap().parse().unwrap();
-                    let intensity: f32 =
-                        parts.next().map(|v| v.parse().unwrap()).unwrap_or_default();
-                    let charge: Option<i32> = parts.next().map(|c| c.parse().unwrap());
+                    let mz: f64 = match parts.next().map(|v| v.parse()) {
+                        Some(Ok(mz)) => mz,
+                        _ => return self.malformed_header(line),
+                    };
+                    let intensity: f32 = match parts.next().map(|v| v.parse()) {
+                        Some(Ok(intensity)) => intensity,
+                        Some(Err(_)) => return self.malformed_header(line),
+                        None => Default::default(),
+                    };
+                    let charge: Option<i32> = match parts.next().map(|c| c.parse()) {
+                        Some(Ok(charge)) => Some(charge),
+                        Some(Err(_)) => return self.malformed_header(line),
+                        None => None,
+                    };
                     builder.description.precursor = Some(Precursor {
                         ions: vec![SelectedIon {
                             mz,
@@ -327,6 +423,7 @@ impl<R: io::Read, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MGFReade
                     });
                 }
                 &_ => {
+                    trace!("Storing unrecognized MGF header {key} as a user parameter");
                     builder
                         .description
                         .add_param(Param::new_key_value(key.to_lowercase(), value));
@@ -335,12 +432,18 @@ impl<R: io::Read, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MGFReade
 
             true
         } else {
-            self.state = MGFParserState::Error;
-            self.error = Some(MGFError::MalformedHeaderLine);
-            false
+            self.malformed_header(line)
         }
     }
 
+    /// Put the parser into the error state for a header line that could not be parsed
+    fn malformed_header(&mut self, line: &str) -> bool {
+        debug!("Malformed MGF header line {line:?}");
+        self.state = MGFParserState::Error;
+        self.error = Some(MGFError::MalformedHeaderLine);
+        false
+    }
+
     fn handle_peak(&mut self, line: &str, builder: &mut SpectrumBuilder<C, D>) -> bool {
         let peak_line = self.parse_peak_from_line(line, builder).unwrap_or(false);
         if peak_line {
@@ -367,6 +470,7 @@ impl<R: io::Read, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MGFReade
             }
         } else if line == "BEGIN IONS" {
             self.state = MGFParserState::ScanHeaders;
+            self.blocks_started += 1;
             true
         } else {
             false
@@ -376,29 +480,56 @@ impl<R: io::Read, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MGFReade
     fn handle_between(&mut self, line: &str) -> bool {
         if line == "BEGIN IONS" {
             self.state = MGFParserState::ScanHeaders;
+            self.blocks_started += 1;
         }
         true
     }
 
     fn read_line(&mut self, buffer: &mut String) -> io::Result<usize> {
-        self.handle.read_line(buffer)
+        let b = self.handle.read_line(buffer)?;
+        self.offset += b;
+        Ok(b)
     }
 
-    /// Read the next spectrum from the file, if there is one.
-    pub fn read_next(&mut self) -> Option<MultiLayerSpectrum<C, D>> {
+    fn read_next_result(&mut self) -> Result<Option<MultiLayerSpectrum<C, D>>, MGFError> {
         let mut builder = SpectrumBuilder::<C, D>::default();
-        match self._parse_into(&mut builder) {
-            Ok((_, started_spectrum)) => {
-                if started_spectrum && !builder.is_empty() {
-                    Some(builder.into())
-                } else {
-                    None
-                }
+        builder.description.ms_level = self.default_ms_level;
+        let (_, started_spectrum) = self._parse_into(&mut builder)?;
+        if started_spectrum && !builder.is_empty() {
+            let mut spectrum: MultiLayerSpectrum<C, D> = builder.into();
+            if let Some(max_peaks) = self.max_peaks_per_spectrum {
+                truncate_to_most_intense(&mut spectrum, max_peaks);
+            }
+            if self.cache_summaries {
+                cache_spectrum_summaries(&mut spectrum);
             }
-            Err(_err) => None,
+            Ok(Some(spectrum))
+        } else {
+            Ok(None)
         }
     }
 
+    /// Read the next spectrum from the file, if there is one.
+    pub fn read_next(&mut self) -> Option<MultiLayerSpectrum<C, D>> {
+        self.read_next_result().ok().flatten()
+    }
+
+    /// Iterate over the remaining spectra, pairing each with the byte offset of the
+    /// `BEGIN IONS` line that starts it.
+    ///
+    /// The offsets are counted from the bytes this reader has consumed, so they are
+    /// available without building an index.
+    pub fn iter_with_offsets(
+        &mut self,
+    ) -> impl Iterator<Item = (u64, MultiLayerSpectrum<C, D>)> + '_ {
+        std::iter::from_fn(move || {
+            self.spectrum_offset = None;
+            let spectrum = self.read_next()?;
+            let offset = self.spectrum_offset?;
+            Some((offset, spectrum))
+        })
+    }
+
     /// Read the next spectrum's contents directly into the passed [`SpectrumBuilder`].
     fn _parse_into(
         &mut self,
@@ -439,6 +570,15 @@ impl<R: io::Read, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MGFReade
                 continue;
             }
 
+            if line == "BEGIN IONS"
+                && matches!(
+                    self.state,
+                    MGFParserState::Start | MGFParserState::FileHeader | MGFParserState::Between
+                )
+            {
+                self.spectrum_offset = Some((self.offset - b) as u64);
+            }
+
             work = match self.state {
                 MGFParserState::Start | MGFParserState::FileHeader => self.handle_start(line),
                 MGFParserState::ScanHeaders => {
@@ -456,6 +596,18 @@ impl<R: io::Read, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MGFReade
                 }
             };
         }
+        if self.state == MGFParserState::Error {
+            if let Some(err) = self.error.take() {
+                return Err(err);
+            }
+        }
+        if had_begin_ions {
+            let ordinal = self.blocks_started.saturating_sub(1);
+            builder.description.index = ordinal;
+            if builder.description.id.is_empty() {
+                builder.description.id = untitled_spectrum_id(ordinal);
+            }
+        }
         Ok((offset, had_begin_ions))
     }
 
@@ -464,6 +616,7 @@ impl<R: io::Read, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MGFReade
         spectrum: &mut MultiLayerSpectrum<C, D>,
     ) -> Result<usize, MGFError> {
         let mut accumulator = SpectrumBuilder::default();
+        accumulator.description.ms_level = self.default_ms_level;
         match self._parse_into(&mut accumulator) {
             Ok((sz, started_spectrum)) => {
                 if !started_spectrum {
@@ -473,6 +626,12 @@ impl<R: io::Read, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MGFReade
                     )))
                 } else {
                     accumulator.into_spectrum(spectrum);
+                    if let Some(max_peaks) = self.max_peaks_per_spectrum {
+                        truncate_to_most_intense(spectrum, max_peaks);
+                    }
+                    if self.cache_summaries {
+                        cache_spectrum_summaries(spectrum);
+                    }
                     Ok(sz)
                 }
             }
@@ -507,9 +666,47 @@ impl<R: io::Read, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MGFReade
             samples: Vec::new(),
             file_description: Self::default_file_description(),
             detail_level: DetailLevel::Full,
+            peak_separator: None,
+            comma_decimal_separator: false,
+            error_policy: ErrorPolicy::Stop,
+            cache_summaries: false,
+            max_peaks_per_spectrum: None,
+            blocks_started: 0,
+            default_ms_level: 2,
+            spectrum_offset: None,
+            compressed: false,
             run: MassSpectrometryRun::default(),
         }
     }
+
+    /// The MS level given to spectra whose block doesn't have an `MSLEVEL` header, 2 unless
+    /// changed with [`MGFReaderType::set_default_ms_level`]
+    pub fn default_ms_level(&self) -> u8 {
+        self.default_ms_level
+    }
+
+    /// Set the MS level given to spectra whose block doesn't have an `MSLEVEL` header, e.g.
+    /// to read a file of MS1 peak lists
+    pub fn set_default_ms_level(&mut self, level: u8) {
+        self.default_ms_level = level;
+    }
+
+    /// The most peaks kept in each spectrum read, if capped with
+    /// [`MGFReaderType::set_max_peaks_per_spectrum`]
+    pub fn max_peaks_per_spectrum(&self) -> Option<usize> {
+        self.max_peaks_per_spectrum
+    }
+
+    /// Cap the number of peaks kept in each spectrum read to bound the memory held by
+    /// spectra from pathological files, or remove the cap with [`None`].
+    ///
+    /// Spectra with more peaks than `cap` keep only the `cap` most intense, and record
+    /// their original count in a `truncated from peak count` user parameter. Each spectrum
+    /// is truncated after it is read, so this does not reduce the peak memory used while
+    /// reading one.
+    pub fn set_max_peaks_per_spectrum(&mut self, cap: Option<usize>) {
+        self.max_peaks_per_spectrum = cap;
+    }
 }
 
 impl<R: io::Read, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> Iterator
@@ -518,8 +715,24 @@ impl<R: io::Read, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> Iterator
     type Item = MultiLayerSpectrum<C, D>;
 
     /// Read the next spectrum from the file.
+    ///
+    /// If a spectrum fails to parse and [`MGFReaderType::error_policy`] is
+    /// [`ErrorPolicy::SkipAndContinue`], the rest of that spectrum is skipped
+    /// and the next one is read instead.
     fn next(&mut self) -> Option<Self::Item> {
-        self.read_next()
+        loop {
+            match self.read_next_result() {
+                Ok(spectrum) => return spectrum,
+                Err(MGFError::IOError(_)) => return None,
+                Err(err) => match self.error_policy {
+                    ErrorPolicy::Stop => return None,
+                    ErrorPolicy::SkipAndContinue => {
+                        warn!("Skipping malformed spectrum: {err}");
+                        self.state = MGFParserState::Between;
+                    }
+                },
+            }
+        }
     }
 }
 
@@ -532,8 +745,23 @@ impl<R: SeekRead, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MGFReade
         reader
     }
 
+    /// Refuse random access on a compressed file, recording why in [`MGFReaderType::error`]
+    fn check_random_access(&mut self) -> Result<(), SpectrumAccessError> {
+        if self.compressed {
+            self.error = Some(MGFError::RandomAccessUnsupported);
+            Err(SpectrumAccessError::IOError(Some(io::Error::new(
+                io::ErrorKind::Unsupported,
+                MGFError::RandomAccessUnsupported,
+            ))))
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
-        self.handle.seek(pos)
+        let position = self.handle.seek(pos)?;
+        self.offset = position as usize;
+        Ok(position)
     }
 
     /// Builds an offset index to each `BEGIN IONS` line
@@ -543,6 +771,7 @@ impl<R: SeekRead, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MGFReade
         let mut last_start: u64 = 0;
 
         let mut found_start = false;
+        let mut blocks: usize = 0;
 
         let start = self
             .handle
@@ -567,15 +796,21 @@ impl<R: SeekRead, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MGFReade
             if buffer.starts_with(b"BEGIN IONS") {
                 found_start = true;
                 last_start = offset;
+                blocks += 1;
             } else if found_start && buffer.starts_with(b"TITLE=") {
                 match str::from_utf8(&buffer[6..]) {
                     Ok(string) => {
-                        self.index.insert(string.to_owned(), last_start);
+                        self.index.insert(normalize_title(string), last_start);
                     }
                     Err(_err) => {}
                 };
                 found_start = false;
                 last_start = 0;
+            } else if found_start && buffer.starts_with(b"END IONS") {
+                self.index
+                    .insert(untitled_spectrum_id(blocks - 1), last_start);
+                found_start = false;
+                last_start = 0;
             }
             offset += b as u64;
         }
@@ -584,6 +819,11 @@ impl<R: SeekRead, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MGFReade
         self.index.init = true;
         if self.index.is_empty() {
             warn!("An index was built but no entries were found")
+        } else {
+            debug!(
+                "Built an index of {} spectra over {offset} bytes",
+                self.index.len()
+            );
         }
         offset
     }
@@ -594,17 +834,20 @@ impl<R: SeekRead, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting>
 {
     /// Retrieve a spectrum by it's native ID
     fn get_spectrum_by_id(&mut self, id: &str) -> Option<MultiLayerSpectrum<C, D>> {
+        self.check_random_access().ok()?;
         let offset = self.index.get(id)?;
         let index = self.index.index_of(id)?;
         let start = self
             .handle
             .stream_position()
             .expect("Failed to save checkpoint");
+        let blocks_started = mem::replace(&mut self.blocks_started, index);
         self.seek(SeekFrom::Start(offset))
             .expect("Failed to move seek to offset");
         let result = self.read_next();
         self.seek(SeekFrom::Start(start))
             .expect("Failed to restore offset");
+        self.blocks_started = blocks_started;
         result.map(|mut scan| {
                 scan.description.index = index;
             scan
@@ -613,16 +856,19 @@ impl<R: SeekRead, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting>
 
     /// Retrieve a spectrum by it's integer index
     fn get_spectrum_by_index(&mut self, index: usize) -> Option<MultiLayerSpectrum<C, D>> {
+        self.check_random_access().ok()?;
         let (_id, offset) = self.index.get_index(index)?;
         let byte_offset = offset;
         let start = self
             .handle
             .stream_position()
             .expect("Failed to save checkpoint");
+        let blocks_started = mem::replace(&mut self.blocks_started, index);
         self.seek(SeekFrom::Start(byte_offset)).ok()?;
         let result = self.read_next();
         self.seek(SeekFrom::Start(start))
             .expect("Failed to restore offset");
+        self.blocks_started = blocks_started;
         match result {
             Some(mut scan) => {
                 scan.description.index = index;
@@ -636,6 +882,7 @@ impl<R: SeekRead, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting>
     fn reset(&mut self) {
         self.seek(SeekFrom::Start(0))
             .expect("Failed to reset file stream");
+        self.blocks_started = 0;
     }
 
     fn get_index(&self) -> &OffsetIndex {
@@ -654,9 +901,13 @@ impl<R: SeekRead, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting>
     RandomAccessSpectrumIterator<C, D, MultiLayerSpectrum<C, D>> for MGFReaderType<R, C, D>
 {
     fn start_from_id(&mut self, id: &str) -> Result<&mut Self, SpectrumAccessError> {
+        self.check_random_access()?;
         match self._offset_of_id(id) {
             Some(offset) => match self.seek(SeekFrom::Start(offset)) {
-                Ok(_) => Ok(self),
+                Ok(_) => {
+                    self.blocks_started = self.index.index_of(id).unwrap_or_default();
+                    Ok(self)
+                }
                 Err(err) => Err(SpectrumAccessError::IOError(Some(err))),
             },
             None => Err(SpectrumAccessError::SpectrumIdNotFound(id.to_string())),
@@ -664,9 +915,13 @@ impl<R: SeekRead, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting>
     }
 
     fn start_from_index(&mut self, index: usize) -> Result<&mut Self, SpectrumAccessError> {
+        self.check_random_access()?;
         match self._offset_of_index(index) {
             Some(offset) => match self.seek(SeekFrom::Start(offset)) {
-                Ok(_) => Ok(self),
+                Ok(_) => {
+                    self.blocks_started = index;
+                    Ok(self)
+                }
                 Err(err) => Err(SpectrumAccessError::IOError(Some(err))),
             },
             None => Err(SpectrumAccessError::SpectrumIndexNotFound(index)),
@@ -674,9 +929,17 @@ impl<R: SeekRead, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting>
     }
 
     fn start_from_time(&mut self, time: f64) -> Result<&mut Self, SpectrumAccessError> {
+        self.check_random_access()?;
         match self._offset_of_time(time) {
             Some(offset) => match self.seek(SeekFrom::Start(offset)) {
-                Ok(_) => Ok(self),
+                Ok(_) => {
+                    self.blocks_started = self
+                        .index
+                        .iter()
+                        .position(|(_, o)| *o == offset)
+                        .unwrap_or_default();
+                    Ok(self)
+                }
                 Err(err) => Err(SpectrumAccessError::IOError(Some(err))),
             },
             None => Err(SpectrumAccessError::SpectrumNotFound),
@@ -685,14 +948,30 @@ impl<R: SeekRead, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting>
 }
 
 impl<C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting>
-    MZFileReader<C, D, MultiLayerSpectrum<C, D>> for MGFReaderType<fs::File, C, D>
+    MZFileReader<C, D, MultiLayerSpectrum<C, D>>
+    for MGFReaderType<MaybeCompressed<fs::File>, C, D>
 {
+    /// Open an MGF file, reading it through a decompressor if it is gzip or
+    /// bzip2-compressed, like `spectra.mgf.gz`.
+    ///
+    /// A compressed file can only be read in order, so it is not indexed, and random
+    /// access like [`SpectrumSource::get_spectrum_by_index`] or
+    /// [`RandomAccessSpectrumIterator::start_from_index`] fails with
+    /// [`MGFError::RandomAccessUnsupported`].
     fn open_file(source: fs::File) -> io::Result<Self> {
-        Ok(Self::new(source))
+        let handle = MaybeCompressed::new(source)?;
+        let compressed = handle.is_compressed();
+        let mut reader = Self::new(handle);
+        reader.compressed = compressed;
+        Ok(reader)
     }
 
     fn construct_index_from_stream(&mut self) -> u64 {
-        self.build_index()
+        if self.compressed {
+            0
+        } else {
+            self.build_index()
+        }
     }
 }
 
@@ -799,7 +1078,8 @@ impl MGFHeaderStyle for MZDataMGFStyle {
     }
 }
 
-/// An MGF writer type that only writes centroided MSn spectra.
+/// An MGF writer type that only writes centroided MSn spectra, unless MS1 peak lists are
+/// enabled with [`MGFWriterType::set_include_ms1`].
 ///
 /// To customize the way that spectrum metadata is written, provide
 /// a type implementing [`MGFHeaderStyle`]. The default style, [`MZDataMGFStyle`]
@@ -821,6 +1101,20 @@ pub struct MGFWriterType<
     data_processings: Vec<DataProcessing>,
     style_type: PhantomData<Y>,
     run: MassSpectrometryRun,
+    /// The number of decimal places to write m/z values with, or `None` to write
+    /// the shortest representation that round-trips. Defaults to 6.
+    pub mz_precision: Option<usize>,
+    /// The number of decimal places to write intensity values with, or `None` to
+    /// write the shortest representation that round-trips. Defaults to 2.
+    pub intensity_precision: Option<usize>,
+    include_ms1: bool,
+}
+
+fn format_with_precision<T: Display>(value: T, precision: Option<usize>) -> String {
+    match precision {
+        Some(precision) => format!("{:.*}", precision, value),
+        None => value.to_string(),
+    }
 }
 
 impl<W: io::Write, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting, Y: MGFHeaderStyle>
@@ -840,9 +1134,45 @@ impl<W: io::Write, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting, Y: MGFH
             data_processings: Default::default(),
             run: Default::default(),
             style_type: PhantomData,
+            mz_precision: Some(6),
+            intensity_precision: Some(2),
+            include_ms1: false,
         }
     }
 
+    /// Whether MS1 spectra are written, rather than skipped. Defaults to `false`.
+    pub fn include_ms1(&self) -> bool {
+        self.include_ms1
+    }
+
+    /// Set whether MS1 spectra are written, e.g. to write a file of MS1 peak lists.
+    ///
+    /// MS1 spectra are written with an `MSLEVEL=1` header so they are read back as MS1
+    /// spectra. They must still be centroided.
+    pub fn set_include_ms1(&mut self, include_ms1: bool) {
+        self.include_ms1 = include_ms1;
+    }
+
+    /// Set the number of decimal places used when writing m/z and intensity values.
+    /// `None` writes the shortest representation that round-trips.
+    pub fn set_precision(
+        &mut self,
+        mz_precision: Option<usize>,
+        intensity_precision: Option<usize>,
+    ) {
+        self.mz_precision = mz_precision;
+        self.intensity_precision = intensity_precision;
+    }
+
+    fn write_peak_values(&mut self, mz: f64, intensity: f32) -> io::Result<()> {
+        self.handle
+            .write_all(format_with_precision(mz, self.mz_precision).as_bytes())?;
+        self.handle.write_all(b" ")?;
+        self.handle
+            .write_all(format_with_precision(intensity, self.intensity_precision).as_bytes())?;
+        Ok(())
+    }
+
     /// Format a spectrum title similarly to the [Trans-Proteomic Pipeline](https://tools.proteomecenter.org/software.php)
     /// compatibility.
     pub fn make_title<S: SpectrumLike<C, D>>(&self, spectrum: &S) -> String {
@@ -930,16 +1260,30 @@ impl<W: io::Write, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting, Y: MGFH
             .map(|p| (p.value.clone(), true))
             .unwrap_or_else(|| (self.make_title(spectrum).into(), false));
         self.handle.write_all(&title.as_bytes())?;
-        self.handle.write_all(b"\nRTINSECONDS=")?;
-        self.handle
-            .write_all((spectrum.start_time() * 60.0).to_string().as_bytes())?;
         self.handle.write_all(b"\n")?;
+        if let Some(time) = spectrum.retention_time() {
+            self.handle.write_all(b"RTINSECONDS=")?;
+            self.handle
+                .write_all(minutes_to_seconds(time).to_string().as_bytes())?;
+            self.handle.write_all(b"\n")?;
+        }
+        if desc.ms_level == 1 {
+            self.write_kv("MSLEVEL", "1")?;
+        }
         match &desc.precursor {
             Some(precursor) => {
                 self.write_precursor(precursor)?;
             }
             None => {}
         }
+        // Deconvoluted peaks are written with their charges, so state the precursor's too
+        if let RefPeakDataLevel::Deconvoluted(_) = spectrum.peaks() {
+            let charge = desc.precursor.as_ref().and_then(|prec| prec.ion().charge());
+            if let (Some(z), None) = (charge, desc.get_param_by_name("charge")) {
+                let sign = if z < 0 { '-' } else { '+' };
+                self.write_kv("CHARGE", &format!("{}{}", z.abs(), sign))?;
+            }
+        }
 
         Y::write_header(self, spectrum)?;
         Ok(())
@@ -950,10 +1294,7 @@ impl<W: io::Write, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting, Y: MGFH
             centroids.iter().map(|p| p.as_centroid()).collect();
         centroids.sort_by(|a, b| a.mz().total_cmp(&b.mz()));
         for peak in centroids.into_iter() {
-            self.handle.write_all(peak.mz().to_string().as_bytes())?;
-            self.handle.write_all(b" ")?;
-            self.handle
-                .write_all(peak.intensity().to_string().as_bytes())?;
+            self.write_peak_values(peak.mz(), peak.intensity())?;
             self.handle.write_all(b" ")?;
             self.handle
                 .write_all(peak.charge().to_string().as_bytes())?;
@@ -964,10 +1305,7 @@ impl<W: io::Write, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting, Y: MGFH
 
     fn write_centroids(&mut self, centroids: &[C]) -> io::Result<()> {
         for peak in centroids {
-            self.handle.write_all(peak.mz().to_string().as_bytes())?;
-            self.handle.write_all(b" ")?;
-            self.handle
-                .write_all(peak.intensity().to_string().as_bytes())?;
+            self.write_peak_values(peak.mz(), peak.intensity())?;
             self.handle.write_all(b"\n")?;
         }
         Ok(())
@@ -981,9 +1319,7 @@ impl<W: io::Write, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting, Y: MGFH
         match description.signal_continuity {
             SignalContinuity::Centroid => {
                 for (mz, inten) in arrays.mzs()?.iter().zip(arrays.intensities()?.iter()) {
-                    self.handle.write_all(mz.to_string().as_bytes())?;
-                    self.handle.write_all(b" ")?;
-                    self.handle.write_all(inten.to_string().as_bytes())?;
+                    self.write_peak_values(*mz, *inten)?;
                     self.handle.write_all(b"\n")?;
                 }
             }
@@ -1024,9 +1360,10 @@ impl<W: io::Write, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting, Y: MGFH
     }
 
     /// Write a spectrum from start to finish. It will skip spectra where `ms_level() == 1`
+    /// unless [`MGFWriterType::include_ms1`] is set.
     pub fn write<S: SpectrumLike<C, D> + 'static>(&mut self, spectrum: &S) -> io::Result<usize> {
         let description = spectrum.description();
-        if description.ms_level == 1 {
+        if description.ms_level == 1 && !self.include_ms1 {
             log::warn!(
                 "Attempted to write an MS1 spectrum to MGF, {}, skipping.",
                 description.id
@@ -1045,6 +1382,23 @@ TITLE="#,
     }
 }
 
+impl<W: io::Write, D: DeconvolutedPeakAdapting + 'static, Y: MGFHeaderStyle>
+    MGFWriterType<W, CentroidPeak, D, Y>
+{
+    /// Write a charge deconvolved spectrum from start to finish. Each peak is written as
+    /// an `m/z intensity charge` line, preserving its charge state, and the precursor
+    /// charge, if known, is written as the `CHARGE` header.
+    ///
+    /// This is [`MGFWriterType::write`], which does the same for any spectrum whose
+    /// peaks are deconvoluted, so it will also skip spectra where `ms_level() == 1`.
+    pub fn write_deconvoluted(
+        &mut self,
+        spectrum: &DeconvolutedSpectrumType<D>,
+    ) -> io::Result<usize> {
+        self.write(spectrum)
+    }
+}
+
 impl<W: io::Write, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting, Y: MGFHeaderStyle>
     MSDataFileMetadata for MGFWriterType<W, C, D, Y>
 {
@@ -1063,7 +1417,7 @@ impl<W: io::Write, C: CentroidPeakAdapting + 'static, D: DeconvolutedPeakAdaptin
     SpectrumWriter<C, D> for MGFWriterType<W, C, D>
 {
     fn write<S: SpectrumLike<C, D> + 'static>(&mut self, spectrum: &S) -> io::Result<usize> {
-        if spectrum.ms_level() != 1 {
+        if spectrum.ms_level() != 1 || self.include_ms1 {
             self.write(spectrum)
         } else {
             log::trace!("Skipping writing MS1 spectrum {} to MGF", spectrum.id());
@@ -1079,6 +1433,9 @@ impl<W: io::Write, C: CentroidPeakAdapting + 'static, D: DeconvolutedPeakAdaptin
         group: &G,
     ) -> io::Result<usize> {
         let mut c = 0;
+        if let Some(s) = group.precursor().filter(|_| self.include_ms1) {
+            c += self.write(s)?;
+        }
         for s in group.products() {
             c += self.write(s)?;
         }
@@ -1099,9 +1456,9 @@ pub type MGFWriter<W> = MGFWriterType<W, CentroidPeak, DeconvolutedPeak, MZDataM
 
 #[cfg(test)]
 mod test {
-    use mzpeaks::IndexedCoordinate;
+    use mzpeaks::{prelude::PeakCollectionMut, IndexedCoordinate, Tolerance};
 
-    use crate::CentroidSpectrum;
+    use crate::{curie, CentroidSpectrum};
 
     use super::*;
     use std::fs;
@@ -1126,6 +1483,46 @@ mod test {
         assert_eq!(msn_count, 34);
     }
 
+    #[test]
+    fn test_reader_gzipped() -> io::Result<()> {
+        let mut reader = MGFReader::open_path("./test/data/small.mgf.gz")?;
+        let expected: Vec<_> = MGFReader::open_path("./test/data/small.mgf")?.collect();
+
+        let spectra: Vec<_> = reader.by_ref().collect();
+        assert_eq!(spectra.len(), 34);
+        for (spectrum, expected) in spectra.iter().zip(expected.iter()) {
+            assert_eq!(spectrum.id(), expected.id());
+            assert_eq!(spectrum.peaks().len(), expected.peaks().len());
+        }
+
+        // The compressed stream can be read again from the start, but not jumped into
+        reader.reset();
+        assert_eq!(reader.next().unwrap().id(), expected[0].id());
+        assert!(reader.get_spectrum_by_index(10).is_none());
+        assert!(matches!(
+            reader.error,
+            Some(MGFError::RandomAccessUnsupported)
+        ));
+        match reader.start_from_index(10) {
+            Err(SpectrumAccessError::IOError(Some(e))) => {
+                assert_eq!(e.kind(), io::ErrorKind::Unsupported)
+            }
+            Err(e) => panic!("Expected random access to be unsupported, got {e}"),
+            Ok(_) => panic!("Expected random access to be unsupported"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_reader_spectrum_index() {
+        let path = path::Path::new("./test/data/small.mgf");
+        let file = fs::File::open(path).expect("Test file doesn't exist");
+        let reader = MGFReaderType::<_>::new(file);
+        for (i, scan) in reader.enumerate() {
+            assert_eq!(scan.index(), i);
+        }
+    }
+
     #[test]
     fn test_reader_indexed() {
         let path = path::Path::new("./test/data/small.mgf");
@@ -1153,6 +1550,304 @@ mod test {
         assert_eq!(msn_count, 34);
     }
 
+    #[test]
+    fn test_spectrum_ids() {
+        let path = path::Path::new("./test/data/small.mgf");
+        let file = fs::File::open(path).expect("Test file doesn't exist");
+        let mut reader = MGFReaderType::<_, CentroidPeak, DeconvolutedPeak>::new_indexed(file);
+
+        let ids: Vec<String> = reader.spectrum_ids().map(|s| s.to_string()).collect();
+        assert_eq!(ids.len(), 34);
+        assert_eq!(ids[0], "small.3.3.");
+        assert_eq!(ids[1], "small.4.4.");
+
+        for (i, id) in ids.iter().enumerate() {
+            let scan = reader.get_spectrum_by_id(id).expect("Missing spectrum");
+            assert_eq!(scan.id(), id);
+            assert_eq!(scan.index(), i);
+        }
+    }
+
+    #[test]
+    fn test_peak_line_formats() {
+        let peaks_of = |spectrum: &MultiLayerSpectrum| -> Vec<(f64, f32)> {
+            spectrum
+                .peaks
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|p| (p.mz, p.intensity))
+                .collect()
+        };
+
+        let text = "BEGIN IONS\nTITLE=comma\nPEPMASS=500.0\n445,12\t100\n500,5\t2,5e3\nEND IONS\n\
+                    BEGIN IONS\nTITLE=scientific\nPEPMASS=500.0\n4.4512e2 1e5\nEND IONS\n";
+        let mut reader = MGFReader::new(io::Cursor::new(text.as_bytes()));
+        reader.comma_decimal_separator = true;
+        let spectra: Vec<_> = reader.collect();
+        assert_eq!(spectra.len(), 2);
+        assert_eq!(
+            peaks_of(&spectra[0]),
+            vec![(445.12, 100.0), (500.5, 2500.0)]
+        );
+        assert_eq!(peaks_of(&spectra[1]), vec![(445.12, 1e5)]);
+
+        let text = "BEGIN IONS\nTITLE=semicolon\nPEPMASS=500.0\n445.12;100\n500.5 ; 20\nEND IONS\n";
+        let mut reader = MGFReader::new(io::Cursor::new(text.as_bytes()));
+        reader.peak_separator = Some(';');
+        let spectra: Vec<_> = reader.collect();
+        assert_eq!(spectra.len(), 1);
+        assert_eq!(peaks_of(&spectra[0]), vec![(445.12, 100.0), (500.5, 20.0)]);
+    }
+
+    #[test]
+    fn test_cache_summaries() {
+        use crate::params::ParamValue;
+
+        let path = path::Path::new("./test/data/small.mgf");
+        let mut reader = MGFReader::new(fs::File::open(path).unwrap());
+        let scan = reader.next().unwrap();
+        assert!(scan.get_param_by_curie(&curie!(MS:1000285)).is_none());
+
+        let mut reader = MGFReader::new(fs::File::open(path).unwrap());
+        reader.cache_summaries = true;
+        let scan = reader.next().unwrap();
+        let tic = scan.get_param_by_curie(&curie!(MS:1000285)).unwrap();
+        assert!((tic.to_f32().unwrap() - scan.peaks().tic()).abs() < 1e-3);
+        let bpmz = scan.get_param_by_curie(&curie!(MS:1000504)).unwrap();
+        assert_eq!(bpmz.to_f64().unwrap(), scan.peaks().base_peak().mz);
+        assert!(scan.get_param_by_curie(&curie!(MS:1000505)).is_some());
+    }
+
+    #[test]
+    fn test_max_peaks_per_spectrum() {
+        let text = "BEGIN IONS\nTITLE=many\nPEPMASS=500.0\n100.0 10\n200.0 40\n300.0 20\n400.0 30\nEND IONS\nBEGIN IONS\nTITLE=few\nPEPMASS=500.0\n100.0 10\nEND IONS\n";
+        let mut reader = MGFReader::new(io::Cursor::new(text.as_bytes()));
+        reader.set_max_peaks_per_spectrum(Some(2));
+        assert_eq!(reader.max_peaks_per_spectrum(), Some(2));
+
+        let spectrum = reader.next().unwrap();
+        let peaks = spectrum.peaks.as_ref().unwrap();
+        let mzs: Vec<f64> = peaks.iter().map(|p| p.mz).collect();
+        assert_eq!(mzs, vec![200.0, 400.0]);
+        let flag = spectrum
+            .description()
+            .params
+            .iter()
+            .find(|p| p.name == "truncated from peak count")
+            .unwrap();
+        assert_eq!(flag.value, 4i64);
+
+        let spectrum = reader.next().unwrap();
+        assert_eq!(spectrum.peaks.as_ref().unwrap().len(), 1);
+        assert!(!spectrum
+            .description()
+            .params
+            .iter()
+            .any(|p| p.name == "truncated from peak count"));
+    }
+
+    #[test]
+    fn test_unsorted_peaks() {
+        let text = "BEGIN IONS\nTITLE=unsorted\nPEPMASS=500.0\n300.0 30\n100.0 10\n400.0 40\n200.0 20\nEND IONS\n";
+        let mut reader = MGFReader::new(io::Cursor::new(text.as_bytes()));
+        let spectrum = reader.next().unwrap();
+        let mut peaks = spectrum.peaks.unwrap();
+        let mzs: Vec<_> = peaks.iter().map(|p| p.mz).collect();
+        assert_eq!(mzs, vec![100.0, 200.0, 300.0, 400.0]);
+        for (i, p) in peaks.iter().enumerate() {
+            assert_eq!(p.get_index() as usize, i);
+        }
+        assert_eq!(peaks.search(300.0, Tolerance::PPM(5.0)), Some(2));
+
+        // Pushing a peak out of order re-sorts the peak set
+        peaks.push(CentroidPeak::new(150.0, 15.0, 0));
+        let mzs: Vec<_> = peaks.iter().map(|p| p.mz).collect();
+        assert_eq!(mzs, vec![100.0, 150.0, 200.0, 300.0, 400.0]);
+        assert_eq!(peaks.search(300.0, Tolerance::PPM(5.0)), Some(3));
+        assert_eq!(peaks.search(150.0, Tolerance::PPM(5.0)), Some(1));
+    }
+
+    #[test]
+    fn test_skip_corrupt_spectrum() {
+        let text = "BEGIN IONS\nTITLE=first\nPEPMASS=500.0\n100.0 10\nEND IONS\n\
+                    BEGIN IONS\nTITLE=corrupt\nPEPMASS=500.0\n100.0 10\n2x0.0 5\n300.0 1\nEND IONS\n\
+                    BEGIN IONS\nTITLE=last\nPEPMASS=500.0\n100.0 10\nEND IONS\n";
+
+        let reader = MGFReader::new(io::Cursor::new(text.as_bytes()));
+        let ids: Vec<_> = reader.map(|s| s.id().to_string()).collect();
+        assert_eq!(ids, vec!["first"]);
+
+        let mut reader = MGFReader::new(io::Cursor::new(text.as_bytes()));
+        reader.error_policy = ErrorPolicy::SkipAndContinue;
+        let ids: Vec<_> = reader.map(|s| s.id().to_string()).collect();
+        assert_eq!(ids, vec!["first", "last"]);
+    }
+
+    #[test]
+    fn test_skip_corrupt_header() {
+        let text = "BEGIN IONS\nTITLE=first\nPEPMASS=500.0\n100.0 10\nEND IONS\n\
+                    BEGIN IONS\nTITLE=corrupt\nPEPMASS=5x0.0 20\n100.0 10\nEND IONS\n\
+                    BEGIN IONS\nTITLE=last\nPEPMASS=500.0\nRTINSECONDS=60.0\n100.0 10\nEND IONS\n";
+
+        let mut reader = MGFReader::new(io::Cursor::new(text.as_bytes()));
+        assert_eq!(reader.next().unwrap().id(), "first");
+        assert!(matches!(
+            reader.read_next_result(),
+            Err(MGFError::MalformedHeaderLine)
+        ));
+
+        let mut reader = MGFReader::new(io::Cursor::new(text.as_bytes()));
+        reader.error_policy = ErrorPolicy::SkipAndContinue;
+        let spectra: Vec<_> = reader.collect();
+        let ids: Vec<_> = spectra.iter().map(|s| s.id()).collect();
+        assert_eq!(ids, vec!["first", "last"]);
+        assert_eq!(spectra[1].start_time(), 1.0);
+
+        for header in ["RTINSECONDS=sixty", "PEPMASS=500.0 lots", "PEPMASS=500.0 20 two"] {
+            let text = format!("BEGIN IONS\nTITLE=corrupt\n{header}\n100.0 10\nEND IONS\n");
+            let mut reader = MGFReader::new(io::Cursor::new(text.as_bytes()));
+            assert!(
+                matches!(reader.read_next_result(), Err(MGFError::MalformedHeaderLine)),
+                "{header} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_conversion() {
+        let err: Box<dyn std::error::Error> = MGFError::MalformedPeakLine.into();
+        assert_eq!(err.to_string(), "Encountered a malformed peak line");
+
+        let err: io::Error = MGFError::MalformedHeaderLine.into();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let err: io::Error =
+            MGFError::IOError(io::Error::new(io::ErrorKind::UnexpectedEof, "eof")).into();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let text = "BEGIN IONS\nTITLE=corrupt\nPEPMASS=500.0\n2x0.0 5\nEND IONS\n";
+        let mut reader = MGFReader::new(io::Cursor::new(text.as_bytes()));
+        let mut read = || -> Result<(), Box<dyn std::error::Error>> {
+            reader.read_next_result()?;
+            Ok(())
+        };
+        assert!(read().is_err());
+    }
+
+    #[test]
+    fn test_untitled_spectra() {
+        let text = "BEGIN IONS\nPEPMASS=500.0\n100.0 10\nEND IONS\n\
+                    BEGIN IONS\nTITLE=titled\nPEPMASS=600.0\n200.0 20\nEND IONS\n\
+                    BEGIN IONS\nPEPMASS=700.0\n300.0 30\nEND IONS\n";
+
+        let reader = MGFReader::new(io::Cursor::new(text.as_bytes()));
+        let ids: Vec<_> = reader.map(|s| s.id().to_string()).collect();
+        assert_eq!(ids, vec!["index=0", "titled", "index=2"]);
+
+        let mut reader = MGFReader::new_indexed(io::Cursor::new(text.as_bytes()));
+        assert_eq!(reader.len(), 3);
+        let keys: Vec<_> = reader.get_index().keys().map(|k| k.to_string()).collect();
+        assert_eq!(keys, vec!["index=0", "titled", "index=2"]);
+
+        let spectrum = reader.get_spectrum_by_id("index=2").unwrap();
+        assert_eq!(spectrum.id(), "index=2");
+        assert_eq!(spectrum.index(), 2);
+        assert_eq!(spectrum.peaks().base_peak().mz, 300.0);
+
+        let spectrum = reader.get_spectrum_by_id("titled").unwrap();
+        assert_eq!(spectrum.index(), 1);
+
+        let spectrum = reader.get_spectrum_by_index(0).unwrap();
+        assert_eq!(spectrum.id(), "index=0");
+
+        let ids: Vec<_> = reader
+            .start_from_index(2)
+            .unwrap()
+            .map(|s| s.id().to_string())
+            .collect();
+        assert_eq!(ids, vec!["index=2"]);
+    }
+
+    #[test]
+    fn test_title_with_equals_signs() {
+        let text = "BEGIN IONS\nTITLE=run=1 scan=10 \t\r\nPEPMASS=500.0\n100.0 10\nEND IONS\r\n\
+                    BEGIN IONS\r\nTITLE= file=a.raw;index=2\t\r\nPEPMASS=600.0\n200.0 20\nEND IONS\n";
+
+        let ids: Vec<_> = MGFReader::new(io::Cursor::new(text.as_bytes()))
+            .map(|s| s.id().to_string())
+            .collect();
+        assert_eq!(ids, vec!["run=1 scan=10", "file=a.raw;index=2"]);
+
+        let mut reader = MGFReader::new_indexed(io::Cursor::new(text.as_bytes()));
+        let indexed_ids: Vec<_> = reader.get_index().keys().map(|k| k.to_string()).collect();
+        assert_eq!(indexed_ids, ids);
+        for (i, id) in ids.iter().enumerate() {
+            let spectrum = reader.get_spectrum_by_id(id).unwrap();
+            assert_eq!(spectrum.id(), id);
+            assert_eq!(spectrum.index(), i);
+        }
+    }
+
+    #[test]
+    fn test_iter_with_offsets() -> io::Result<()> {
+        let indexed = MGFReader::open_path("./test/data/small.mgf")?;
+        let mut reader = MGFReader::new(fs::File::open("./test/data/small.mgf")?);
+
+        let mut last_offset = None;
+        let mut count = 0;
+        for (offset, spectrum) in reader.iter_with_offsets() {
+            if let Some(last_offset) = last_offset {
+                assert!(offset > last_offset);
+            }
+            assert_eq!(indexed.get_index().get(spectrum.id()), Some(offset));
+            last_offset = Some(offset);
+            count += 1;
+        }
+        assert_eq!(count, 34);
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_ms_level() {
+        let text = "BEGIN IONS\nTITLE=survey 1\n100.0 10\n200.0 20\nEND IONS\n\
+                    BEGIN IONS\nTITLE=survey 2\n150.0 15\nEND IONS\n\
+                    BEGIN IONS\nTITLE=fragments\nMSLEVEL=2\nPEPMASS=150.0\n50.0 5\nEND IONS\n";
+
+        let reader = MGFReader::new(io::Cursor::new(text.as_bytes()));
+        assert_eq!(reader.default_ms_level(), 2);
+        let levels: Vec<_> = reader.map(|s| s.ms_level()).collect();
+        assert_eq!(levels, vec![2, 2, 2]);
+
+        let mut reader = MGFReader::new_indexed(io::Cursor::new(text.as_bytes()));
+        reader.set_default_ms_level(1);
+        let levels: Vec<_> = reader.iter().map(|s| s.ms_level()).collect();
+        assert_eq!(levels, vec![1, 1, 2]);
+        assert_eq!(reader.get_spectrum_by_id("survey 2").unwrap().ms_level(), 1);
+    }
+
+    #[test]
+    fn test_missing_retention_time() -> io::Result<()> {
+        let text = "BEGIN IONS\nTITLE=no time\nPEPMASS=500.0\n100.0 10\nEND IONS\n\
+                    BEGIN IONS\nTITLE=timed\nRTINSECONDS=0\nPEPMASS=600.0\n200.0 20\nEND IONS\n";
+        let spectra: Vec<_> = MGFReader::new(io::Cursor::new(text.as_bytes())).collect();
+        assert_eq!(spectra.len(), 2);
+        assert_eq!(spectra[0].retention_time(), None);
+        assert_eq!(spectra[1].retention_time(), Some(0.0));
+
+        let mut writer = MGFWriter::new(Vec::new());
+        writer.write(&spectra[0])?;
+        writer.write(&spectra[1])?;
+        writer.flush()?;
+        let text = String::from_utf8(writer.into_inner().into_inner()?).unwrap();
+        assert_eq!(text.matches("RTINSECONDS=").count(), 1);
+
+        let spectra: Vec<_> = MGFReader::new(io::Cursor::new(text.as_bytes())).collect();
+        assert_eq!(spectra[0].retention_time(), None);
+        assert_eq!(spectra[1].retention_time(), Some(0.0));
+        Ok(())
+    }
+
     #[test]
     fn test_writer() -> io::Result<()> {
         let buff: Vec<u8> = Vec::new();
@@ -1175,4 +1870,99 @@ mod test {
         // Not including platform-specific line endings
         Ok(())
     }
+
+    #[test]
+    fn test_write_precision() -> io::Result<()> {
+        let mut spectrum: CentroidSpectrum = vec![(100.123456789, 10.555f32)].into_iter().collect();
+        spectrum.description.id = "scan=1".to_string();
+        spectrum.description.ms_level = 2;
+
+        let mut writer = MGFWriter::new(Vec::new());
+        writer.write(&spectrum)?;
+        writer.flush()?;
+        let text = String::from_utf8(writer.into_inner().into_inner()?).unwrap();
+        assert!(text.contains("\n100.123457 10.55\n") || text.contains("\n100.123457 10.56\n"));
+
+        let mut writer = MGFWriter::new(Vec::new());
+        writer.set_precision(Some(2), Some(0));
+        writer.write(&spectrum)?;
+        writer.flush()?;
+        let text = String::from_utf8(writer.into_inner().into_inner()?).unwrap();
+        assert!(text.contains("\n100.12 11\n"));
+
+        let mut writer = MGFWriter::new(Vec::new());
+        writer.set_precision(None, None);
+        writer.write(&spectrum)?;
+        writer.flush()?;
+        let text = String::from_utf8(writer.into_inner().into_inner()?).unwrap();
+        assert!(text.contains("\n100.123456789 10.555\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_deconvoluted() -> io::Result<()> {
+        let mut description = SpectrumDescription {
+            id: "scan=1".to_string(),
+            ms_level: 2,
+            signal_continuity: SignalContinuity::Centroid,
+            ..Default::default()
+        };
+        description.precursor = Some(Precursor {
+            ions: vec![SelectedIon {
+                mz: 512.3,
+                intensity: 1000.0,
+                charge: Some(3),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        let peaks = vec![
+            DeconvolutedPeak {
+                neutral_mass: 1200.5,
+                intensity: 50.0,
+                charge: 2,
+                ..Default::default()
+            },
+            DeconvolutedPeak {
+                neutral_mass: 400.25,
+                intensity: 120.0,
+                charge: 1,
+                ..Default::default()
+            },
+        ];
+        let spectrum = DeconvolutedSpectrumType::new(description, peaks.into_iter().collect());
+
+        let mut writer = MGFWriter::new(Vec::new());
+        writer.write_deconvoluted(&spectrum)?;
+        writer.flush()?;
+        let buffer = writer.into_inner().into_inner()?;
+        let text = String::from_utf8_lossy(&buffer).to_string();
+        assert!(text.contains("CHARGE=3+"));
+
+        let mut reader = MGFReader::new(io::Cursor::new(buffer));
+        let scan = reader.next().unwrap();
+        assert_eq!(scan.precursor().unwrap().ion().charge, Some(3));
+        let read_peaks = scan.deconvoluted_peaks.as_ref().unwrap();
+        assert_eq!(read_peaks.len(), spectrum.deconvoluted_peaks.len());
+        for (expected, found) in spectrum.deconvoluted_peaks.iter().zip(read_peaks.iter()) {
+            assert_eq!(expected.charge, found.charge);
+            assert_eq!(expected.intensity, found.intensity);
+            assert!((expected.neutral_mass - found.neutral_mass).abs() < 1e-6);
+        }
+
+        // Any spectrum holding deconvoluted peaks is written the same way
+        let mut writer = MGFWriter::new(Vec::new());
+        writer.write(&scan)?;
+        writer.flush()?;
+        let buffer = writer.into_inner().into_inner()?;
+        let rewritten = String::from_utf8_lossy(&buffer).to_string();
+        assert!(rewritten.contains("CHARGE=3"));
+        let peak_lines = |text: &str| {
+            text.lines()
+                .filter(|line| line.starts_with(|c: char| c.is_ascii_digit()))
+                .count()
+        };
+        assert_eq!(peak_lines(&rewritten), peak_lines(&text));
+        Ok(())
+    }
 }
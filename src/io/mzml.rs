@@ -24,7 +24,7 @@ mod writer;
 mod r#async;
 
 pub use reading_shared::{
-    CVParamParse, MzMLParserError, MzMLParserState, MzMLSAX, XMLParseBase,
+    CVParamParse, MzMLParserError, MzMLParserErrorContext, MzMLParserState, MzMLSAX, XMLParseBase,
     FileMetadataBuilder, EntryType
 };
 
@@ -36,6 +36,9 @@ pub use crate::io::mzml::reader::{
     SpectrumBuilding,
 };
 
+#[cfg(feature = "mmap")]
+pub use crate::io::mzml::reader::MmapMzMLReaderType;
+
 pub(crate) use crate::io::mzml::reader::is_mzml;
 
 pub use crate::io::mzml::writer::{MzMLWriter, MzMLWriterState, MzMLWriterType, MzMLWriterError};
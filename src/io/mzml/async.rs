@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::SeekFrom;
 use std::marker::PhantomData;
 use std::mem;
@@ -22,7 +22,7 @@ use quick_xml::Reader;
 use crate::prelude::*;
 
 use crate::io::utils::DetailLevel;
-use crate::meta::{DataProcessing, FileDescription, InstrumentConfiguration, MSDataFileMetadata, MassSpectrometryRun, Sample, Software};
+use crate::meta::{CvReference, DataProcessing, FileDescription, InstrumentConfiguration, MSDataFileMetadata, MassSpectrometryRun, Sample, Software};
 use crate::params::Param;
 use crate::spectrum::bindata::BuildFromArrayMap;
 use crate::spectrum::spectrum_types::{
@@ -61,6 +61,8 @@ pub struct MzMLReaderType<
     /// The description of the file's contents and the previous data files that were
     /// consumed to produce it.
     pub(crate) file_description: FileDescription,
+    /// The controlled vocabularies declared in the `<cvList>` and their versions
+    pub(crate) cv_list: Vec<CvReference>,
     /// A mapping of different instrument configurations (source, analyzer, detector) components
     /// by ID string.
     pub(crate) instrument_configurations: HashMap<u32, InstrumentConfiguration>,
@@ -77,6 +79,8 @@ pub struct MzMLReaderType<
     pub detail_level: DetailLevel,
 
     pub(crate) instrument_id_map: IncrementingIdMap,
+    /// The unrecognized accessions already warned about
+    reported_accessions: HashSet<String>,
 
     pub run: MassSpectrometryRun,
 
@@ -115,6 +119,7 @@ impl<
             index: OffsetIndex::new("spectrum".to_owned()),
 
             file_description: FileDescription::default(),
+            cv_list: Vec::new(),
             instrument_configurations: HashMap::new(),
             softwares: Vec::new(),
             samples: Vec::new(),
@@ -125,6 +130,7 @@ impl<
             centroid_type: PhantomData,
             deconvoluted_type: PhantomData,
             instrument_id_map: IncrementingIdMap::default(),
+            reported_accessions: HashSet::new(),
             run: MassSpectrometryRun::default(),
             num_spectra: None,
         };
@@ -142,6 +148,7 @@ impl<
         reader.trim_text(true);
         let mut accumulator = FileMetadataBuilder::default();
         accumulator.instrument_id_map = Some(&mut self.instrument_id_map);
+        accumulator.reported_accessions = Some(&mut self.reported_accessions);
         loop {
             match reader.read_event_into_async(&mut self.buffer).await {
                 Ok(Event::Start(ref e)) => {
@@ -149,8 +156,8 @@ impl<
                         Ok(state) => {
                             self.state = state;
                             match &self.state {
-                                MzMLParserState::Run
-                                | MzMLParserState::SpectrumList
+                                MzMLParserState::SpectrumList
+                                | MzMLParserState::ChromatogramList
                                 | MzMLParserState::Spectrum => break,
                                 _ => {}
                             }
@@ -223,13 +230,12 @@ impl<
                 _ => {}
             };
             self.buffer.clear();
-            match self.state {
-                MzMLParserState::Run | MzMLParserState::ParserError => {
-                    break;
-                }
-                _ => {}
-            };
+            if self.state == MzMLParserState::ParserError {
+                break;
+            }
         }
+        self.buffer.clear();
+        self.cv_list = accumulator.cv_list;
         self.file_description = accumulator.file_description;
         self.instrument_configurations = accumulator
             .instrument_configurations
@@ -266,6 +272,8 @@ impl<
         let mut reader = Reader::from_reader(&mut self.handle);
         reader.trim_text(true);
         accumulator.instrument_id_map = Some(&mut self.instrument_id_map);
+        accumulator.reference_param_groups = Some(&self.reference_param_groups);
+        accumulator.reported_accessions = Some(&mut self.reported_accessions);
         let mut offset: usize = 0;
         loop {
             let event = reader.read_event_into_async(&mut self.buffer).await;
@@ -407,6 +415,10 @@ impl<
         match self._parse_into(accumulator).await {
             Ok((sz, accumulator)) => {
                 accumulator.into_spectrum(spectrum);
+                if spectrum.description.data_processing_id.is_none() {
+                    spectrum.description.data_processing_id =
+                        self.run.default_data_processing_id.clone();
+                }
                 Ok(sz)
             }
             Err(err) => Err(err),
@@ -441,6 +453,14 @@ impl<
     fn run_description_mut(&mut self) -> Option<&mut MassSpectrometryRun> {
         Some(&mut self.run)
     }
+
+    fn controlled_vocabularies(&self) -> Option<&Vec<CvReference>> {
+        Some(&self.cv_list)
+    }
+
+    fn controlled_vocabularies_mut(&mut self) -> Option<&mut Vec<CvReference>> {
+        Some(&mut self.cv_list)
+    }
 }
 
 /// A specialization of [`AsyncMzMLReaderType`](crate::io::mzml::AsyncMzMLReaderType) for the default peak types, for common use.
@@ -487,7 +507,7 @@ impl IndexedMzMLIndexExtractor {
         event: &BytesStart,
         state: IndexParserState,
     ) -> Result<IndexParserState, XMLError> {
-        let elt_name = event.name();
+        let elt_name = event.local_name();
         match elt_name.as_ref() {
             b"offset" => {
                 for attr_parsed in event.attributes() {
@@ -542,7 +562,7 @@ impl IndexedMzMLIndexExtractor {
         event: &BytesEnd,
         state: IndexParserState,
     ) -> Result<IndexParserState, XMLError> {
-        let elt_name = event.name();
+        let elt_name = event.local_name();
         match elt_name.as_ref() {
             b"offset" => {}
             b"index" => {}
@@ -823,11 +843,11 @@ mod test {
                 .acquisition()
                 .first_scan()
                 .unwrap()
-                .get_param_by_accession("MS:1000512")
+                .filter_string()
                 .unwrap();
             let configs = spec.acquisition().instrument_configuration_ids();
             let conf = configs[0];
-            if filter_string.value.to_str().contains("ITMS") {
+            if filter_string.contains("ITMS") {
                 assert_eq!(conf, 1);
             } else {
                 assert_eq!(conf, 0);
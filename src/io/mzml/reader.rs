@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
 use std::fs;
 use std::io;
@@ -8,6 +8,9 @@ use std::mem;
 
 use log::{debug, trace, warn};
 
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+
 use mzpeaks::CentroidLike;
 use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::Error as XMLError;
@@ -27,7 +30,7 @@ use super::reading_shared::EntryType;
 use mzpeaks::{CentroidPeak, DeconvolutedPeak};
 
 use crate::meta::{
-    DataProcessing, FileDescription, InstrumentConfiguration, MSDataFileMetadata,
+    CvReference, DataProcessing, FileDescription, InstrumentConfiguration, MSDataFileMetadata,
     MassSpectrometryRun, Software,
 };
 use crate::params::{Param, ParamList, Unit};
@@ -42,17 +45,54 @@ use crate::spectrum::spectrum_types::{
     CentroidPeakAdapting, CentroidSpectrumType, DeconvolutedPeakAdapting, MultiLayerSpectrum,
     RawSpectrum, Spectrum,
 };
+use crate::utils::seconds_to_minutes;
 
-use crate::io::utils::DetailLevel;
+use crate::io::utils::{
+    cache_spectrum_summaries, truncate_to_most_intense, ArrayLengthValidation, DetailLevel,
+    ErrorPolicy,
+};
 
 use super::reading_shared::{
     CVParamParse, FileMetadataBuilder, IncrementingIdMap, IndexParserState,
-    IndexedMzMLIndexExtractor, MzMLIndexingError, MzMLParserError, MzMLParserState, MzMLSAX,
-    ParserResult, XMLParseBase,
+    IndexedMzMLIndexExtractor, MzMLIndexingError, MzMLParserError, MzMLParserErrorContext,
+    MzMLParserState, MzMLSAX, ParserResult, XMLParseBase, warn_unrecognized_accession,
 };
 
 pub type Bytes = Vec<u8>;
 
+/// Name `array` as `array_type`, unless it has already been named as the other of the m/z
+/// and intensity arrays, in which case it holds both as interleaved `(m/z, intensity)` pairs
+fn set_signal_array_name(array: &mut DataArray, array_type: ArrayType) {
+    let interleaved = ArrayType::nonstandard(ArrayType::INTERLEAVED_MZ_INTENSITY_ARRAY);
+    array.name = match &array.name {
+        ArrayType::MZArray | ArrayType::IntensityArray if array.name != array_type => interleaved,
+        name if *name == interleaved => interleaved,
+        _ => array_type,
+    };
+}
+
+/// Convert a scan start time given in `unit` to minutes, or [`None`] if `unit` is not a
+/// unit of time
+fn scan_time_as_minutes(value: f64, unit: &Unit) -> Option<f64> {
+    match unit {
+        Unit::Minute => Some(value),
+        Unit::Second => Some(seconds_to_minutes(value)),
+        Unit::Millisecond => Some(seconds_to_minutes(value / 1000.0)),
+        _ => None,
+    }
+}
+
+/// Convert an ion injection time given in `unit` to milliseconds, or [`None`] if `unit`
+/// is not a unit of time. Times without a unit are assumed to be in milliseconds.
+fn injection_time_as_milliseconds(value: f32, unit: &Unit) -> Option<f32> {
+    match unit {
+        Unit::Millisecond | Unit::Unknown => Some(value),
+        Unit::Second => Some(value * 1000.0),
+        Unit::Minute => Some(value * 60000.0),
+        _ => None,
+    }
+}
+
 /// Convert mzML spectrum XML into [`MultiLayerSpectrum`](crate::spectrum::MultiLayerSpectrum)
 pub trait SpectrumBuilding<
     'a,
@@ -76,8 +116,8 @@ pub trait SpectrumBuilding<
     fn fill_spectrum<P: ParamLike + Into<Param> + ParamValue>(&mut self, param: P);
 
     fn fill_binary_data_array<P: ParamLike + Into<Param> + ParamValue>(&mut self, param: P) {
-        if param.is_ms() {
-            match param.accession().unwrap() {
+        if let Some(accession) = param.accession().filter(|_| param.is_ms()) {
+            match accession {
                 // Compression types
                 1000574 => {
                     self.current_array_mut().compression = BinaryCompressionType::Zlib;
@@ -127,8 +167,10 @@ pub trait SpectrumBuilding<
                 }
 
                 // Array types
-                1000514 => self.current_array_mut().name = ArrayType::MZArray,
-                1000515 => self.current_array_mut().name = ArrayType::IntensityArray,
+                1000514 => set_signal_array_name(self.current_array_mut(), ArrayType::MZArray),
+                1000515 => {
+                    set_signal_array_name(self.current_array_mut(), ArrayType::IntensityArray)
+                }
                 1000516 => self.current_array_mut().name = ArrayType::ChargeArray,
                 1000517 => self.current_array_mut().name = ArrayType::SignalToNoiseArray,
                 1000786 => {
@@ -184,6 +226,9 @@ pub trait SpectrumBuilding<
                     self.current_array_mut().add_param(param.into());
                 }
             }
+        } else if param.name() == ArrayType::INTERLEAVED_MZ_INTENSITY_ARRAY {
+            self.current_array_mut().name =
+                ArrayType::nonstandard(ArrayType::INTERLEAVED_MZ_INTENSITY_ARRAY);
         } else {
             self.current_array_mut().add_param(param.into());
         }
@@ -208,7 +253,9 @@ pub trait SpectrumBuilding<
         };
     }
 
-    fn fill_isolation_window(&mut self, param: Param) {
+    /// Fill in the isolation window from `param`, returning it if it does not describe
+    /// the isolation window bounds so the caller can retain it elsewhere.
+    fn fill_isolation_window(&mut self, param: Param) -> Option<Param> {
         let window = self.isolation_window_mut();
         match param.name.as_ref() {
             "isolation window target m/z" => {
@@ -274,11 +321,14 @@ pub trait SpectrumBuilding<
                     window.upper_bound = upper_bound;
                 }
             }
-            &_ => {}
+            &_ => return Some(param),
         }
+        None
     }
 
-    fn fill_scan_window(&mut self, param: Param) {
+    /// Fill in the scan window from `param`, returning it if it does not describe the
+    /// scan window bounds so the caller can retain it elsewhere.
+    fn fill_scan_window(&mut self, param: Param) -> Option<Param> {
         let window = self.scan_window_mut();
         match param.name.as_ref() {
             "scan window lower limit" => {
@@ -287,14 +337,26 @@ pub trait SpectrumBuilding<
             "scan window upper limit" => {
                 window.upper_bound = param.to_f32().expect("Failed to parse scan window limit");
             }
-            &_ => {}
+            &_ => return Some(param),
         }
+        None
     }
 
     fn borrow_instrument_configuration(
         self,
         instrument_configurations: &'a mut IncrementingIdMap,
     ) -> Self;
+
+    /// Give the builder access to the `<referenceableParamGroup>` definitions of the document so
+    /// that `<referenceableParamGroupRef>` elements can be expanded
+    fn borrow_reference_param_groups(
+        self,
+        reference_param_groups: &'a HashMap<String, Vec<Param>>,
+    ) -> Self;
+
+    /// Give the builder the unrecognized accessions its reader has already warned about, so each
+    /// is only reported once per reader
+    fn borrow_reported_accessions(self, reported_accessions: &'a mut HashSet<String>) -> Self;
 }
 
 const BUFFER_SIZE: usize = 10000;
@@ -316,12 +378,23 @@ pub struct MzMLSpectrumBuilder<
 
     pub index: usize,
     pub entry_id: String,
+    pub data_processing_id: Option<String>,
     pub ms_level: u8,
     pub polarity: ScanPolarity,
     pub signal_continuity: SignalContinuity,
     pub has_precursor: bool,
     pub detail_level: DetailLevel,
+    /// Whether to check decoded arrays against their declared length
+    pub array_length_validation: ArrayLengthValidation,
+    /// The `defaultArrayLength` of the current entry
+    pub default_array_length: Option<usize>,
+    /// The `arrayLength` of the current data array, overriding `default_array_length`
+    current_array_length: Option<usize>,
     pub instrument_id_map: Option<&'a mut IncrementingIdMap>,
+    /// The parameter groups defined in the `<referenceableParamGroupList>`
+    pub reference_param_groups: Option<&'a HashMap<String, Vec<Param>>>,
+    /// The unrecognized accessions the reader has already warned about
+    pub reported_accessions: Option<&'a mut HashSet<String>>,
     entry_type: EntryType,
     centroid_type: PhantomData<C>,
     deconvoluted_type: PhantomData<D>,
@@ -389,6 +462,7 @@ impl<
         description.polarity = self.polarity;
 
         description.params = self.params;
+        description.data_processing_id = self.data_processing_id;
         description.acquisition = self.acquisition;
         if self.has_precursor {
             description.precursor = Some(self.precursor);
@@ -430,6 +504,22 @@ impl<
         self
     }
 
+    fn borrow_reference_param_groups(
+        mut self,
+        reference_param_groups: &'inner HashMap<String, Vec<Param>>,
+    ) -> Self {
+        self.reference_param_groups = Some(reference_param_groups);
+        self
+    }
+
+    fn borrow_reported_accessions(
+        mut self,
+        reported_accessions: &'inner mut HashSet<String>,
+    ) -> Self {
+        self.reported_accessions = Some(reported_accessions);
+        self
+    }
+
     fn new_selected_ion(&mut self) -> &mut SelectedIon {
         self.precursor.add_ion(SelectedIon::default());
         self.precursor.last_ion_mut()
@@ -490,6 +580,23 @@ impl<
         }
     }
 
+    /// Warn about the accession of the `<cvParam>` `event` if `param` could not be resolved
+    /// against a known controlled vocabulary
+    pub(crate) fn check_accession<P: ParamLike>(
+        &mut self,
+        event: &BytesStart,
+        param: &P,
+        reader_position: usize,
+    ) {
+        if param.controlled_vocabulary().is_none() || param.accession().is_none() {
+            warn_unrecognized_accession(
+                event,
+                reader_position,
+                self.reported_accessions.as_deref_mut(),
+            );
+        }
+    }
+
     fn warning_context(&self) -> String {
         if self.is_spectrum_entry() {
             format!("spectrum entry {} ({})", self.index, self.entry_id)
@@ -500,12 +607,143 @@ impl<
         }
     }
 
+    /// Add the parameters of the group named by a `<referenceableParamGroupRef>` element to
+    /// whatever is being built in `state`
+    fn expand_param_group_ref(
+        &mut self,
+        event: &BytesStart,
+        state: MzMLParserState,
+    ) -> ParserResult {
+        for attr_parsed in event.attributes() {
+            match attr_parsed {
+                Ok(attr) => {
+                    if attr.key.as_ref() != b"ref" {
+                        continue;
+                    }
+                    let group_id = attr
+                        .unescape_value()
+                        .expect("Error decoding reference group");
+                    let group = self
+                        .reference_param_groups
+                        .and_then(|groups| groups.get(group_id.as_ref()));
+                    match group {
+                        Some(params) => {
+                            for param in params {
+                                self.fill_param_into(param.clone(), state)?;
+                            }
+                        }
+                        None => {
+                            warn!(
+                                "Encountered a reference to an undefined param group {group_id} for {}",
+                                self.warning_context()
+                            );
+                        }
+                    }
+                }
+                Err(msg) => {
+                    return Err(self.handle_xml_error(msg.into(), state));
+                }
+            }
+        }
+        Ok(state)
+    }
+
+    /// When a spectrum carries a second m/z or intensity array, as when vendor centroids are
+    /// stored alongside the profile data, rename it so it doesn't replace the first one.
+    /// See [`RawSpectrum::centroids`].
+    fn rename_repeated_signal_array(&self, array: &mut DataArray) {
+        let name = match array.name {
+            ArrayType::MZArray => ArrayType::CENTROID_MZ_ARRAY,
+            ArrayType::IntensityArray => ArrayType::CENTROID_INTENSITY_ARRAY,
+            _ => return,
+        };
+        if self.arrays.has_array(&array.name) {
+            debug!(
+                "Found a second {} in {}, storing it as {name}",
+                array.name, self.entry_id
+            );
+            array.name = ArrayType::nonstandard(name);
+        }
+    }
+
+    /// Compare the length of `array` against the declared `arrayLength` or `defaultArrayLength`
+    /// according to [`MzMLSpectrumBuilder::array_length_validation`]
+    fn validate_array_length(
+        &mut self,
+        array: &DataArray,
+        state: MzMLParserState,
+    ) -> Result<(), MzMLParserError> {
+        let declared = self
+            .current_array_length
+            .take()
+            .or(self.default_array_length);
+        let expected = match declared {
+            Some(expected) => expected,
+            None => return Ok(()),
+        };
+        if self.array_length_validation == ArrayLengthValidation::Skip
+            || self.detail_level == DetailLevel::MetadataOnly
+        {
+            return Ok(());
+        }
+        let found = array
+            .data_len()
+            .map_err(|e| MzMLParserError::ArrayDecodingError(state, e))?;
+        // An interleaved array holds an m/z and an intensity for each declared point
+        let expected = match &array.name {
+            ArrayType::NonStandardDataArray { name }
+                if **name == ArrayType::INTERLEAVED_MZ_INTENSITY_ARRAY =>
+            {
+                expected * 2
+            }
+            _ => expected,
+        };
+        if found != expected {
+            if self.array_length_validation == ArrayLengthValidation::Error {
+                return Err(MzMLParserError::ArrayLengthMismatch(
+                    array.name.clone(),
+                    found,
+                    expected,
+                ));
+            }
+            warn!(
+                "The {} has {found} entries but {expected} were declared for {}",
+                array.name,
+                self.warning_context()
+            );
+        }
+        Ok(())
+    }
+
+    /// If a spectrum supplied only one of the m/z or intensity arrays, add an empty
+    /// array in place of the missing one so that downstream peak construction doesn't
+    /// fail or pair values with the wrong array.
+    fn fill_missing_signal_arrays(&mut self) {
+        if self.arrays.is_empty() {
+            return;
+        }
+        let has_mz = self.arrays.has_array(&ArrayType::MZArray);
+        let has_intensity = self.arrays.has_array(&ArrayType::IntensityArray);
+        let (array_type, dtype) = match (has_mz, has_intensity) {
+            (true, false) => (ArrayType::IntensityArray, BinaryDataArrayType::Float32),
+            (false, true) => (ArrayType::MZArray, BinaryDataArrayType::Float64),
+            _ => return,
+        };
+        warn!(
+            "Missing {} for {}, using an empty array",
+            array_type,
+            self.warning_context()
+        );
+        self.arrays.add(DataArray::from_name_and_type(&array_type, dtype));
+    }
+
     pub fn _reset(&mut self) {
         self.params.clear();
         self.acquisition = Acquisition::default();
         self.arrays.clear();
         self.current_array.clear();
         self.entry_id.clear();
+        self.data_processing_id = None;
 
         self.precursor = Precursor::default();
         self.index = 0;
@@ -530,21 +768,20 @@ impl<
         matches!(self.entry_type, EntryType::Chromatogram)
     }
 
-    pub fn fill_param_into(&mut self, param: Param, state: MzMLParserState) {
+    pub fn fill_param_into(
+        &mut self,
+        param: Param,
+        state: MzMLParserState,
+    ) -> Result<(), MzMLParserError> {
         match state {
-            MzMLParserState::Spectrum => {
+            MzMLParserState::Spectrum | MzMLParserState::Chromatogram => {
                 self.fill_spectrum(param);
             }
             MzMLParserState::ScanList => {
-                if param.is_controlled() {
-                    if let Some(comb) = ScanCombination::from_accession(
-                        param.controlled_vocabulary.unwrap(),
-                        param.accession.unwrap(),
-                    ) {
-                        self.acquisition.combination = comb
-                    } else {
-                        self.acquisition.add_param(param)
-                    }
+                if let Some(comb) = param.curie().and_then(|c| {
+                    ScanCombination::from_accession(c.controlled_vocabulary, c.accession)
+                }) {
+                    self.acquisition.combination = comb
                 } else {
                     self.acquisition.add_param(param)
                 }
@@ -553,25 +790,28 @@ impl<
                 let event = self.acquisition.scans.last_mut().unwrap();
                 match param.name.as_bytes() {
                     b"scan start time" => {
-                        let value: f64 = param
-                            .to_f64()
-                            .expect("Expected floating point number for scan time");
-                        let value = match &param.unit {
-                            Unit::Minute => value,
-                            Unit::Second => value / 60.0,
-                            Unit::Millisecond => value / 60000.0,
-                            _ => {
-                                warn!("Could not infer unit for {:?}", param);
-                                value
-                            }
-                        };
+                        let value: f64 = param.to_f64().map_err(|e| {
+                            MzMLParserError::MalformedParamValue(state, param.name.clone(), e)
+                        })?;
+                        let value = scan_time_as_minutes(value, &param.unit).unwrap_or_else(|| {
+                            warn!("Could not infer unit for {:?}", param);
+                            value
+                        });
                         event.start_time = value;
                     }
                     b"ion injection time" => {
-                        event.injection_time = param
-                            .to_f64()
-                            .expect("Expected floating point number for injection time")
-                            as f32;
+                        let value = param.to_f32().map_err(|e| {
+                            MzMLParserError::MalformedParamValue(state, param.name.clone(), e)
+                        })?;
+                        let value = injection_time_as_milliseconds(value, &param.unit)
+                            .unwrap_or_else(|| {
+                                warn!("Could not infer unit for {:?}", param);
+                                value
+                            });
+                        event.injection_time = Some(value);
+                    }
+                    b"filter string" => {
+                        event.filter_string = Some(param.value.to_string());
                     }
                     _ => event.add_param(param),
                 }
@@ -580,10 +820,14 @@ impl<
                 self.acquisition.scans.last_mut().unwrap().add_param(param)
             }
             MzMLParserState::ScanWindow => {
-                self.fill_scan_window(param);
+                if let Some(param) = self.fill_scan_window(param) {
+                    self.acquisition.scans.last_mut().unwrap().add_param(param)
+                }
             }
             MzMLParserState::IsolationWindow => {
-                self.fill_isolation_window(param);
+                if let Some(param) = self.fill_isolation_window(param) {
+                    self.precursor.isolation_window.add_param(param)
+                }
             }
             MzMLParserState::SelectedIon | MzMLParserState::SelectedIonList => {
                 self.fill_selected_ion(param);
@@ -614,6 +858,49 @@ impl<
             }
             _ => {}
         };
+        Ok(())
+    }
+
+    /// Read the attributes of a `<scan>` element, whether it has children or not, into a
+    /// new [`ScanEvent`]
+    fn scan_event_from(
+        &mut self,
+        event: &BytesStart,
+        state: MzMLParserState,
+    ) -> Result<ScanEvent, MzMLParserError> {
+        let mut scan_event = ScanEvent::default();
+        for attr_parsed in event.attributes() {
+            match attr_parsed {
+                Ok(attr) => match attr.key.as_ref() {
+                    b"instrumentConfigurationRef" => {
+                        scan_event.instrument_configuration_id = self
+                            .instrument_id_map
+                            .as_mut()
+                            .expect("An instrument ID map was not provided")
+                            .get(&attr.unescape_value().expect("Error decoding id"));
+                    }
+                    b"sourceFileRef" => {
+                        scan_event.source_file_ref = Some(
+                            attr.unescape_value()
+                                .expect("Error decoding source file reference")
+                                .to_string(),
+                        );
+                    }
+                    b"externalSpectrumID" => {
+                        scan_event.external_spectrum_id = Some(
+                            attr.unescape_value()
+                                .expect("Error decoding external spectrum ID")
+                                .to_string(),
+                        );
+                    }
+                    _ => {}
+                },
+                Err(msg) => {
+                    return Err(self.handle_xml_error(msg.into(), state));
+                }
+            }
+        }
+        Ok(scan_event)
     }
 }
 
@@ -625,7 +912,7 @@ impl<
     > MzMLSAX for MzMLSpectrumBuilder<'inner, C, D>
 {
     fn start_element(&mut self, event: &BytesStart, state: MzMLParserState) -> ParserResult {
-        let elt_name = event.name();
+        let elt_name = event.local_name();
         match elt_name.as_ref() {
             b"spectrum" => {
                 self.set_entry_type(EntryType::Spectrum);
@@ -643,6 +930,17 @@ impl<
                                     .parse::<usize>()
                                     .expect("Failed to parse index");
                             }
+                            b"defaultArrayLength" => {
+                                self.default_array_length =
+                                    String::from_utf8_lossy(&attr.value).parse().ok();
+                            }
+                            b"dataProcessingRef" => {
+                                self.data_processing_id = Some(
+                                    attr.unescape_value()
+                                        .expect("Error decoding data processing reference")
+                                        .to_string(),
+                                );
+                            }
                             _ => {}
                         },
                         Err(msg) => {
@@ -659,23 +957,7 @@ impl<
                 return Ok(MzMLParserState::ScanList);
             }
             b"scan" => {
-                let mut scan_event = ScanEvent::default();
-                for attr_parsed in event.attributes() {
-                    match attr_parsed {
-                        Ok(attr) => {
-                            if attr.key.as_ref() == b"instrumentConfigurationRef" {
-                                scan_event.instrument_configuration_id = self
-                                    .instrument_id_map
-                                    .as_mut()
-                                    .expect("An instrument ID map was not provided")
-                                    .get(&attr.unescape_value().expect("Error decoding id"));
-                            }
-                        }
-                        Err(msg) => {
-                            return Err(self.handle_xml_error(msg.into(), state));
-                        }
-                    }
-                }
+                let scan_event = self.scan_event_from(event, state)?;
                 self.acquisition.scans.push(scan_event);
                 return Ok(MzMLParserState::Scan);
             }
@@ -730,7 +1012,17 @@ impl<
             b"binaryDataArrayList" => {
                 return Ok(MzMLParserState::BinaryDataArrayList);
             }
+            b"referenceableParamGroupRef" => {
+                return self.expand_param_group_ref(event, state);
+            }
             b"binaryDataArray" => {
+                self.current_array_length = None;
+                for attr in event.attributes().flatten() {
+                    if attr.key.as_ref() == b"arrayLength" {
+                        self.current_array_length =
+                            String::from_utf8_lossy(&attr.value).parse().ok();
+                    }
+                }
                 return Ok(MzMLParserState::BinaryDataArray);
             }
             b"binary" => {
@@ -753,6 +1045,10 @@ impl<
                                     .parse::<usize>()
                                     .expect("Failed to parse index");
                             }
+                            b"defaultArrayLength" => {
+                                self.default_array_length =
+                                    String::from_utf8_lossy(&attr.value).parse().ok();
+                            }
                             _ => {}
                         },
                         Err(msg) => {
@@ -773,49 +1069,54 @@ impl<
         state: MzMLParserState,
         reader_position: usize,
     ) -> ParserResult {
-        let elt_name = event.name();
+        let elt_name = event.local_name();
         match elt_name.as_ref() {
             // Inline the `fill_param_into` to avoid excessive copies.
             b"cvParam" | b"userParam" => {
-                match Self::handle_param_borrowed(event, reader_position, state) {
+                let parsed = Self::handle_param_borrowed(event, reader_position, state);
+                if let Ok(param) = &parsed {
+                    self.check_accession(event, param, reader_position);
+                }
+                match parsed {
                     Ok(param) => match state {
                         MzMLParserState::Spectrum | MzMLParserState::Chromatogram => {
                             self.fill_spectrum(param)
                         }
                         MzMLParserState::ScanList => {
-                            if param.is_controlled() {
-                                if let Some(comb) = ScanCombination::from_accession(
-                                    param.controlled_vocabulary.unwrap(),
-                                    param.accession.unwrap(),
-                                ) {
-                                    self.acquisition.combination = comb
-                                } else {
-                                    self.acquisition.add_param(param.into())
-                                }
+                            if let Some(comb) = param.curie().and_then(|c| {
+                                ScanCombination::from_accession(c.controlled_vocabulary, c.accession)
+                            }) {
+                                self.acquisition.combination = comb
                             } else {
                                 self.acquisition.add_param(param.into())
                             }
                         }
                         MzMLParserState::Scan => match param.name.as_bytes() {
                             b"scan start time" => {
-                                let value: f64 = param
-                                    .to_f64()
-                                    .unwrap_or_else(|e| panic!("Expected floating point number for scan time: {e} for {}", self.warning_context()));
-                                let value = match &param.unit {
-                                    Unit::Minute => value,
-                                    Unit::Second => value / 60.0,
-                                    Unit::Millisecond => value / 60000.0,
-                                    _ => {
+                                let value: f64 = param.to_f64().map_err(|e| {
+                                    MzMLParserError::MalformedParamValue(state, param.name.to_string(), e)
+                                })?;
+                                let value = scan_time_as_minutes(value, &param.unit)
+                                    .unwrap_or_else(|| {
                                         warn!("Could not infer unit for {:?} for {}", param, self.warning_context());
                                         value
-                                    }
-                                };
+                                    });
                                 self.acquisition.scans.last_mut().unwrap().start_time = value;
                             }
                             b"ion injection time" => {
-                                self.acquisition.scans.last_mut().unwrap().injection_time = param.to_f32().unwrap_or_else(
-                                            |e| panic!("Expected floating point number for injection time: {e} for {}", self.warning_context())
-                                        );
+                                let value = param.to_f32().map_err(|e| {
+                                    MzMLParserError::MalformedParamValue(state, param.name.to_string(), e)
+                                })?;
+                                let value = injection_time_as_milliseconds(value, &param.unit)
+                                    .unwrap_or_else(|| {
+                                        warn!("Could not infer unit for {:?} for {}", param, self.warning_context());
+                                        value
+                                    });
+                                self.acquisition.scans.last_mut().unwrap().injection_time = Some(value);
+                            }
+                            b"filter string" => {
+                                self.acquisition.scans.last_mut().unwrap().filter_string =
+                                    Some(param.value.to_string());
                             }
                             _ => self
                                 .acquisition
@@ -831,10 +1132,14 @@ impl<
                             .unwrap()
                             .add_param(param.into()),
                         MzMLParserState::ScanWindow => {
-                            self.fill_scan_window(param.into());
+                            if let Some(param) = self.fill_scan_window(param.into()) {
+                                self.acquisition.scans.last_mut().unwrap().add_param(param)
+                            }
                         }
                         MzMLParserState::IsolationWindow => {
-                            self.fill_isolation_window(param.into());
+                            if let Some(param) = self.fill_isolation_window(param.into()) {
+                                self.precursor.isolation_window.add_param(param)
+                            }
                         }
                         MzMLParserState::SelectedIon | MzMLParserState::SelectedIonList => {
                             self.fill_selected_ion(param.into());
@@ -881,13 +1186,18 @@ impl<
                     Err(err) => return Err(err),
                 }
             }
+            b"referenceableParamGroupRef" => return self.expand_param_group_ref(event, state),
+            b"scan" => {
+                let scan_event = self.scan_event_from(event, state)?;
+                self.acquisition.scans.push(scan_event);
+            }
             &_ => {}
         }
         Ok(state)
     }
 
     fn end_element(&mut self, event: &BytesEnd, state: MzMLParserState) -> ParserResult {
-        let elt_name = event.name();
+        let elt_name = event.local_name();
         match elt_name.as_ref() {
             b"spectrum" => return Ok(MzMLParserState::SpectrumDone),
             b"chromatogram" => return Ok(MzMLParserState::ChromatogramDone),
@@ -902,15 +1212,25 @@ impl<
             b"selectedIon" => return Ok(MzMLParserState::SelectedIonList),
             b"activation" => return Ok(MzMLParserState::Precursor),
             b"binaryDataArrayList" => {
+                if self.detail_level == DetailLevel::Full {
+                    self.arrays
+                        .deinterleave()
+                        .map_err(|e| MzMLParserError::ArrayDecodingError(state, e))?;
+                }
+                if self.is_spectrum_entry() {
+                    self.fill_missing_signal_arrays();
+                }
                 return Ok(MzMLParserState::Spectrum);
             }
             b"binaryDataArray" => {
                 let mut array = mem::take(&mut self.current_array);
                 if self.detail_level == DetailLevel::Full {
-                    array
-                        .decode_and_store()
-                        .expect("Error during decoding and storing of array data");
+                    if let Err(e) = array.decode_and_store() {
+                        return Err(MzMLParserError::ArrayDecodingError(state, e));
+                    }
                 }
+                self.validate_array_length(&array, state)?;
+                self.rename_repeated_signal_array(&mut array);
                 self.arrays.add(array);
                 return Ok(MzMLParserState::BinaryDataArrayList);
             }
@@ -971,6 +1291,12 @@ or [`CentroidSpectrum`](crate::spectrum::CentroidSpectrum) as is appropriate to
 
 When the readable stream the parser is wrapped around supports [`io::Seek`],
 additional random access operations are available.
+
+Data arrays are kept in the type they are stored in the file, so there is no option
+to keep 64-bit intensities: a `64-bit float` intensity array is read as
+[`BinaryDataArrayType::Float64`] and [`BinaryArrayMap::intensities_f64`](crate::spectrum::BinaryArrayMap::intensities_f64)
+returns it without losing precision. Only converting a spectrum into centroid peaks, which
+store intensities as `f32`, narrows them.
 */
 pub struct MzMLReaderType<
     R: Read,
@@ -985,10 +1311,18 @@ pub struct MzMLReaderType<
     error: Option<MzMLParserError>,
     /// A spectrum ID to byte offset for fast random access
     pub spectrum_index: OffsetIndex,
+    /// The position in `spectrum_index` of each spectrum by its declared `index` attribute,
+    /// only present when these differ from the order of the spectra in the file
+    declared_spectrum_indices: Option<HashMap<usize, usize>>,
+    /// Whether the file has been scanned for declared `index` attributes since the offset
+    /// index was last replaced, so the scan isn't repeated when it found no gaps
+    declared_spectrum_indices_scanned: bool,
     pub chromatogram_index: Box<OffsetIndex>,
     /// The description of the file's contents and the previous data files that were
     /// consumed to produce it.
     pub(crate) file_description: FileDescription,
+    /// The controlled vocabularies declared in the `<cvList>` and their versions
+    pub(crate) cv_list: Vec<CvReference>,
     /// A mapping of different instrument configurations (source, analyzer, detector) components
     /// by ID string.
     pub(crate) instrument_configurations: HashMap<u32, InstrumentConfiguration>,
@@ -1002,6 +1336,16 @@ pub struct MzMLReaderType<
     /// A cache of repeated paramters
     pub reference_param_groups: HashMap<String, Vec<Param>>,
     pub detail_level: DetailLevel,
+    /// What to do when a spectrum fails to parse during iteration
+    pub error_policy: ErrorPolicy,
+    /// Whether to compute the total ion current, base peak and m/z range of each spectrum
+    /// that doesn't already describe them and store them in its parameters while parsing
+    pub cache_summaries: bool,
+    /// Whether to check that each decoded data array has as many entries as its spectrum
+    /// or chromatogram declared
+    pub array_length_validation: ArrayLengthValidation,
+    /// The most peaks to keep in each spectrum, see [`MzMLReaderType::set_max_peaks_per_spectrum`]
+    max_peaks_per_spectrum: Option<usize>,
 
     // SpectrumList attributes
     pub run: MassSpectrometryRun,
@@ -1011,6 +1355,14 @@ pub struct MzMLReaderType<
     centroid_type: PhantomData<C>,
     deconvoluted_type: PhantomData<D>,
     instrument_id_map: IncrementingIdMap,
+    /// The unrecognized accessions already warned about
+    reported_accessions: HashSet<String>,
+    /// The number of bytes consumed from the stream so far
+    stream_offset: u64,
+    /// The byte offset of the `<spectrum>` element most recently started
+    spectrum_offset: Option<u64>,
+    /// The byte offset and element name at which the last error was encountered
+    error_location: Option<(u64, String)>,
 }
 
 impl<
@@ -1042,21 +1394,32 @@ impl<
             error: None,
             buffer: Bytes::new(),
             spectrum_index: OffsetIndex::new("spectrum".to_owned()),
+            declared_spectrum_indices: None,
+            declared_spectrum_indices_scanned: false,
             chromatogram_index: Box::new(OffsetIndex::new("chromatogram".to_owned())),
 
             file_description: FileDescription::default(),
+            cv_list: Vec::new(),
             instrument_configurations: HashMap::new(),
             softwares: Vec::new(),
             samples: Vec::new(),
             data_processings: Vec::new(),
             reference_param_groups: HashMap::new(),
             detail_level,
+            error_policy: ErrorPolicy::Stop,
+            cache_summaries: false,
+            array_length_validation: ArrayLengthValidation::Skip,
+            max_peaks_per_spectrum: None,
 
             centroid_type: PhantomData,
             deconvoluted_type: PhantomData,
             instrument_id_map: IncrementingIdMap::default(),
+            reported_accessions: HashSet::new(),
             num_spectra: None,
             run: MassSpectrometryRun::default(),
+            stream_offset: 0,
+            spectrum_offset: None,
+            error_location: None,
         };
         match inst.parse_metadata() {
             Ok(()) => {}
@@ -1072,6 +1435,7 @@ impl<
         reader.trim_text(true);
         let mut accumulator = FileMetadataBuilder {
             instrument_id_map: Some(&mut self.instrument_id_map),
+            reported_accessions: Some(&mut self.reported_accessions),
             ..Default::default()
         };
         loop {
@@ -1081,7 +1445,9 @@ impl<
                         Ok(state) => {
                             self.state = state;
                             match &self.state {
-                                MzMLParserState::SpectrumList | MzMLParserState::Spectrum => break,
+                                MzMLParserState::SpectrumList
+                                | MzMLParserState::ChromatogramList
+                                | MzMLParserState::Spectrum => break,
                                 MzMLParserState::ParserError => {
                                     eprintln!(
                                         "Encountered an error while starting {:?}",
@@ -1159,13 +1525,13 @@ impl<
                 _ => {}
             };
             self.buffer.clear();
-            match self.state {
-                MzMLParserState::Run | MzMLParserState::ParserError => {
-                    break;
-                }
-                _ => {}
-            };
+            if self.state == MzMLParserState::ParserError {
+                break;
+            }
         }
+        self.buffer.clear();
+        self.stream_offset += reader.buffer_position() as u64;
+        self.cv_list = accumulator.cv_list;
         self.file_description = accumulator.file_description;
         self.instrument_configurations = accumulator
             .instrument_configurations
@@ -1207,17 +1573,37 @@ impl<
 
         let mut reader = Reader::from_reader(&mut self.handle);
         reader.trim_text(true);
-        accumulator = accumulator.borrow_instrument_configuration(&mut self.instrument_id_map);
+        accumulator = accumulator
+            .borrow_instrument_configuration(&mut self.instrument_id_map)
+            .borrow_reference_param_groups(&self.reference_param_groups)
+            .borrow_reported_accessions(&mut self.reported_accessions);
         let mut offset: usize = 0;
+        let stream_offset = self.stream_offset;
+        let locate = |position: usize, element: &[u8]| {
+            (
+                stream_offset + position as u64,
+                String::from_utf8_lossy(element).into_owned(),
+            )
+        };
+        let mut last_element = Vec::new();
+        self.error_location = None;
         loop {
             match reader.read_event_into(&mut self.buffer) {
                 Ok(Event::Start(ref e)) => {
+                    last_element.clear();
+                    last_element.extend_from_slice(e.local_name().as_ref());
                     if log::log_enabled!(log::Level::Trace) {
                         log::trace!(
                             "Starting mzML element: {}",
                             String::from_utf8_lossy(e.name().as_ref())
                         );
                     }
+                    if e.local_name().as_ref() == b"spectrum" {
+                        // The < and > bytes are not part of the event
+                        self.spectrum_offset = Some(
+                            self.stream_offset + (reader.buffer_position() - e.len() - 2) as u64,
+                        );
+                    }
                     match accumulator.start_element(e, self.state) {
                         Ok(state) => {
                             self.state = state;
@@ -1231,6 +1617,8 @@ impl<
                         Err(message) => {
                             self.state = MzMLParserState::ParserError;
                             self.error = Some(message);
+                            self.error_location =
+                                Some(locate(reader.buffer_position(), &last_element));
                         }
                     };
                 }
@@ -1248,6 +1636,8 @@ impl<
                         Err(message) => {
                             self.state = MzMLParserState::ParserError;
                             self.error = Some(message);
+                            self.error_location =
+                                Some(locate(reader.buffer_position(), e.local_name().as_ref()));
                         }
                     };
                 }
@@ -1259,6 +1649,8 @@ impl<
                         Err(message) => {
                             self.state = MzMLParserState::ParserError;
                             self.error = Some(message);
+                            self.error_location =
+                                Some(locate(reader.buffer_position(), &last_element));
                         }
                     };
                 }
@@ -1270,6 +1662,8 @@ impl<
                         Err(message) => {
                             self.state = MzMLParserState::ParserError;
                             self.error = Some(message);
+                            self.error_location =
+                                Some(locate(reader.buffer_position(), e.local_name().as_ref()));
                         }
                     }
                 }
@@ -1290,6 +1684,8 @@ impl<
                                 String::from_utf8_lossy(&self.buffer).to_string(),
                                 self.state,
                             ));
+                            self.error_location =
+                                Some(locate(reader.buffer_position(), &last_element));
                             self.state = MzMLParserState::ParserError;
                             log::trace!("Expected element {expected}, found {_found}");
                         }
@@ -1299,6 +1695,7 @@ impl<
                             e.to_string(),
                             self.state,
                         ));
+                        self.error_location = Some(locate(reader.buffer_position(), &last_element));
                         self.state = MzMLParserState::ParserError;
                     }
                 },
@@ -1315,6 +1712,7 @@ impl<
                 _ => {}
             };
         }
+        self.stream_offset += reader.buffer_position() as u64;
         match self.state {
             MzMLParserState::SpectrumDone | MzMLParserState::ChromatogramDone => {
                 Ok((accumulator, offset))
@@ -1342,7 +1740,8 @@ impl<
         &mut self,
         spectrum: &mut MultiLayerSpectrum<C, D>,
     ) -> Result<usize, MzMLParserError> {
-        let accumulator = MzMLSpectrumBuilder::<C, D>::with_detail_level(self.detail_level);
+        let mut accumulator = MzMLSpectrumBuilder::<C, D>::with_detail_level(self.detail_level);
+        accumulator.array_length_validation = self.array_length_validation;
         match self.state {
             MzMLParserState::SpectrumDone => {
                 self.state = MzMLParserState::Resume;
@@ -1361,12 +1760,39 @@ impl<
         match self._parse_into(accumulator) {
             Ok((accumulator, sz)) => {
                 accumulator.into_spectrum(spectrum);
+                if let Some(max_peaks) = self.max_peaks_per_spectrum {
+                    truncate_to_most_intense(spectrum, max_peaks);
+                }
+                if spectrum.description.data_processing_id.is_none() {
+                    spectrum.description.data_processing_id =
+                        self.run.default_data_processing_id.clone();
+                }
+                if self.cache_summaries {
+                    cache_spectrum_summaries(spectrum);
+                }
                 Ok(sz)
             }
             Err(err) => Err(err),
         }
     }
 
+    /// The most peaks kept in each spectrum read, if capped with
+    /// [`MzMLReaderType::set_max_peaks_per_spectrum`]
+    pub fn max_peaks_per_spectrum(&self) -> Option<usize> {
+        self.max_peaks_per_spectrum
+    }
+
+    /// Cap the number of peaks kept in each spectrum read to bound the memory held by
+    /// spectra from pathological files, or remove the cap with [`None`].
+    ///
+    /// Centroid spectra with more peaks or data array points than `cap` keep only the `cap`
+    /// most intense, and record their original count in a `truncated from peak count` user
+    /// parameter. Profile spectra are left whole. Each spectrum is truncated after it is
+    /// read, so this does not reduce the peak memory used while reading one.
+    pub fn set_max_peaks_per_spectrum(&mut self, cap: Option<usize>) {
+        self.max_peaks_per_spectrum = cap;
+    }
+
     /// Read the next spectrum directly. Used to implement iteration.
     pub fn read_next(&mut self) -> Option<MultiLayerSpectrum<C, D>> {
         if self.state == MzMLParserState::EOF {
@@ -1382,8 +1808,125 @@ impl<
         }
     }
 
+    /// Iterate over the remaining spectra, pairing each with the byte offset of its
+    /// `<spectrum>` element's opening tag in the stream.
+    ///
+    /// The offsets are counted from the bytes this reader has consumed, so they do not
+    /// depend on an index and are available for non-seekable streams too.
+    pub fn iter_with_offsets(
+        &mut self,
+    ) -> impl Iterator<Item = (u64, MultiLayerSpectrum<C, D>)> + '_ {
+        std::iter::from_fn(move || {
+            self.spectrum_offset = None;
+            let spectrum = self.read_next()?;
+            let offset = self.spectrum_offset?;
+            Some((offset, spectrum))
+        })
+    }
+
+    /// Iterate over the remaining spectra, yielding an error with the byte offset and
+    /// element at which parsing failed for each malformed spectrum instead of stopping.
+    ///
+    /// After an error, the rest of the malformed spectrum is skipped and iteration resumes
+    /// with the next spectrum, regardless of [`MzMLReaderType::error_policy`].
+    pub fn iter_results(
+        &mut self,
+    ) -> impl Iterator<Item = Result<MultiLayerSpectrum<C, D>, MzMLParserErrorContext>> + '_ {
+        std::iter::from_fn(move || {
+            if self.state == MzMLParserState::EOF {
+                return None;
+            }
+            let mut spectrum = MultiLayerSpectrum::<C, D>::default();
+            match self.read_into(&mut spectrum) {
+                Ok(_sz) => Some(Ok(spectrum)),
+                Err(err) if self.state == MzMLParserState::ParserError => {
+                    let (offset, element) = self
+                        .error_location
+                        .take()
+                        .unwrap_or_else(|| (self.stream_offset, String::new()));
+                    self.skip_to_spectrum_end();
+                    Some(Err(MzMLParserErrorContext {
+                        offset,
+                        element,
+                        source: err,
+                    }))
+                }
+                Err(err) => {
+                    trace!("Failed to read next spectrum: {err}");
+                    None
+                }
+            }
+        })
+    }
+
+    /// Read spectra until one whose description satisfies `predicate` is found and return it.
+    ///
+    /// Peak data are only decoded for the spectrum that is returned, so this is cheaper than
+    /// filtering the output of the iterator when most spectra are rejected. Returns [`None`]
+    /// when the stream is exhausted.
+    pub fn read_if<F: Fn(&SpectrumDescription) -> bool>(
+        &mut self,
+        predicate: F,
+    ) -> Option<MultiLayerSpectrum<C, D>> {
+        let detail_level = self.detail_level;
+        if detail_level == DetailLevel::Full {
+            self.detail_level = DetailLevel::Lazy;
+        }
+        let result = self.find(|spectrum| predicate(&spectrum.description));
+        self.detail_level = detail_level;
+
+        let mut spectrum = result?;
+        if detail_level == DetailLevel::Full {
+            if let Some(arrays) = spectrum.arrays.as_mut() {
+                if let Err(err) = arrays.decode_all_arrays() {
+                    warn!(
+                        "Failed to decode arrays of {}: {err}",
+                        spectrum.description.id
+                    );
+                }
+            }
+        }
+        Some(spectrum)
+    }
+
+    /// Discard the rest of the spectrum being read after a parsing error, leaving the
+    /// reader ready to parse the next spectrum.
+    ///
+    /// If the stream can't be read past the malformed spectrum, the error is stored and
+    /// the reader is left at [`MzMLParserState::EOF`] so iteration stops.
+    fn skip_to_spectrum_end(&mut self) {
+        let mut reader = Reader::from_reader(&mut self.handle);
+        reader.trim_text(true);
+        // The opening tags were consumed by another reader, so their end tags can't be matched
+        reader.check_end_names(false);
+        self.error = None;
+        loop {
+            match reader.read_event_into(&mut self.buffer) {
+                Ok(Event::End(ref e)) if e.local_name().as_ref() == b"spectrum" => {
+                    self.state = MzMLParserState::SpectrumDone;
+                    break;
+                }
+                Ok(Event::Eof) => {
+                    self.state = MzMLParserState::EOF;
+                    break;
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    warn!("Failed to skip past a malformed spectrum, stopping: {err}");
+                    self.error = Some(MzMLParserError::XMLError(MzMLParserState::Spectrum, err));
+                    self.state = MzMLParserState::EOF;
+                    break;
+                }
+            }
+            self.buffer.clear();
+        }
+        self.stream_offset += reader.buffer_position() as u64;
+        self.buffer.clear();
+    }
+
     fn _read_next_chromatogram(&mut self) -> Result<Chromatogram, MzMLParserError> {
-        let accumulator = MzMLSpectrumBuilder::<C, D>::with_detail_level(self.detail_level);
+        let mut accumulator = MzMLSpectrumBuilder::<C, D>::with_detail_level(self.detail_level);
+        accumulator.array_length_validation = self.array_length_validation;
 
         match self.state {
             MzMLParserState::ChromatogramDone => {
@@ -1441,7 +1984,7 @@ impl<
                         position,
                         String::from_utf8_lossy(e.name().0)
                     );
-                    e.name().0 == next_tag.as_bytes()
+                    e.local_name().as_ref() == next_tag.as_bytes()
                 }
                 Event::End(ref e) => {
                     trace!(
@@ -1457,7 +2000,7 @@ impl<
                         position,
                         String::from_utf8_lossy(e.name().0)
                     );
-                    e.name().0 == next_tag.as_bytes()
+                    e.local_name().as_ref() == next_tag.as_bytes()
                 }
                 Event::Text(ref e) => {
                     trace!(
@@ -1564,8 +2107,32 @@ impl<
 {
     type Item = MultiLayerSpectrum<C, D>;
 
+    /// Read the next spectrum from the file.
+    ///
+    /// If a spectrum fails to parse and [`MzMLReaderType::error_policy`] is
+    /// [`ErrorPolicy::SkipAndContinue`], the rest of that spectrum is skipped
+    /// and the next one is read instead.
     fn next(&mut self) -> Option<Self::Item> {
-        self.read_next()
+        loop {
+            if self.state == MzMLParserState::EOF {
+                return None;
+            }
+            let mut spectrum = MultiLayerSpectrum::<C, D>::default();
+            match self.read_into(&mut spectrum) {
+                Ok(_sz) => return Some(spectrum),
+                Err(err)
+                    if self.error_policy == ErrorPolicy::SkipAndContinue
+                        && self.state == MzMLParserState::ParserError =>
+                {
+                    warn!("Skipping malformed spectrum: {err}");
+                    self.skip_to_spectrum_end();
+                }
+                Err(err) => {
+                    trace!("Failed to read next spectrum: {err}");
+                    return None;
+                }
+            }
+        }
     }
 }
 
@@ -1597,24 +2164,49 @@ impl<
         result
     }
 
-    /// Retrieve a spectrum by it's integer index
+    /// Retrieve a spectrum by it's integer index.
+    ///
+    /// The `index` attribute declared on each `<spectrum>` takes precedence over the
+    /// spectrum's position in the file. The offset list of an `<indexedmzML>` document doesn't
+    /// record these, so if the spectrum found at a position declares a different index, the
+    /// file is scanned once with [`MzMLReaderType::build_index`] to find them. If that scan
+    /// finds no gaps, the spectrum at that position is returned.
     fn get_spectrum_by_index(&mut self, index: usize) -> Option<MultiLayerSpectrum<C, D>> {
-        let (_id, offset) = self.spectrum_index.get_index(index)?;
-        let byte_offset = offset;
-        let start = self
-            .handle
-            .stream_position()
-            .expect("Failed to save checkpoint");
-        self.seek(SeekFrom::Start(byte_offset)).ok()?;
-        debug_assert!(
-            self.check_stream("spectrum").unwrap(),
-            "The next XML tag was not `spectrum`"
-        );
-        self.state = MzMLParserState::Resume;
-        let result = self.read_next();
-        self.seek(SeekFrom::Start(start))
-            .expect("Failed to restore offset");
-        result
+        let result = self.read_spectrum_at_offset(self._offset_of_index(index)?);
+        match result {
+            Some(spectrum)
+                if spectrum.index() != index && !self.declared_spectrum_indices_scanned =>
+            {
+                debug!(
+                    "Spectrum {} declared index {} but was found at position {index}, rebuilding the index",
+                    spectrum.id(),
+                    spectrum.index()
+                );
+                self.build_index();
+                if self.declared_spectrum_indices.is_some() {
+                    self.read_spectrum_at_offset(self._offset_of_index(index)?)
+                } else {
+                    Some(spectrum)
+                }
+            }
+            result => result,
+        }
+    }
+
+    fn get_spectrum_by_position(&mut self, position: usize) -> Option<MultiLayerSpectrum<C, D>> {
+        let (_id, offset) = self.get_index().get_index(position)?;
+        self.read_spectrum_at_offset(offset)
+    }
+
+    /// Find the byte offset of the spectrum whose declared `index` attribute is `index`
+    fn _offset_of_index(&self, index: usize) -> Option<u64> {
+        let position = match self.declared_spectrum_indices.as_ref() {
+            Some(positions) => *positions.get(&index)?,
+            None => index,
+        };
+        self.get_index()
+            .get_index(position)
+            .map(|(_id, offset)| offset)
     }
 
     /// Return the data stream to the beginning
@@ -1632,7 +2224,84 @@ impl<
     }
 
     fn set_index(&mut self, index: OffsetIndex) {
-        self.spectrum_index = index
+        self.spectrum_index = index;
+        self.declared_spectrum_indices = None;
+        self.declared_spectrum_indices_scanned = false;
+    }
+
+    /// Sum the `defaultArrayLength` of each spectrum, skipping over the binary data
+    /// arrays entirely.
+    fn total_peak_count(&mut self, ms_level: Option<u8>) -> u64 {
+        self.reset();
+        let mut total = 0;
+        while self.state != MzMLParserState::EOF {
+            if self.state == MzMLParserState::SpectrumDone {
+                self.state = MzMLParserState::Resume;
+            }
+            let accumulator =
+                MzMLSpectrumBuilder::<C, D>::with_detail_level(DetailLevel::MetadataOnly);
+            match self._parse_into(accumulator) {
+                Ok((accumulator, _)) => {
+                    if ms_level.map_or(true, |level| accumulator.ms_level == level) {
+                        total += accumulator.default_array_length.unwrap_or_default() as u64;
+                    }
+                }
+                Err(err) => {
+                    trace!("Stopped counting peaks: {err}");
+                    break;
+                }
+            }
+        }
+        self.reset();
+        total
+    }
+
+    /// Read each spectrum's metadata with [`MzMLReaderType::read_if`], decoding the peak
+    /// data only for spectra whose precursor falls within the window.
+    fn iter_precursor_range(
+        &mut self,
+        low: f64,
+        high: f64,
+    ) -> impl Iterator<Item = MultiLayerSpectrum<C, D>>
+    where
+        Self: Sized,
+    {
+        self.reset();
+        std::iter::from_fn(move || {
+            self.read_if(|description| {
+                description.precursor.as_ref().is_some_and(|prec| {
+                    prec.ions.iter().any(|ion| low <= ion.mz && ion.mz <= high)
+                })
+            })
+        })
+    }
+}
+
+/// An [`MzMLReaderType`] reading from a memory mapped file, see [`MzMLReaderType::open_path_mmap`]
+#[cfg(feature = "mmap")]
+pub type MmapMzMLReaderType<C, D> = MzMLReaderType<io::Cursor<Mmap>, C, D>;
+
+#[cfg(feature = "mmap")]
+impl<
+        C: CentroidPeakAdapting + BuildFromArrayMap,
+        D: DeconvolutedPeakAdapting + BuildFromArrayMap,
+    > MzMLReaderType<io::Cursor<Mmap>, C, D>
+{
+    /// Open the mzML file at `path` through a read-only memory map instead of reading it
+    /// into buffers, letting the operating system page in only the parts of a large file
+    /// that are actually visited. Seeking to a spectrum's byte offset for random access
+    /// reads directly from the mapped region.
+    ///
+    /// The offset index is read from the end of an indexedmzML file, or built by scanning
+    /// the map otherwise.
+    ///
+    /// The file must not be truncated or modified by another process while it is mapped.
+    pub fn open_path_mmap<P: AsRef<std::path::Path>>(path: P) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        // Safety: the map is read-only, and the caller is warned that the file must not
+        // change underneath it
+        let mmap = unsafe { Mmap::map(&file)? };
+        Ok(Self::new_indexed(io::Cursor::new(mmap)))
     }
 }
 
@@ -1702,16 +2371,34 @@ impl<
         reader
     }
 
-    fn _read_index(&mut self) {
-        if let Err(err) = self.read_index_from_end() {
-            debug!("Failed to read index from the end of the file: {}", err);
-            match self.seek(SeekFrom::Start(0)) {
-                Ok(_) => {
-                    self.build_index();
-                }
-                Err(error) => {
-                    panic!(
-                        "Unrecoverable IO Error during file pointer reset {} while handling {:?}",
+    /// Read the spectrum starting at `offset`, restoring the stream position afterwards
+    fn read_spectrum_at_offset(&mut self, offset: u64) -> Option<MultiLayerSpectrum<C, D>> {
+        let start = self
+            .handle
+            .stream_position()
+            .expect("Failed to save checkpoint");
+        self.seek(SeekFrom::Start(offset)).ok()?;
+        debug_assert!(
+            self.check_stream("spectrum").unwrap(),
+            "The next XML tag was not `spectrum`"
+        );
+        self.state = MzMLParserState::Resume;
+        let result = self.read_next();
+        self.seek(SeekFrom::Start(start))
+            .expect("Failed to restore offset");
+        result
+    }
+
+    fn _read_index(&mut self) {
+        if let Err(err) = self.read_index_from_end() {
+            debug!("Failed to read index from the end of the file: {}", err);
+            match self.seek(SeekFrom::Start(0)) {
+                Ok(_) => {
+                    self.build_index();
+                }
+                Err(error) => {
+                    panic!(
+                        "Unrecoverable IO Error during file pointer reset {} while handling {:?}",
                         error, err
                     );
                 }
@@ -1730,7 +2417,80 @@ impl<
     }
 
     pub fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
-        self.handle.seek(pos)
+        let position = self.handle.seek(pos)?;
+        self.stream_offset = position;
+        Ok(position)
+    }
+
+    /// Read the bytes of the `<spectrum>` element with the native ID `id` verbatim, from
+    /// its opening tag through its closing tag, using the offset index.
+    ///
+    /// This is useful for copying spectra between files without re-encoding them.
+    pub fn get_spectrum_bytes_by_id(&mut self, id: &str) -> Option<Vec<u8>> {
+        let offset = self.spectrum_index.get(id)?;
+        let start = self
+            .handle
+            .stream_position()
+            .expect("Failed to save checkpoint");
+        let result = self.read_element_bytes(offset, b"spectrum");
+        self.seek(SeekFrom::Start(start))
+            .expect("Failed to restore offset");
+        result
+    }
+
+    fn read_element_bytes(&mut self, offset: u64, tag: &[u8]) -> Option<Vec<u8>> {
+        self.seek(SeekFrom::Start(offset)).ok()?;
+        let length = {
+            let mut reader = Reader::from_reader(&mut self.handle);
+            reader.check_end_names(false);
+            let mut buffer = Vec::new();
+            loop {
+                match reader.read_event_into(&mut buffer) {
+                    Ok(Event::End(ref e)) if e.local_name().as_ref() == tag => {
+                        break reader.buffer_position();
+                    }
+                    Ok(Event::Eof) | Err(_) => return None,
+                    _ => {}
+                }
+                buffer.clear();
+            }
+        };
+        self.seek(SeekFrom::Start(offset)).ok()?;
+        let mut bytes = vec![0; length];
+        self.handle.read_exact(&mut bytes).ok()?;
+        Some(bytes)
+    }
+
+    /// Read the next spectrum from a file that may still be being written.
+    ///
+    /// If the stream ends before the next spectrum is complete, the reader moves back to
+    /// where that spectrum started and returns [`None`], so calling this again after more
+    /// data has been written resumes from the same spectrum.
+    pub fn poll_next(&mut self) -> Option<MultiLayerSpectrum<C, D>> {
+        let start = self.stream_position().ok()?;
+        let state = match self.state {
+            MzMLParserState::EOF => MzMLParserState::Resume,
+            state => state,
+        };
+        self.state = state;
+        let mut spectrum = MultiLayerSpectrum::<C, D>::default();
+        match self.read_into(&mut spectrum) {
+            Ok(_sz) => Some(spectrum),
+            Err(err) => {
+                trace!("The spectrum starting at {start} is not complete: {err}");
+                self.error = None;
+                self.state = state;
+                self.seek(SeekFrom::Start(start)).ok()?;
+                None
+            }
+        }
+    }
+
+    /// Iterate over the spectra that have been completely written so far using
+    /// [`Self::poll_next`]. The iterator stops at the current end of the stream, and
+    /// calling this again later resumes from there.
+    pub fn iter_available(&mut self) -> impl Iterator<Item = MultiLayerSpectrum<C, D>> + '_ {
+        std::iter::from_fn(move || self.poll_next())
     }
 
     pub fn stream_position(&mut self) -> io::Result<u64> {
@@ -1825,6 +2585,8 @@ impl<
         self.buffer.clear();
         self.spectrum_index = indexer.spectrum_index;
         self.spectrum_index.init = true;
+        self.declared_spectrum_indices = None;
+        self.declared_spectrum_indices_scanned = false;
         *self.chromatogram_index = indexer.chromatogram_index;
         self.chromatogram_index.init = true;
         self.handle.seek(SeekFrom::Start(current_position)).unwrap();
@@ -1842,28 +2604,30 @@ impl<
             .expect("Failed to reset stream to beginning");
         let mut reader = Reader::from_reader(&mut self.handle);
         reader.trim_text(true);
+        let mut declared_positions = Vec::new();
         loop {
             match reader.read_event_into(&mut self.buffer) {
                 Ok(Event::Start(ref e)) => {
-                    let element_name = e.name();
+                    let element_name = e.local_name();
                     if element_name.as_ref() == b"spectrum" {
-                        // Hit a spectrum, extract ID and save current offset
-
+                        // Hit a spectrum, extract ID and declared index and save current offset
+                        let mut scan_id = None;
+                        let mut declared_index = None;
                         for attr_parsed in e.attributes() {
                             match attr_parsed {
                                 Ok(attr) => {
                                     match attr.key.as_ref() {
                                         b"id" => {
-                                            let scan_id = attr
-                                                .unescape_value()
-                                                .expect("Error decoding id")
-                                                .to_string();
-                                            // This count is off by 2 because somehow the < and > bytes are removed?
-                                            self.spectrum_index.insert(
-                                                scan_id,
-                                                (reader.buffer_position() - e.len() - 2) as u64,
+                                            scan_id = Some(
+                                                attr.unescape_value()
+                                                    .expect("Error decoding id")
+                                                    .to_string(),
                                             );
-                                            break;
+                                        }
+                                        b"index" => {
+                                            declared_index = String::from_utf8_lossy(&attr.value)
+                                                .parse::<usize>()
+                                                .ok();
                                         }
                                         &_ => {}
                                     };
@@ -1871,10 +2635,17 @@ impl<
                                 Err(_msg) => {}
                             }
                         }
+                        if let Some(scan_id) = scan_id {
+                            let position = declared_positions.len();
+                            // This count is off by 2 because somehow the < and > bytes are removed?
+                            self.spectrum_index
+                                .insert(scan_id, (reader.buffer_position() - e.len() - 2) as u64);
+                            declared_positions.push((declared_index.unwrap_or(position), position));
+                        }
                     }
                 }
                 Ok(Event::End(ref e)) => {
-                    let element_name = e.name();
+                    let element_name = e.local_name();
                     if element_name.as_ref() == b"spectrumList" {
                         break;
                     }
@@ -1887,12 +2658,25 @@ impl<
             self.buffer.clear();
         }
         let offset = reader.buffer_position() as u64;
-        self.handle
-            .seek(SeekFrom::Start(start))
+        self.seek(SeekFrom::Start(start))
             .expect("Failed to restore location");
+        self.declared_spectrum_indices = if declared_positions
+            .iter()
+            .any(|(declared, position)| declared != position)
+        {
+            Some(declared_positions.into_iter().collect())
+        } else {
+            None
+        };
+        self.declared_spectrum_indices_scanned = true;
         self.spectrum_index.init = true;
         if self.spectrum_index.is_empty() {
             warn!("An index was built but no entries were found")
+        } else {
+            debug!(
+                "Built an index of {} spectra over {offset} bytes",
+                self.spectrum_index.len()
+            );
         }
         offset
     }
@@ -1932,6 +2716,14 @@ impl<R: Read, C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MSDataFileMe
     fn run_description_mut(&mut self) -> Option<&mut MassSpectrometryRun> {
         Some(&mut self.run)
     }
+
+    fn controlled_vocabularies(&self) -> Option<&Vec<CvReference>> {
+        Some(&self.cv_list)
+    }
+
+    fn controlled_vocabularies_mut(&mut self) -> Option<&mut Vec<CvReference>> {
+        Some(&mut self.cv_list)
+    }
 }
 
 /// A specialization of [`MzMLReaderType`] for the default peak types, for common use.
@@ -1944,7 +2736,7 @@ pub(crate) fn is_mzml(buf: &[u8]) -> bool {
     loop {
         match reader.read_event_into(&mut buffer) {
             Ok(Event::Start(ref e)) => {
-                let elt_name = e.name();
+                let elt_name = e.local_name();
                 match elt_name.as_ref() {
                     b"mzML" => return true,
                     b"indexedmzML" => return true,
@@ -2003,7 +2795,9 @@ impl<
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::curie;
     use crate::io::traits::SpectrumGrouping;
+    use crate::meta::DissociationMethodTerm;
     use crate::spectrum::spectrum_types::SpectrumLike;
     use std::fs;
     use std::path;
@@ -2101,11 +2895,11 @@ mod test {
                 .acquisition()
                 .first_scan()
                 .unwrap()
-                .get_param_by_accession("MS:1000512")
+                .filter_string()
                 .unwrap();
             let configs = scan.acquisition().instrument_configuration_ids();
             let conf = configs[0];
-            if filter_string.value.to_string().contains("ITMS") {
+            if filter_string.contains("ITMS") {
                 assert_eq!(conf, 1);
             } else {
                 assert_eq!(conf, 0);
@@ -2216,6 +3010,1206 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_cv_list() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let reader = MzMLReader::open_path(path)?;
+        let cvs = reader.controlled_vocabularies().unwrap();
+        let ids: Vec<_> = cvs.iter().map(|cv| cv.id.as_str()).collect();
+        assert_eq!(ids, ["MS", "UO"]);
+        assert_eq!(cvs[0].version.as_deref(), Some("4.1.117"));
+        assert_eq!(
+            cvs[0].uri,
+            "https://raw.githubusercontent.com/HUPO-PSI/psi-ms-CV/master/psi-ms.obo"
+        );
+        assert_eq!(cvs[1].version.as_deref(), Some("09:04:2014"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_instrument_summary() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut reader = MzMLReader::open_path(path)?;
+        assert_eq!(reader.instrument_summary(), "ESI-FT-ICR");
+
+        let config = reader.instrument_configurations_mut().get_mut(&0).unwrap();
+        config
+            .new_component(crate::meta::ComponentType::Analyzer)
+            .add_param(crate::meta::MassAnalyzerTerm::Orbitrap.into());
+        assert_eq!(reader.instrument_summary(), "ESI-FT-ICR/Orbitrap");
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample_list() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let reader = MzMLReader::open_path(path)?;
+        let samples = reader.samples();
+        assert_eq!(samples.len(), 1);
+        let sample = &samples[0];
+        assert_eq!(sample.id, "_x0031_");
+        assert_eq!(sample.name.as_deref(), Some(""));
+        assert_eq!(sample.sample_name().as_deref(), Some("1"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_injection_time() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut reader = MzMLReader::open_path(path)?;
+        let scan = reader.get_spectrum_by_index(0).unwrap();
+        let event = scan.acquisition().first_scan().unwrap();
+        assert!((event.injection_time.unwrap() - 68.227486).abs() < 1e-3);
+        assert!((scan.injection_time().unwrap() - 68.227486).abs() < 1e-3);
+
+        let scan = reader.get_spectrum_by_index(1).unwrap();
+        assert!((scan.injection_time().unwrap() - 2.07659).abs() < 1e-3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_preset_scan_configuration() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut reader = MzMLReader::open_path(path)?;
+        let configurations: Vec<_> = (0..3)
+            .map(|i| {
+                reader
+                    .get_spectrum_by_index(i)
+                    .unwrap()
+                    .acquisition()
+                    .first_scan()
+                    .unwrap()
+                    .preset_scan_configuration()
+            })
+            .collect();
+        assert_eq!(configurations, vec![Some(1), Some(2), Some(3)]);
+
+        let mut event = ScanEvent::default();
+        assert_eq!(event.preset_scan_configuration(), None);
+        event.set_preset_scan_configuration(4);
+        event.set_preset_scan_configuration(5);
+        assert_eq!(event.preset_scan_configuration(), Some(5));
+        assert_eq!(event.params().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_filter() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut reader = MzMLReader::open_path(path)?;
+        let scan = reader.get_spectrum_by_index(2).unwrap();
+        let event = scan.acquisition().first_scan().unwrap();
+        let expected = "ITMS + c ESI d Full ms2 810.79@cid35.00 [210.00-1635.00]";
+        assert_eq!(event.filter_string.as_deref(), Some(expected));
+        assert_eq!(event.scan_filter(), Some(expected));
+        assert_eq!(event.filter_string().as_deref(), Some(expected));
+        // The filter string is only stored in the field, not duplicated in the parameters
+        assert!(event.get_param_by_accession("MS:1000512").is_none());
+
+        let mut writer = MzMLWriter::new(io::Cursor::new(Vec::new()));
+        writer.copy_metadata_from(&reader);
+        *writer.spectrum_count_mut() = 1;
+        writer.write(&scan)?;
+        writer.close()?;
+        let buffer = writer.get_mut()?.get_ref().clone();
+        assert_eq!(String::from_utf8_lossy(&buffer).matches("MS:1000512").count(), 1);
+        let scan = MzMLReader::new(io::Cursor::new(buffer)).next().unwrap();
+        let event = scan.acquisition().first_scan().unwrap();
+        assert_eq!(event.scan_filter(), Some(expected));
+
+        assert_eq!(ScanEvent::default().scan_filter(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_iter_time_range() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut reader = MzMLReader::open_path(path)?;
+        let indices: Vec<_> = reader
+            .iter_time_range(0.1, 0.2)
+            .map(|s| {
+                assert!(s.start_time() >= 0.1 && s.start_time() <= 0.2);
+                s.index()
+            })
+            .collect();
+        assert_eq!(indices, (11..20).collect::<Vec<_>>());
+
+        let n = reader.iter_time_range(0.0, 1.0).count();
+        assert_eq!(n, 48);
+
+        let n = reader.iter_time_range(5.0, 10.0).count();
+        assert_eq!(n, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_statistics() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut reader = MzMLReader::open_path(path)?;
+        let stats = reader.run_statistics();
+        assert_eq!(stats.count_for_level(1), 14);
+        assert_eq!(stats.count_for_level(2), 34);
+        assert_eq!(stats.spectrum_count(), 48);
+        assert!((stats.min_time.unwrap() - 0.004935).abs() < 1e-6);
+        assert!((stats.max_time.unwrap() - 0.487236666667).abs() < 1e-6);
+        let mean = stats.mean_time.unwrap();
+        assert!(mean > stats.min_time.unwrap() && mean < stats.max_time.unwrap());
+        assert!(stats.median_peak_count.unwrap() > 0.0);
+        assert!(stats.total_ion_current > 0.0);
+
+        // The reader is rewound afterwards
+        assert_eq!(reader.next().unwrap().index(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_spectrum_arc() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut reader = MzMLReader::open_path(path)?;
+        let scan = reader.get_spectrum_arc_by_index(10).unwrap();
+        assert_eq!(std::sync::Arc::strong_count(&scan), 1);
+
+        let shared: Vec<_> = (0..3).map(|_| scan.clone()).collect();
+        assert_eq!(std::sync::Arc::strong_count(&scan), 4);
+        assert!(shared.iter().all(|s| s.index() == 10));
+        drop(shared);
+        assert_eq!(std::sync::Arc::strong_count(&scan), 1);
+
+        let by_id = reader
+            .get_spectrum_arc_by_id("controllerType=0 controllerNumber=1 scan=11")
+            .unwrap();
+        assert_eq!(by_id.index(), scan.index());
+
+        let n = reader.iter_arc().filter(|s| std::sync::Arc::strong_count(s) == 1).count();
+        assert_eq!(n, 48);
+        Ok(())
+    }
+
+    #[test]
+    fn test_spectrum_params_preserved() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut reader = MzMLReader::open_path(path)?;
+        let scan = reader.get_spectrum_by_index(0).unwrap();
+        let accessions: Vec<_> = scan
+            .description()
+            .params()
+            .iter()
+            .map(|p| p.curie().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            accessions,
+            [
+                "MS:1000579",
+                "MS:1000504",
+                "MS:1000505",
+                "MS:1000285",
+                "MS:1000528",
+                "MS:1000527"
+            ]
+        );
+        assert_eq!(
+            scan.description().data_processing_id.as_deref(),
+            Some("pwiz_Reader_Thermo_conversion")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_spectrum_data_processing_ref() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut buf = Vec::new();
+        fs::File::open(path)?.read_to_end(&mut buf)?;
+        let text = String::from_utf8(buf)
+            .unwrap()
+            .replacen(
+                r#"<spectrum index="0" id="controllerType=0 controllerNumber=1 scan=1""#,
+                r#"<spectrum index="0" id="controllerType=0 controllerNumber=1 scan=1" dataProcessingRef="pwiz_Reader_Thermo_conversion""#,
+                1,
+            )
+            .replacen(
+                r#"<spectrumList count="48" defaultDataProcessingRef="pwiz_Reader_Thermo_conversion">"#,
+                r#"<spectrumList count="48" defaultDataProcessingRef="deconvolution">"#,
+                1,
+            );
+        let mut reader = MzMLReader::new(io::Cursor::new(text.into_bytes()));
+        assert_eq!(
+            reader
+                .run_description()
+                .unwrap()
+                .default_data_processing_id
+                .as_deref(),
+            Some("deconvolution")
+        );
+        assert_eq!(reader.spectrum_count_hint(), Some(48));
+        let scan = reader.next().unwrap();
+        assert_eq!(
+            scan.description().data_processing_id.as_deref(),
+            Some("pwiz_Reader_Thermo_conversion")
+        );
+        let scan = reader.next().unwrap();
+        assert_eq!(
+            scan.description().data_processing_id.as_deref(),
+            Some("deconvolution")
+        );
+        assert_eq!(reader.count(), 46);
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_data_processing_ref() -> io::Result<()> {
+        let reader = MzMLReader::open_path("./test/data/small.mzML")?;
+        let run = reader.run_description().unwrap();
+        assert_eq!(
+            run.default_data_processing_id.as_deref(),
+            Some("pwiz_Reader_Thermo_conversion")
+        );
+        for spectrum in MzMLReader::open_path("./test/data/small.mzML")? {
+            assert_eq!(
+                spectrum.description().data_processing_id.as_deref(),
+                Some("pwiz_Reader_Thermo_conversion")
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_intensity_array() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut buf = Vec::new();
+        fs::File::open(path)?.read_to_end(&mut buf)?;
+        let mut text = String::from_utf8(buf).unwrap();
+        // Drop the intensity array of the first spectrum, leaving only the m/z array
+        let start = text
+            .find(r#"<binaryDataArray encodedLength="106204">"#)
+            .unwrap();
+        let closer = "</binaryDataArray>";
+        let end = start + text[start..].find(closer).unwrap() + closer.len();
+        text.replace_range(start..end, "");
+
+        let mut reader = MzMLReader::new(io::Cursor::new(text.into_bytes()));
+        let scan = reader.next().unwrap();
+        let arrays = scan.arrays.as_ref().unwrap();
+        assert_eq!(arrays.mzs()?.len(), 19913);
+        assert!(arrays.has_array(&ArrayType::IntensityArray));
+        assert!(arrays.intensities()?.is_empty());
+
+        let scan = reader.next().unwrap();
+        let arrays = scan.arrays.as_ref().unwrap();
+        assert_eq!(arrays.mzs()?.len(), arrays.intensities()?.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_spectra_by_ids() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut reader = MzMLReader::open_path(path)?;
+        let ids = [
+            "controllerType=0 controllerNumber=1 scan=30",
+            "controllerType=0 controllerNumber=1 scan=missing",
+            "controllerType=0 controllerNumber=1 scan=12",
+            "controllerType=0 controllerNumber=1 scan=2",
+        ];
+        let spectra = reader.get_spectra_by_ids(&ids);
+        assert_eq!(spectra.len(), 4);
+        assert!(spectra[1].is_none());
+        for (id, spectrum) in ids.iter().zip(spectra.iter()) {
+            if let Some(spectrum) = spectrum {
+                assert_eq!(spectrum.id(), *id);
+            }
+        }
+        assert_eq!(spectra[0].as_ref().unwrap().index(), 29);
+        assert_eq!(spectra[2].as_ref().unwrap().index(), 11);
+        assert_eq!(spectra[3].as_ref().unwrap().index(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_has_index() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut reader = MzMLReader::new(fs::File::open(path)?);
+        assert!(!reader.has_index());
+        reader.build_index();
+        assert!(reader.has_index());
+        assert_eq!(reader.len(), 48);
+
+        let reader = MzMLReader::open_path(path)?;
+        assert!(reader.has_index());
+        Ok(())
+    }
+
+    #[test]
+    fn test_declared_spectrum_index() -> io::Result<()> {
+        let text = fs::read_to_string("./test/data/small.mzML")?;
+
+        // Declare non-contiguous indices, counting by tens
+        let mut sparse = text.clone();
+        for i in (0..48).rev() {
+            sparse = sparse.replacen(
+                &format!(r#"<spectrum index="{i}" "#),
+                &format!(r#"<spectrum index="{}" "#, i * 10),
+                1,
+            );
+        }
+        let mut reader = MzMLReader::new(io::Cursor::new(sparse.into_bytes()));
+        reader.build_index();
+        assert_eq!(reader.len(), 48);
+        let spectrum = reader.get_spectrum_by_index(30).unwrap();
+        assert_eq!(spectrum.index(), 30);
+        assert_eq!(spectrum.id(), "controllerType=0 controllerNumber=1 scan=4");
+        assert!(reader.get_spectrum_by_index(3).is_none());
+        reader.start_from_index(470).unwrap();
+        assert_eq!(reader.next().unwrap().index(), 470);
+
+        // Iteration walks the spectra by position, so the gaps don't end it early
+        let indices: Vec<usize> = reader.iter().map(|s| s.index()).collect();
+        assert_eq!(indices, (0..48).map(|i| i * 10).collect::<Vec<_>>());
+        assert_eq!(reader.iter().next_back().unwrap().index(), 470);
+        let mut iter = reader.iter();
+        iter.start_from_index(460).unwrap();
+        let indices: Vec<usize> = iter.map(|s| s.index()).collect();
+        assert_eq!(indices, vec![460, 470]);
+        let indices: Vec<usize> = reader.iter_time_range(0.1, 0.2).map(|s| s.index()).collect();
+        assert_eq!(indices, (11..20).map(|i| i * 10).collect::<Vec<_>>());
+        let mut ms1_count = 0;
+        let mut msn_count = 0;
+        for group in reader.groups() {
+            ms1_count += group.precursor.is_some() as usize;
+            msn_count += group.products.len();
+        }
+        assert_eq!(ms1_count, 14);
+        assert_eq!(msn_count, 34);
+
+        // Swap the declared indices of two spectra without moving any byte offsets, so
+        // the offset list of the indexedmzML document can still be used
+        let swapped = text
+            .replacen(r#"<spectrum index="1" "#, r#"<spectrum index="x" "#, 1)
+            .replacen(r#"<spectrum index="2" "#, r#"<spectrum index="1" "#, 1)
+            .replacen(r#"<spectrum index="x" "#, r#"<spectrum index="2" "#, 1);
+        let mut reader = MzMLReader::new_indexed(io::Cursor::new(swapped.into_bytes()));
+        let spectrum = reader.get_spectrum_by_index(1).unwrap();
+        assert_eq!(spectrum.index(), 1);
+        assert_eq!(spectrum.id(), "controllerType=0 controllerNumber=1 scan=3");
+        let spectrum = reader.get_spectrum_by_index(2).unwrap();
+        assert_eq!(spectrum.id(), "controllerType=0 controllerNumber=1 scan=2");
+        assert_eq!(
+            reader.get_spectrum_by_index(0).unwrap().id(),
+            "controllerType=0 controllerNumber=1 scan=1"
+        );
+
+        // Without any declared indices, lookups fall back to the position in the file
+        let mut undeclared = text.clone();
+        for i in 0..48 {
+            undeclared =
+                undeclared.replacen(&format!(r#"<spectrum index="{i}" "#), "<spectrum ", 1);
+        }
+        let mut reader = MzMLReader::new(io::Cursor::new(undeclared.into_bytes()));
+        reader.build_index();
+        for _ in 0..2 {
+            let spectrum = reader.get_spectrum_by_index(5).unwrap();
+            assert_eq!(spectrum.id(), "controllerType=0 controllerNumber=1 scan=6");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_spectrum_index_increments() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let reader = MzMLReader::new(fs::File::open(path)?);
+        let indices: Vec<usize> = reader.map(|s| s.index()).collect();
+        assert_eq!(indices, (0..48).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_array_length_validation() -> io::Result<()> {
+        let text = fs::read_to_string("./test/data/small.mzML")?;
+
+        let mut reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+        reader.array_length_validation = ArrayLengthValidation::Error;
+        assert_eq!(reader.count(), 48);
+
+        let text = text.replacen(
+            r#"defaultArrayLength="19913""#,
+            r#"defaultArrayLength="19912""#,
+            1,
+        );
+
+        let mut reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+        reader.array_length_validation = ArrayLengthValidation::Error;
+        let mut spectrum = Spectrum::default();
+        match reader.read_into(&mut spectrum) {
+            Err(MzMLParserError::ArrayLengthMismatch(_, found, expected)) => {
+                assert_eq!(found, 19913);
+                assert_eq!(expected, 19912);
+            }
+            other => panic!("Expected an array length mismatch, got {:?}", other),
+        }
+
+        let mut reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+        reader.array_length_validation = ArrayLengthValidation::Warn;
+        let spectrum = reader.next().unwrap();
+        assert_eq!(spectrum.arrays.unwrap().mzs()?.len(), 19913);
+        Ok(())
+    }
+
+    #[test]
+    fn test_profile_with_vendor_centroids() -> io::Result<()> {
+        let text = fs::read_to_string("./test/data/small.mzML")?;
+        let list_start = r#"<binaryDataArrayList count="2">"#;
+        let list_end = "</binaryDataArrayList>";
+
+        // Copy the centroided arrays of the second spectrum into the profile first spectrum
+        let first_end = text.find(list_end).unwrap();
+        let second_start = first_end + text[first_end..].find(list_start).unwrap();
+        let second_end = second_start + text[second_start..].find(list_end).unwrap();
+        let centroid_arrays = &text[second_start + list_start.len()..second_end];
+        let text = format!(
+            "{}{}{}",
+            &text[..first_end],
+            centroid_arrays.trim_start(),
+            &text[first_end..]
+        );
+
+        let mut reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+        let profile: RawSpectrum = reader.next().unwrap().into();
+        let centroid: RawSpectrum = reader.next().unwrap().into();
+        assert_eq!(
+            profile.description.signal_continuity,
+            SignalContinuity::Profile
+        );
+        assert_eq!(profile.mzs().len(), 19913);
+
+        let vendor = profile.centroids().unwrap();
+        assert_eq!(vendor.signal_continuity(), SignalContinuity::Centroid);
+        assert_eq!(vendor.id(), profile.description.id);
+        assert_eq!(vendor.peaks.len(), centroid.mzs().len());
+        assert_eq!(vendor.peaks[0].mz, centroid.mzs()[0]);
+        assert!(centroid.centroids().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_explicit_no_compression() -> io::Result<()> {
+        let text = fs::read_to_string("./test/data/small.mzML")?;
+        let mut reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+        let expected = reader.next().unwrap();
+        let expected = expected.arrays.as_ref().unwrap();
+
+        // The first spectrum's arrays declare `MS:1000576` explicitly, so they should be
+        // kept as plain base64 until decoded on demand
+        let mut reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+        reader.detail_level = DetailLevel::Lazy;
+        let spectrum = reader.next().unwrap();
+        let arrays = spectrum.arrays.as_ref().unwrap();
+        for array_type in [ArrayType::MZArray, ArrayType::IntensityArray] {
+            let array = arrays.get(&array_type).unwrap();
+            assert_eq!(array.compression, BinaryCompressionType::NoCompression);
+            assert_eq!(
+                array.to_f64()?,
+                expected.get(&array_type).unwrap().to_f64()?
+            );
+        }
+        assert_eq!(arrays.mzs()?.len(), 19913);
+        Ok(())
+    }
+
+    #[test]
+    fn test_interleaved_array() -> io::Result<()> {
+        let text = fs::read_to_string("./test/data/small.mzML")?;
+        let mut reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+        let expected: RawSpectrum = reader.next().unwrap().into();
+        let interleaved: Vec<f64> = expected
+            .mzs()
+            .iter()
+            .zip(expected.intensities().iter())
+            .flat_map(|(mz, inten)| [*mz, *inten as f64])
+            .collect();
+        let array = DataArray::wrap(
+            &ArrayType::nonstandard(ArrayType::INTERLEAVED_MZ_INTENSITY_ARRAY),
+            BinaryDataArrayType::Float64,
+            crate::spectrum::bindata::to_bytes(&interleaved),
+        );
+        let encoded = array.encode_bytestring(BinaryCompressionType::NoCompression);
+        let encoded = String::from_utf8(encoded).unwrap();
+
+        let both_terms = r#"<cvParam cvRef="MS" accession="MS:1000514" name="m/z array" value=""/>
+              <cvParam cvRef="MS" accession="MS:1000515" name="intensity array" value=""/>"#;
+        let user_param = format!(
+            r#"<userParam name="{}" value=""/>"#,
+            ArrayType::INTERLEAVED_MZ_INTENSITY_ARRAY
+        );
+        for declaration in [both_terms.to_string(), user_param] {
+            // Replace the separate arrays of the first spectrum with a single interleaved array
+            let list_start = r#"<binaryDataArrayList count="2">"#;
+            let list_end = "</binaryDataArrayList>";
+            let start = text.find(list_start).unwrap();
+            let end = text.find(list_end).unwrap() + list_end.len();
+            let replacement = format!(
+                r#"<binaryDataArrayList count="1">
+            <binaryDataArray encodedLength="{}">
+              <cvParam cvRef="MS" accession="MS:1000523" name="64-bit float" value=""/>
+              <cvParam cvRef="MS" accession="MS:1000576" name="no compression" value=""/>
+              {}
+              <binary>{}</binary>
+            </binaryDataArray>
+          </binaryDataArrayList>"#,
+                encoded.len(),
+                declaration,
+                encoded
+            );
+            let text = format!("{}{}{}", &text[..start], replacement, &text[end..]);
+
+            let mut reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+            reader.array_length_validation = ArrayLengthValidation::Error;
+            let spectrum: RawSpectrum = reader.next().unwrap().into();
+            assert!(!spectrum.arrays.has_array(&ArrayType::nonstandard(
+                ArrayType::INTERLEAVED_MZ_INTENSITY_ARRAY
+            )));
+            assert_eq!(spectrum.mzs().len(), 19913);
+            assert_eq!(spectrum.mzs(), expected.mzs());
+            assert_eq!(spectrum.intensities(), expected.intensities());
+
+            // Lazily read arrays are split when they are accessed or decoded
+            let mut reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+            reader.detail_level = DetailLevel::Lazy;
+            let mut spectrum: RawSpectrum = reader.next().unwrap().into();
+            assert!(spectrum.arrays.has_array(&ArrayType::nonstandard(
+                ArrayType::INTERLEAVED_MZ_INTENSITY_ARRAY
+            )));
+            assert_eq!(spectrum.mzs(), expected.mzs());
+            assert_eq!(spectrum.intensities(), expected.intensities());
+            spectrum.decode_all_arrays()?;
+            assert!(spectrum.arrays.has_array(&ArrayType::MZArray));
+            assert_eq!(spectrum.mzs(), expected.mzs());
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_open_path_mmap() -> io::Result<()> {
+        let mut reader = MzMLReader::open_path_mmap("./test/data/small.mzML")?;
+        assert_eq!(reader.len(), 48);
+
+        let mut ms1_count = 0;
+        let mut msn_count = 0;
+        for spectrum in reader.iter() {
+            if spectrum.ms_level() == 1 {
+                ms1_count += 1;
+            } else {
+                msn_count += 1;
+            }
+        }
+        assert_eq!(ms1_count, 14);
+        assert_eq!(msn_count, 34);
+
+        let spectrum = reader.get_spectrum_by_index(10).unwrap();
+        assert_eq!(spectrum.index(), 10);
+        let id = spectrum.id().to_string();
+        let again = reader.get_spectrum_by_id(&id).unwrap();
+        assert_eq!(again.peaks().len(), spectrum.peaks().len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_metadata() -> io::Result<()> {
+        let reader = MzMLReader::open_path("./test/data/small.mzML")?;
+        let run = reader.run_description().unwrap();
+        assert_eq!(run.id.as_deref(), Some("small"));
+        assert_eq!(run.default_source_file_id.as_deref(), Some("RAW1"));
+        assert!(reader
+            .instrument_configurations()
+            .contains_key(&run.default_instrument_id.unwrap()));
+        assert_eq!(
+            run.start_time.unwrap().to_rfc3339(),
+            "2005-07-20T19:44:22+00:00"
+        );
+
+        // A timestamp without a time zone is valid `xsd:dateTime` and is taken as UTC
+        let text = fs::read_to_string("./test/data/small.mzML")?.replacen(
+            r#"startTimeStamp="2005-07-20T19:44:22Z""#,
+            r#"startTimeStamp="2005-07-20T19:44:22""#,
+            1,
+        );
+        let reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+        let run = reader.run_description().unwrap();
+        assert_eq!(
+            run.start_time.unwrap().to_rfc3339(),
+            "2005-07-20T19:44:22+00:00"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_total_peak_count() -> io::Result<()> {
+        let mut reader = MzMLReader::open_path("./test/data/small.mzML")?;
+        let expected_ms1: u64 = reader
+            .iter()
+            .filter(|s| s.ms_level() == 1)
+            .map(|s| s.peaks().len() as u64)
+            .sum();
+        let expected: u64 = reader.iter().map(|s| s.peaks().len() as u64).sum();
+
+        let total = reader.total_peak_count(None);
+        assert_eq!(total, expected);
+        assert!(total > 19913);
+        assert_eq!(reader.total_peak_count(Some(1)), expected_ms1);
+        assert!(reader.total_peak_count(Some(1)) < total);
+        assert_eq!(reader.total_peak_count(Some(5)), 0);
+
+        let spectrum = reader.next().unwrap();
+        assert_eq!(spectrum.index(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_iter_with_offsets() -> io::Result<()> {
+        let indexed = MzMLReader::open_path("./test/data/small.mzML")?;
+        let mut reader = MzMLReader::new(fs::File::open("./test/data/small.mzML")?);
+
+        let mut last_offset = None;
+        let mut count = 0;
+        for (offset, spectrum) in reader.iter_with_offsets() {
+            if let Some(last_offset) = last_offset {
+                assert!(offset > last_offset);
+            }
+            assert_eq!(indexed.get_index().get(spectrum.id()), Some(offset));
+            last_offset = Some(offset);
+            count += 1;
+        }
+        assert_eq!(count, 48);
+        Ok(())
+    }
+
+    #[test]
+    fn test_referenceable_param_group_in_spectrum() -> io::Result<()> {
+        let text = fs::read_to_string("./test/data/small.mzML")?;
+        let text = text.replacen(
+            r#"    </referenceableParamGroupList>"#,
+            r#"      <referenceableParamGroup id="NegativeMode">
+        <cvParam cvRef="MS" accession="MS:1000129" name="negative scan" value=""/>
+        <userParam name="acquisition mode" value="grouped"/>
+      </referenceableParamGroup>
+    </referenceableParamGroupList>"#,
+            1,
+        );
+        let text = text.replacen(
+            r#"<cvParam cvRef="MS" accession="MS:1000130" name="positive scan" value=""/>"#,
+            r#"<referenceableParamGroupRef ref="NegativeMode"/>"#,
+            1,
+        );
+
+        let mut reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+        assert_eq!(reader.reference_param_groups.len(), 2);
+        let spectrum = reader.next().unwrap();
+        assert_eq!(spectrum.index(), 0);
+        assert_eq!(spectrum.polarity(), ScanPolarity::Negative);
+        assert_eq!(
+            spectrum
+                .description()
+                .get_param_by_name("acquisition mode")
+                .map(|p| p.value.to_string()),
+            Some("grouped".to_string())
+        );
+
+        let spectrum = reader.next().unwrap();
+        assert_eq!(spectrum.polarity(), ScanPolarity::Positive);
+        Ok(())
+    }
+
+    #[test]
+    fn test_namespace_prefixed_elements() -> io::Result<()> {
+        let text = fs::read_to_string("./test/data/small.mzML")?;
+        let tag_pattern = regex::Regex::new(r"<(/?)([A-Za-z])").unwrap();
+        let prefixed = tag_pattern
+            .replace_all(&text, "<${1}mzml:${2}")
+            .replace("xmlns=", "xmlns:mzml=");
+        assert!(prefixed.contains("<mzml:spectrum "));
+
+        let mut reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+        let mut prefixed_reader = MzMLReader::new(io::Cursor::new(prefixed.as_bytes()));
+        assert_eq!(
+            reader.instrument_configurations().len(),
+            prefixed_reader.instrument_configurations().len()
+        );
+        assert_eq!(reader.softwares().len(), prefixed_reader.softwares().len());
+
+        let spectra: Vec<_> = reader.by_ref().collect();
+        let prefixed_spectra: Vec<_> = prefixed_reader.by_ref().collect();
+        assert_eq!(spectra.len(), 48);
+        assert_eq!(spectra.len(), prefixed_spectra.len());
+        for (a, b) in spectra.iter().zip(prefixed_spectra.iter()) {
+            assert_eq!(a.id(), b.id());
+            assert_eq!(a.ms_level(), b.ms_level());
+            assert_eq!(a.description, b.description);
+            assert_eq!(a.peaks().len(), b.peaks().len());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_poll_growing_file() -> io::Result<()> {
+        use std::io::Write;
+
+        let text = fs::read_to_string("./test/data/small.mzML")?;
+        let closer = "</spectrum>";
+        let spectrum_ends: Vec<usize> = text
+            .match_indices(closer)
+            .map(|(i, _)| i + closer.len())
+            .collect();
+        let partial = spectrum_ends[1] + 200;
+        let second_batch = spectrum_ends[3];
+
+        let tmpdir = tempfile::tempdir()?;
+        let path = tmpdir.path().join("growing.mzML");
+        fs::write(&path, &text[..partial])?;
+        let mut appender = fs::OpenOptions::new().append(true).open(&path)?;
+
+        let mut reader = MzMLReader::new(fs::File::open(&path)?);
+        let ids: Vec<_> = reader.iter_available().map(|s| s.index()).collect();
+        assert_eq!(ids, [0, 1]);
+        assert!(reader.poll_next().is_none());
+
+        appender.write_all(text[partial..second_batch].as_bytes())?;
+        appender.flush()?;
+        let ids: Vec<_> = reader.iter_available().map(|s| s.index()).collect();
+        assert_eq!(ids, [2, 3]);
+
+        appender.write_all(text[second_batch..].as_bytes())?;
+        appender.flush()?;
+        assert_eq!(reader.iter_available().count(), 44);
+        assert!(reader.poll_next().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_spectrum_bytes_by_id() -> io::Result<()> {
+        let mut reader = MzMLReader::open_path("./test/data/small.mzML")?;
+        let id = "controllerType=0 controllerNumber=1 scan=2";
+        let bytes = reader.get_spectrum_bytes_by_id(id).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.starts_with("<spectrum "));
+        assert!(text.ends_with("</spectrum>"));
+        assert!(text.contains(&format!("id=\"{id}\"")));
+        assert_eq!(text.matches("<spectrum ").count(), 1);
+
+        assert!(reader
+            .get_spectrum_bytes_by_id("controllerType=0 controllerNumber=1 scan=missing")
+            .is_none());
+        assert_eq!(reader.next().unwrap().index(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_if() -> io::Result<()> {
+        let text = fs::read_to_string("./test/data/small.mzML")?;
+        let text = text.replacen(
+            r#"accession="MS:1000130" name="positive scan""#,
+            r#"accession="MS:1000129" name="negative scan""#,
+            10,
+        );
+        let is_negative_ms2 = |desc: &SpectrumDescription| {
+            desc.ms_level == 2 && desc.polarity == ScanPolarity::Negative
+        };
+
+        let expected: Vec<_> = MzMLReader::new(io::Cursor::new(text.as_bytes()))
+            .filter(|s| is_negative_ms2(&s.description))
+            .map(|s| s.id().to_string())
+            .collect();
+        assert!(!expected.is_empty());
+
+        let mut reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+        let mut found = Vec::new();
+        while let Some(spectrum) = reader.read_if(is_negative_ms2) {
+            let mzs = spectrum
+                .arrays
+                .as_ref()
+                .unwrap()
+                .get(&ArrayType::MZArray)
+                .unwrap();
+            assert_eq!(mzs.compression, BinaryCompressionType::Decoded);
+            found.push(spectrum.id().to_string());
+        }
+        assert_eq!(found, expected);
+        assert_eq!(reader.detail_level, DetailLevel::Full);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_accessions_retained() -> io::Result<()> {
+        let text = fs::read_to_string("./test/data/small.mzML")?;
+        let start = text.find(r#"scan=3" defaultArrayLength"#).unwrap();
+        let mut text = text;
+        let insertions = [
+            (
+                r#"<cvParam cvRef="MS" accession="MS:1000511" name="ms level" value="2"/>"#,
+                r#"<cvParam cvRef="MS" accession="MS:9999999" name="made up term" value="42"/>"#,
+            ),
+            (
+                r#"<cvParam cvRef="MS" accession="MS:1000795" name="no combination" value=""/>"#,
+                r#"<cvParam cvRef="XX" accession="XX:0000001" name="unknown vocabulary term" value=""/>"#,
+            ),
+            (
+                r#"<cvParam cvRef="MS" accession="MS:1000501" name="scan window lower limit""#,
+                r#"<cvParam cvRef="MS" accession="MS:9999998" name="made up window term" value="1"/>"#,
+            ),
+        ];
+        for (anchor, param) in insertions {
+            let at = start + text[start..].find(anchor).unwrap();
+            text.insert_str(at, param);
+        }
+
+        let mut reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+        let spectrum = reader.nth(2).unwrap();
+        assert_eq!(spectrum.ms_level(), 2);
+
+        let param = spectrum.get_param_by_curie(&curie!(MS:9999999)).unwrap();
+        assert_eq!(param.name, "made up term");
+        assert_eq!(param.to_i32().unwrap(), 42);
+
+        let acquisition = spectrum.acquisition();
+        assert_eq!(acquisition.combination, ScanCombination::NoCombination);
+        assert!(acquisition
+            .params()
+            .iter()
+            .any(|p| p.name == "unknown vocabulary term"));
+        assert!(acquisition
+            .first_scan()
+            .unwrap()
+            .get_param_by_curie(&curie!(MS:9999998))
+            .is_some());
+        assert!(spectrum
+            .precursor()
+            .unwrap()
+            .isolation_window
+            .params()
+            .iter()
+            .any(|p| p.name == "ms level"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_supplemental_activation() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut buf = Vec::new();
+        fs::File::open(path)?.read_to_end(&mut buf)?;
+        let mut text = String::from_utf8(buf).unwrap();
+        // Replace the first precursor's CID activation with EThcD
+        let start = text.find("<activation>").unwrap();
+        let closer = "</activation>";
+        let end = start + text[start..].find(closer).unwrap() + closer.len();
+        text.replace_range(
+            start..end,
+            r#"<activation>
+                <cvParam cvRef="MS" accession="MS:1000598" name="electron transfer dissociation" value=""/>
+                <cvParam cvRef="MS" accession="MS:1002678" name="supplemental beam-type collision-induced dissociation" value=""/>
+                <cvParam cvRef="MS" accession="MS:1000045" name="collision energy" value="25.0" unitCvRef="UO" unitAccession="UO:0000266" unitName="electronvolt"/>
+                <cvParam cvRef="MS" accession="MS:1002680" name="supplemental collision energy" value="15.0" unitCvRef="UO" unitAccession="UO:0000266" unitName="electronvolt"/>
+              </activation>"#,
+        );
+
+        let mut reader = MzMLReader::new(io::Cursor::new(text.into_bytes()));
+        let scan = reader.find(|s| s.ms_level() == 2).unwrap();
+        let activation = &scan.precursor().unwrap().activation;
+        assert!(activation.is_combined());
+        assert_eq!(
+            activation.methods(),
+            &[
+                DissociationMethodTerm::ElectronTransferDissociation,
+                DissociationMethodTerm::SupplementalBeamTypeCollisionInducedDissociation,
+            ]
+        );
+        assert_eq!(activation.energy, 25.0);
+        assert_eq!(activation.supplemental_energy(), Some(15.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_corrupt_spectrum() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut buf = Vec::new();
+        fs::File::open(path)?.read_to_end(&mut buf)?;
+        let mut text = String::from_utf8(buf).unwrap();
+        // Replace the first data array of the second spectrum with invalid base64
+        let spectrum_start = text.find(r#"<spectrum index="1""#).unwrap();
+        let start = spectrum_start + text[spectrum_start..].find("<binary>").unwrap() + 8;
+        let end = start + text[start..].find("</binary>").unwrap();
+        text.replace_range(start..end, "@@@@");
+        let text = text.into_bytes();
+
+        let reader = MzMLReader::new(io::Cursor::new(text.clone()));
+        assert_eq!(reader.count(), 1);
+
+        let mut reader = MzMLReader::new(io::Cursor::new(text));
+        reader.error_policy = ErrorPolicy::SkipAndContinue;
+        let indices: Vec<_> = reader.map(|s| s.index()).collect();
+        assert_eq!(indices.len(), 47);
+        assert_eq!(indices[0], 0);
+        assert_eq!(indices[1], 2);
+        assert_eq!(*indices.last().unwrap(), 47);
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_malformed_scan_time() -> io::Result<()> {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut buf = Vec::new();
+        fs::File::open(path)?.read_to_end(&mut buf)?;
+        let text = String::from_utf8(buf)
+            .unwrap()
+            .replacen(r#"value="0.007896666667""#, r#"value="later""#, 1)
+            .into_bytes();
+
+        let mut reader = MzMLReader::new(io::Cursor::new(text.clone()));
+        assert_eq!(reader.next().unwrap().index(), 0);
+        let mut spectrum = MultiLayerSpectrum::default();
+        let err = reader.read_into(&mut spectrum).unwrap_err();
+        assert!(matches!(
+            err,
+            MzMLParserError::MalformedParamValue(MzMLParserState::Scan, ref name, _) if name == "scan start time"
+        ));
+
+        let mut reader = MzMLReader::new(io::Cursor::new(text));
+        reader.error_policy = ErrorPolicy::SkipAndContinue;
+        let indices: Vec<_> = reader.map(|s| s.index()).collect();
+        assert_eq!(indices.len(), 47);
+        assert_eq!(indices[1], 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_f64_intensities_preserved() -> io::Result<()> {
+        use crate::io::mzml::MzMLWriter;
+        use crate::io::traits::SpectrumWriter;
+
+        let mut reader = MzMLReader::open_path("./test/data/small.mzML")?;
+        let mut spectrum = reader.get_spectrum_by_index(0).unwrap();
+        let arrays = spectrum.arrays.as_mut().unwrap();
+        let n = arrays.mzs()?.len();
+        let values: Vec<f64> = (0..n).map(|i| 1e6 + i as f64 + 1e-7).collect();
+        let mut intensities =
+            DataArray::from_name_and_type(&ArrayType::IntensityArray, BinaryDataArrayType::Float64);
+        intensities.extend(&values)?;
+        arrays.add(intensities);
+
+        let mut writer = MzMLWriter::new(io::Cursor::new(Vec::new()));
+        writer.copy_metadata_from(&reader);
+        *writer.spectrum_count_mut() = 1;
+        writer.write(&spectrum)?;
+        writer.close()?;
+        let buffer = writer.get_mut()?.get_ref().clone();
+
+        let mut reader = MzMLReader::new(io::Cursor::new(buffer));
+        let spectrum = reader.next().unwrap();
+        let arrays = spectrum.arrays.as_ref().unwrap();
+        let intensities = arrays.get(&ArrayType::IntensityArray).unwrap();
+        assert_eq!(intensities.dtype, BinaryDataArrayType::Float64);
+        assert_eq!(arrays.intensities_f64()?.as_ref(), values.as_slice());
+        assert_ne!(arrays.intensities()?[0] as f64, values[0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_external_spectrum_reference() -> io::Result<()> {
+        use crate::io::mzml::MzMLWriter;
+        use crate::io::traits::SpectrumWriter;
+
+        // The first `<scan>` with an instrument configuration reference is spectrum 1's
+        let text = fs::read_to_string("./test/data/small.mzML")?.replacen(
+            r#"<scan instrumentConfigurationRef="IC2">"#,
+            r#"<scan instrumentConfigurationRef="IC2" sourceFileRef="RAW1" externalSpectrumID="scan=1">"#,
+            1,
+        );
+        let mut reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+        let spectrum = reader.nth(1).unwrap();
+        let scan = spectrum.acquisition().first_scan().unwrap();
+        assert_eq!(scan.source_file_ref.as_deref(), Some("RAW1"));
+        assert_eq!(scan.external_spectrum_id.as_deref(), Some("scan=1"));
+
+        let next = reader.next().unwrap();
+        let next_scan = next.acquisition().first_scan().unwrap();
+        assert!(next_scan.source_file_ref.as_deref().is_none());
+        assert!(next_scan.external_spectrum_id.as_deref().is_none());
+
+        let mut writer = MzMLWriter::new(io::Cursor::new(Vec::new()));
+        writer.copy_metadata_from(&reader);
+        *writer.spectrum_count_mut() = 1;
+        writer.write(&spectrum)?;
+        writer.close()?;
+        let buffer = writer.get_mut()?.get_ref().clone();
+
+        let mut reader = MzMLReader::new(io::Cursor::new(buffer));
+        let spectrum = reader.next().unwrap();
+        let scan = spectrum.acquisition().first_scan().unwrap();
+        assert_eq!(scan.source_file_ref.as_deref(), Some("RAW1"));
+        assert_eq!(scan.external_spectrum_id.as_deref(), Some("scan=1"));
+
+        // A reference to a source file that isn't described is not written
+        let mut dangling = spectrum.clone();
+        dangling
+            .description
+            .acquisition
+            .first_scan_mut()
+            .unwrap()
+            .source_file_ref = Some("RAW2".to_string());
+        let mut writer = MzMLWriter::new(io::Cursor::new(Vec::new()));
+        writer.copy_metadata_from(&reader);
+        *writer.spectrum_count_mut() = 1;
+        writer.write(&dangling)?;
+        writer.close()?;
+        let buffer = writer.get_mut()?.get_ref().clone();
+        assert!(!String::from_utf8_lossy(&buffer).contains("RAW2"));
+        let spectrum = MzMLReader::new(io::Cursor::new(buffer)).next().unwrap();
+        let scan = spectrum.acquisition().first_scan().unwrap();
+        assert!(scan.source_file_ref.as_deref().is_none());
+        assert!(scan.external_spectrum_id.as_deref().is_none());
+
+        // A `<scan>` without any children is read too
+        let mut text = text;
+        let start = text
+            .find(r#"<scan instrumentConfigurationRef="IC2" sourceFileRef"#)
+            .unwrap();
+        let end = start + text[start..].find("</scan>").unwrap() + "</scan>".len();
+        text.replace_range(
+            start..end,
+            r#"<scan instrumentConfigurationRef="IC2" sourceFileRef="RAW1" externalSpectrumID="scan=1"/>"#,
+        );
+        let spectrum = MzMLReader::new(io::Cursor::new(text.as_bytes()))
+            .nth(1)
+            .unwrap();
+        let scan = spectrum.acquisition().first_scan().unwrap();
+        assert_eq!(scan.source_file_ref.as_deref(), Some("RAW1"));
+        assert_eq!(scan.external_spectrum_id.as_deref(), Some("scan=1"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_peaks_per_spectrum() -> io::Result<()> {
+        let mut reader = MzMLReader::open_path("./test/data/small.mzML")?;
+        let expected = reader.get_spectrum_by_index(3).unwrap();
+        assert_eq!(expected.signal_continuity(), SignalContinuity::Centroid);
+        let expected_arrays = expected.arrays.as_ref().unwrap();
+        let mut threshold = expected_arrays.intensities()?.to_vec();
+        threshold.sort_by(|a, b| b.total_cmp(a));
+        let threshold = threshold[99];
+
+        reader.set_max_peaks_per_spectrum(Some(100));
+        let spectrum = reader.get_spectrum_by_index(3).unwrap();
+        let arrays = spectrum.arrays.as_ref().unwrap();
+        let mzs = arrays.mzs()?;
+        let intensities = arrays.intensities()?;
+        assert_eq!(mzs.len(), 100);
+        assert_eq!(intensities.len(), 100);
+        assert!(mzs.windows(2).all(|w| w[0] <= w[1]));
+        assert!(intensities.iter().all(|i| *i >= threshold));
+
+        let flag = spectrum
+            .description()
+            .params
+            .iter()
+            .find(|p| p.name == "truncated from peak count")
+            .unwrap();
+        assert_eq!(flag.value, 1006i64);
+
+        let profile = reader.get_spectrum_by_index(0).unwrap();
+        assert_eq!(
+            profile.description().signal_continuity,
+            SignalContinuity::Profile
+        );
+        assert_eq!(profile.arrays.as_ref().unwrap().mzs()?.len(), 19913);
+        assert!(!profile
+            .description()
+            .params
+            .iter()
+            .any(|p| p.name == "truncated from peak count"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_iter_precursor_range() -> io::Result<()> {
+        let mut reader = MzMLReader::open_path("./test/data/small.mzML")?;
+        let expected: Vec<_> = reader
+            .iter()
+            .filter(|s| {
+                s.precursor()
+                    .map_or(false, |p| (800.0..=840.0).contains(&p.ions[0].mz))
+            })
+            .map(|s| s.index())
+            .collect();
+        assert_eq!(expected.len(), 14);
+
+        let spectra: Vec<_> = reader.iter_precursor_range(800.0, 840.0).collect();
+        let indices: Vec<_> = spectra.iter().map(|s| s.index()).collect();
+        assert_eq!(indices, expected);
+        for spectrum in spectra.iter() {
+            assert_eq!(spectrum.ms_level(), 2);
+            let mzs = spectrum
+                .arrays
+                .as_ref()
+                .unwrap()
+                .get(&ArrayType::MZArray)
+                .unwrap();
+            assert_eq!(mzs.compression, BinaryCompressionType::Decoded);
+        }
+
+        assert_eq!(reader.iter_precursor_range(900.0, 1000.0).count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_precursor_trace() -> io::Result<()> {
+        use mzpeaks::Tolerance;
+
+        let mut reader = MzMLReader::open_path("./test/data/small.mzML")?;
+        let expected: Vec<_> = reader
+            .iter()
+            .filter(|s| {
+                s.precursor()
+                    .map_or(false, |p| (p.ions[0].mz - 837.35).abs() <= 0.05)
+            })
+            .map(|s| (s.start_time(), s.peaks().tic()))
+            .collect();
+        assert_eq!(expected.len(), 4);
+
+        let trace = reader.precursor_trace(837.35, Tolerance::Da(0.05));
+        assert_eq!(trace, expected);
+        assert!(trace.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(trace.iter().all(|(_, tic)| *tic > 0.0));
+
+        assert!(reader
+            .precursor_trace(837.0, Tolerance::PPM(10.0))
+            .is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_location() -> io::Result<()> {
+        let mut text = fs::read_to_string("./test/data/small.mzML")?;
+        let spectrum_start = text.find(r#"<spectrum index="1""#).unwrap();
+        let start = spectrum_start + text[spectrum_start..].find("<binary>").unwrap() + 8;
+        let end = start + text[start..].find("</binary>").unwrap();
+        text.replace_range(start..end, "@@@@");
+        let end = start + "@@@@".len();
+        let next_start = text.find(r#"<spectrum index="2""#).unwrap();
+
+        let mut reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+        let results: Vec<_> = reader.iter_results().collect();
+        assert_eq!(results.len(), 48);
+        assert_eq!(results.iter().filter(|r| r.is_err()).count(), 1);
+        assert_eq!(results[2].as_ref().unwrap().index(), 2);
+
+        let err = results[1].as_ref().unwrap_err();
+        assert!(matches!(
+            err.source,
+            MzMLParserError::ArrayDecodingError(..)
+        ));
+        assert_eq!(err.element, "binaryDataArray");
+        assert!(err.offset > end as u64);
+        assert!(err.offset < next_start as u64);
+        assert!(err.to_string().contains(&format!("at byte {}", err.offset)));
+        Ok(())
+    }
+
     #[test]
     fn test_random_access_iterator() -> io::Result<()> {
         let path = path::Path::new("./test/data/small.mzML");
@@ -1,10 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
 use std::io::SeekFrom;
 use std::{io, mem};
 
-use chrono::{DateTime, FixedOffset};
-use log::warn;
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use log::{debug, warn};
 use quick_xml::events::{BytesEnd, BytesStart, BytesText};
 use quick_xml::Error as XMLError;
 
@@ -14,12 +14,25 @@ use crate::prelude::*;
 use crate::io::traits::SeekRead;
 use crate::io::OffsetIndex;
 use crate::meta::{
-    Component, ComponentType, DataProcessing, FileDescription, InstrumentConfiguration, MassSpectrometerFileFormatTerm, NativeSpectrumIdentifierFormatTerm, ProcessingMethod, Sample, Software, SourceFile
+    Component, ComponentType, CvReference, DataProcessing, FileDescription, InstrumentConfiguration, MassSpectrometerFileFormatTerm, NativeSpectrumIdentifierFormatTerm, ProcessingMethod, Sample, Software, SourceFile
 };
-use crate::params::{curie_to_num, ControlledVocabulary, Param, ParamCow, Unit};
+use crate::params::{
+    curie_to_num, ControlledVocabulary, Param, ParamCow, ParamValueParseError, Unit,
+};
+use crate::spectrum::bindata::{ArrayRetrievalError, ArrayType};
 
 use super::reader::Bytes;
 
+/// Parse an `xsd:dateTime` value. The time zone is optional in XML Schema, and a timestamp
+/// without one is assumed to be in UTC.
+pub(crate) fn parse_xsd_datetime(value: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(value).ok().or_else(|| {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+            .ok()
+            .map(|t| t.and_utc().fixed_offset())
+    })
+}
+
 /**
 The different states the [`MzMLReaderType`](crate::io::mzml::MzMLReaderType) can enter while parsing
 different phases of the document. This information is really only
@@ -122,7 +135,35 @@ pub enum MzMLParserError {
     #[error("An IO error {1} was encountered in {0:?}")]
     IOError(MzMLParserState, #[source] io::Error),
     #[error("The {0} section is over")]
-    SectionOver(&'static str)
+    SectionOver(&'static str),
+    #[error("Failed to decode a data array in {0:?}: {1}")]
+    ArrayDecodingError(MzMLParserState, #[source] ArrayRetrievalError),
+    #[error("The {0} has {1} entries but {2} were declared")]
+    ArrayLengthMismatch(ArrayType, usize, usize),
+    #[error("The value of {1} in {0:?} could not be parsed: {2}")]
+    MalformedParamValue(MzMLParserState, String, #[source] ParamValueParseError),
+}
+
+/// An [`MzMLParserError`] together with where in the stream it was encountered
+#[derive(Debug, Error)]
+#[error("{source} at byte {offset} while processing <{element}>")]
+pub struct MzMLParserErrorContext {
+    /// The number of bytes of the stream consumed when the error was detected
+    pub offset: u64,
+    /// The name of the element being processed when the error was detected
+    pub element: String,
+    #[source]
+    pub source: MzMLParserError,
+}
+
+impl From<MzMLParserErrorContext> for io::Error {
+    fn from(value: MzMLParserErrorContext) -> Self {
+        let kind = match &value.source {
+            MzMLParserError::IOError(_, e) => e.kind(),
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, value)
+    }
 }
 
 impl From<MzMLParserError> for io::Error {
@@ -136,6 +177,35 @@ impl From<MzMLParserError> for io::Error {
 
 pub(crate) type ParserResult = Result<MzMLParserState, MzMLParserError>;
 
+/// Warn about the accession of the `<cvParam>` `event` if it could not be resolved.
+///
+/// When given the accessions a reader has already `reported`, each is only warned about
+/// the first time it is seen so files which use it in every spectrum don't flood the log.
+pub(crate) fn warn_unrecognized_accession(
+    event: &BytesStart,
+    reader_position: usize,
+    reported: Option<&mut HashSet<String>>,
+) {
+    if event.local_name().as_ref() != b"cvParam" {
+        return;
+    }
+    let accession = match event.try_get_attribute("accession") {
+        Ok(Some(attr)) => match attr.unescape_value() {
+            Ok(v) => v,
+            Err(_) => return,
+        },
+        _ => return,
+    };
+    let (cv, acc) = curie_to_num(&accession);
+    if cv.is_some() && acc.is_some() {
+        return;
+    }
+    let first_seen = reported.map_or(true, |seen| seen.insert(accession.to_string()));
+    if first_seen {
+        warn!("Unrecognized CV param accession {accession} at {reader_position}");
+    }
+}
+
 /**
 Common XML error handling behaviors
 */
@@ -217,6 +287,9 @@ pub trait CVParamParse: XMLParseBase {
                             )
                         });
                         unit = Unit::from_accession(&v);
+                        if unit == Unit::Unknown {
+                            debug!("Unrecognized unit accession {v} at {reader_position}");
+                        }
                     }
                     b"unitCvRef" => {}
                     _ => {}
@@ -304,7 +377,11 @@ pub trait CVParamParse: XMLParseBase {
                                 reader_position, e
                             )
                         });
-                        unit_accession = Some(Unit::from_accession(&v));
+                        let unit = Unit::from_accession(&v);
+                        if unit == Unit::Unknown {
+                            debug!("Unrecognized unit accession {v} at {reader_position}");
+                        }
+                        unit_accession = Some(unit);
                     }
                     b"unitCvRef" => {}
                     _ => {}
@@ -440,7 +517,7 @@ impl IndexedMzMLIndexExtractor {
         event: &BytesStart,
         state: IndexParserState,
     ) -> Result<IndexParserState, MzMLIndexingError> {
-        let elt_name = event.name();
+        let elt_name = event.local_name();
         match elt_name.as_ref() {
             b"offset" => {
                 for attr_parsed in event.attributes() {
@@ -499,7 +576,7 @@ impl IndexedMzMLIndexExtractor {
         event: &BytesEnd,
         state: IndexParserState,
     ) -> Result<IndexParserState, MzMLIndexingError> {
-        let elt_name = event.name();
+        let elt_name = event.local_name();
         match elt_name.as_ref() {
             b"offset" => {}
             b"index" => {}
@@ -551,6 +628,7 @@ impl IndexedMzMLIndexExtractor {
 of an mzML file.*/
 #[derive(Debug, Default)]
 pub struct FileMetadataBuilder<'a> {
+    pub cv_list: Vec<CvReference>,
     pub file_description: FileDescription,
     pub instrument_configurations: Vec<InstrumentConfiguration>,
     pub softwares: Vec<Software>,
@@ -559,6 +637,8 @@ pub struct FileMetadataBuilder<'a> {
     pub reference_param_groups: HashMap<String, Vec<Param>>,
     pub last_group: String,
     pub(crate) instrument_id_map: Option<&'a mut IncrementingIdMap>,
+    /// The unrecognized accessions the reader has already warned about
+    pub(crate) reported_accessions: Option<&'a mut HashSet<String>>,
 
     // Run attributes
     pub run_id: Option<String>,
@@ -576,8 +656,9 @@ impl<'a> CVParamParse for FileMetadataBuilder<'a> {}
 
 impl<'a> FileMetadataBuilder<'a> {
     pub fn start_element(&mut self, event: &BytesStart, state: MzMLParserState) -> ParserResult {
-        let elt_name = event.name();
+        let elt_name = event.local_name();
         match elt_name.as_ref() {
+            b"cvList" => return Ok(MzMLParserState::CVList),
             b"fileDescription" => return Ok(MzMLParserState::FileDescription),
             b"fileContent" => return Ok(MzMLParserState::FileContents),
             b"sourceFileList" => return Ok(MzMLParserState::SourceFileList),
@@ -875,11 +956,13 @@ impl<'a> FileMetadataBuilder<'a> {
                             );
                         }
                         b"startTimeStamp" => {
-                            let val = attr.unescape_value().expect("Error decoding start timestamp");
-                            let val = DateTime::parse_from_rfc3339(&val).expect("Expected a dateTime value conforming to ISO 8601 standard");
-                            self.start_timestamp = Some(
-                                val
-                            );
+                            let val = attr
+                                .unescape_value()
+                                .expect("Error decoding start timestamp");
+                            self.start_timestamp = parse_xsd_datetime(&val);
+                            if self.start_timestamp.is_none() {
+                                warn!("Failed to parse run start timestamp {val}");
+                            }
                         }
                         _ => {}
                     }
@@ -908,6 +991,7 @@ impl<'a> FileMetadataBuilder<'a> {
                 }
                 return Ok(MzMLParserState::SpectrumList);
             }
+            b"chromatogramList" => return Ok(MzMLParserState::ChromatogramList),
             _ => {}
         }
 
@@ -976,15 +1060,55 @@ impl<'a> FileMetadataBuilder<'a> {
         state: MzMLParserState,
         reader_position: usize,
     ) -> ParserResult {
-        let elt_name = event.name();
+        let elt_name = event.local_name();
         match elt_name.as_ref() {
             b"cvParam" | b"userParam" => match Self::handle_param(event, reader_position, state) {
                 Ok(param) => {
+                    if param.controlled_vocabulary.is_none() || param.accession.is_none() {
+                        warn_unrecognized_accession(
+                            event,
+                            reader_position,
+                            self.reported_accessions.as_deref_mut(),
+                        );
+                    }
                     self.fill_param_into(param, state);
                     return Ok(state);
                 }
                 Err(err) => return Err(err),
             },
+            b"cv" => {
+                let mut cv = CvReference::default();
+                for attr_parsed in event.attributes() {
+                    match attr_parsed {
+                        Ok(attr) => match attr.key.as_ref() {
+                            b"id" => {
+                                cv.id = attr
+                                    .unescape_value()
+                                    .expect("Error decoding id")
+                                    .to_string();
+                            }
+                            b"URI" => {
+                                cv.uri = attr
+                                    .unescape_value()
+                                    .expect("Error decoding URI")
+                                    .to_string();
+                            }
+                            b"version" => {
+                                cv.version = Some(
+                                    attr.unescape_value()
+                                        .expect("Error decoding version")
+                                        .to_string(),
+                                );
+                            }
+                            _ => {}
+                        },
+                        Err(msg) => {
+                            return Err(self.handle_xml_error(msg.into(), state));
+                        }
+                    }
+                }
+                self.cv_list.push(cv);
+            }
             b"softwareRef" => {
                 if state == MzMLParserState::InstrumentConfiguration {
                     let ic = self.instrument_configurations.last_mut().unwrap();
@@ -1039,8 +1163,9 @@ impl<'a> FileMetadataBuilder<'a> {
     }
 
     pub fn end_element(&mut self, event: &BytesEnd, state: MzMLParserState) -> ParserResult {
-        let elt_name = event.name();
+        let elt_name = event.local_name();
         match elt_name.as_ref() {
+            b"cvList" => return Ok(MzMLParserState::CVList),
             b"fileDescription" => return Ok(MzMLParserState::FileDescription),
             b"fileContent" => return Ok(MzMLParserState::FileDescription),
             b"sourceFile" => return Ok(MzMLParserState::SourceFileList),
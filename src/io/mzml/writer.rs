@@ -17,7 +17,7 @@ use quick_xml::{Error as XMLError, Writer};
 
 use super::super::offset_index::OffsetIndex;
 use super::super::traits::SpectrumWriter;
-use super::super::utils::MD5HashingStream;
+use super::super::utils::SHA1HashingStream;
 
 use mzpeaks::{CentroidPeak, DeconvolutedPeak};
 
@@ -190,13 +190,22 @@ impl From<MzMLWriterError> for io::Error {
 
 pub type WriterResult = Result<(), MzMLWriterError>;
 
+/// A function rewriting spectrum IDs as they are written, see [`MzMLWriterType::set_id_transform`]
+struct IdTransform(Box<dyn Fn(&str) -> String + Send + Sync>);
+
+impl Debug for IdTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("IdTransform").finish()
+    }
+}
+
 struct ByteCountingStream<W: io::Write> {
-    stream: BufWriter<MD5HashingStream<W>>,
+    stream: BufWriter<SHA1HashingStream<W>>,
     bytes_written: u64,
 }
 
 impl<W: io::Write> ByteCountingStream<W> {
-    pub fn new(stream: BufWriter<MD5HashingStream<W>>) -> Self {
+    pub fn new(stream: BufWriter<SHA1HashingStream<W>>) -> Self {
         Self {
             stream,
             bytes_written: 0,
@@ -207,8 +216,11 @@ impl<W: io::Write> ByteCountingStream<W> {
         self.bytes_written
     }
 
-    pub fn checksum(&self) -> md5::Digest {
-        self.stream.get_ref().compute()
+    /// The SHA-1 checksum of all bytes written so far, flushing any buffered
+    /// bytes through to the hashing stream first.
+    pub fn checksum(&mut self) -> io::Result<String> {
+        self.stream.flush()?;
+        Ok(self.stream.get_ref().compute())
     }
 
     pub fn get_mut(&mut self) -> &mut W {
@@ -246,16 +258,15 @@ impl<W: io::Write> InnerXMLWriter<W> {
     pub fn new(file: W) -> InnerXMLWriter<W> {
         let handle = ByteCountingStream::new(BufWriter::with_capacity(
             BUFFER_SIZE,
-            MD5HashingStream::new(file),
+            SHA1HashingStream::new(file),
         ));
         Self {
             handle: Writer::new_with_indent(handle, b' ', 2),
         }
     }
 
-    pub fn digest(&mut self) -> String {
-        let digest = self.handle.get_ref().checksum();
-        format!("{:x}", digest)
+    pub fn digest(&mut self) -> io::Result<String> {
+        self.handle.get_mut().checksum()
     }
 
     pub fn flush(&mut self) -> io::Result<()> {
@@ -480,6 +491,9 @@ pub struct MzMLWriterType<
 
     /// The compression type to use when generating binary data arrays.
     pub data_array_compression: BinaryCompressionType,
+    /// The floating point type to recode floating point binary data arrays to, if any.
+    /// When `None`, each array is written with its own data type.
+    pub data_array_float_type: Option<BinaryDataArrayType>,
 
     /// The file-level metadata describing the provenance of the original data
     pub file_description: FileDescription,
@@ -511,6 +525,7 @@ pub struct MzMLWriterType<
     ms_cv: ControlledVocabulary,
 
     param_groups: Vec<ParamGroup>,
+    id_transform: Option<IdTransform>,
 }
 
 impl<
@@ -609,6 +624,9 @@ impl<
         if let Some(value) = source.spectrum_count_hint() {
             self.spectrum_count = value;
         }
+        if let Some(run) = source.run_description() {
+            self.run.default_data_processing_id = run.default_data_processing_id.clone();
+        }
     }
 
     fn run_description(&self) -> Option<&MassSpectrometryRun> {
@@ -744,9 +762,11 @@ where
             bic_collector: ChromatogramCollector::of(ChromatogramType::BasePeakChromatogram),
             ms_cv: ControlledVocabulary::MS,
             data_array_compression,
+            data_array_float_type: None,
             wrote_summaries: false,
             run: MassSpectrometryRun::default(),
             param_groups: Vec::default(),
+            id_transform: None,
         }
     }
 
@@ -759,6 +779,61 @@ where
         Self::new_with_index(file, true)
     }
 
+    /// Rewrite the ID of each spectrum written from now on with `f`, e.g. to prefix IDs
+    /// when merging several files into one so they stay unique.
+    ///
+    /// The rewritten ID is used for the `spectrum` element, the offset index, and the
+    /// `spectrumRef` of its precursors, so references between spectra written through
+    /// the same transform are preserved.
+    pub fn set_id_transform(&mut self, f: impl Fn(&str) -> String + Send + Sync + 'static) {
+        self.id_transform = Some(IdTransform(Box::new(f)));
+    }
+
+    /// The ID of the data processing spectra use by default, written as the `spectrumList`'s
+    /// `defaultDataProcessingRef`. This is the run's default data processing if set, otherwise
+    /// the first data processing.
+    fn default_data_processing_id(&self) -> Option<&str> {
+        self.run
+            .default_data_processing_id
+            .as_deref()
+            .or_else(|| self.data_processings.first().map(|dp| dp.id.as_str()))
+    }
+
+    /// Apply the ID transform set with [`MzMLWriterType::set_id_transform`], if any, to `id`
+    fn transform_id<'a>(&self, id: &'a str) -> Cow<'a, str> {
+        match self.id_transform.as_ref() {
+            Some(IdTransform(f)) => Cow::Owned(f(id)),
+            None => Cow::Borrowed(id),
+        }
+    }
+
+    /// Control how `binaryDataArray` elements are encoded.
+    ///
+    /// Floating point arrays will be recoded as `dtype`, which must be either
+    /// [`BinaryDataArrayType::Float32`] or [`BinaryDataArrayType::Float64`], and all arrays
+    /// will be compressed with `compression`. Integer and string arrays keep their own type.
+    pub fn set_array_encoding(
+        &mut self,
+        dtype: BinaryDataArrayType,
+        compression: BinaryCompressionType,
+    ) {
+        match dtype {
+            BinaryDataArrayType::Float32 | BinaryDataArrayType::Float64 => {
+                self.data_array_float_type = Some(dtype);
+            }
+            _ => {
+                warn!("The mzML writer can only recode floating point arrays, ignoring {dtype:?}");
+            }
+        }
+        self.data_array_compression = match compression {
+            BinaryCompressionType::Decoded => {
+                warn!("The mzML writer was asked to use the `Decoded` array compression, using `Zlib` instead");
+                BinaryCompressionType::Zlib
+            }
+            _ => compression,
+        };
+    }
+
     fn transition_err(&self, to_state: MzMLWriterState) -> WriterResult {
         Err(MzMLWriterError::StateTransitionError {
             from_state: self.state,
@@ -1184,8 +1259,8 @@ where
         let mut list = bstart!("spectrumList");
         let count = self.spectrum_count.to_string();
         attrib!("count", count, list);
-        if let Some(dp) = self.data_processings.first() {
-            attrib!("defaultDataProcessingRef", dp.id, list);
+        if let Some(dp_id) = self.default_data_processing_id() {
+            attrib!("defaultDataProcessingRef", dp_id, list);
         }
         self.handle.write_event(Event::Start(list))?;
         self.state = MzMLWriterState::SpectrumList;
@@ -1325,20 +1400,50 @@ where
             let mut scan_tag = bstart!("scan");
             let id = instrument_id(&scan.instrument_configuration_id);
             attrib!("instrumentConfigurationRef", id, scan_tag);
+            // A reference to a source file that isn't listed would make the document invalid
+            let source_file_ref = scan.source_file_ref.as_deref().filter(|id| {
+                let known = self
+                    .file_description
+                    .source_files
+                    .iter()
+                    .any(|sf| sf.id == *id);
+                if !known {
+                    warn!("Scan refers to source file {id} which is not in the file description, omitting the reference");
+                }
+                known
+            });
+            if let Some(source_file_ref) = source_file_ref {
+                attrib!("sourceFileRef", source_file_ref, scan_tag);
+                if let Some(external_spectrum_id) = scan.external_spectrum_id.as_deref() {
+                    attrib!("externalSpectrumID", external_spectrum_id, scan_tag);
+                }
+            }
             self.handle.write_event(Event::Start(scan_tag.borrow()))?;
-            self.handle.write_param(&self.ms_cv.const_param(
-                "scan start time",
-                ValueRef::Float(scan.start_time),
-                1000016,
-                Unit::Minute,
-            ))?;
-
-            self.handle.write_param(&self.ms_cv.const_param(
-                "ion injection time",
-                ValueRef::Float(scan.injection_time as f64),
-                1000927,
-                Unit::Millisecond,
-            ))?;
+            if !scan.start_time.is_nan() {
+                self.handle.write_param(&self.ms_cv.const_param(
+                    "scan start time",
+                    ValueRef::Float(scan.start_time),
+                    1000016,
+                    Unit::Minute,
+                ))?;
+            }
+
+            if let Some(injection_time) = scan.injection_time {
+                self.handle.write_param(&self.ms_cv.const_param(
+                    "ion injection time",
+                    ValueRef::Float(injection_time as f64),
+                    1000927,
+                    Unit::Millisecond,
+                ))?;
+            }
+
+            if let Some(filter_string) = scan.filter_string.as_deref() {
+                let param = ParamCow {
+                    value: ValueRef::String(Cow::Borrowed(filter_string)),
+                    ..self.ms_cv.const_param_ident("filter string", 1000512)
+                };
+                self.handle.write_param(&param)?;
+            }
 
             for param in scan.params() {
                 self.handle.write_param(param)?
@@ -1470,6 +1575,7 @@ where
 
         let mut precursor_tag = bstart!("precursor");
         if let Some(prec_id) = precursor.precursor_id() {
+            let prec_id = self.transform_id(prec_id);
             attrib!("spectrumRef", prec_id, precursor_tag);
         }
         self.handle
@@ -1676,8 +1782,28 @@ where
         array: &DataArray,
         default_array_len: usize,
     ) -> WriterResult {
+        let array = Self::recode_array(array, self.data_array_float_type)?;
         let encoded_array = array.encode_bytestring(self.data_array_compression);
-        self.write_binary_data_array_pre_encoded(array, default_array_len, &encoded_array)
+        self.write_binary_data_array_pre_encoded(&array, default_array_len, &encoded_array)
+    }
+
+    /// Convert a floating point `array` to `float_type` if it differs from the array's own
+    /// data type, otherwise borrow it unchanged.
+    fn recode_array(
+        array: &DataArray,
+        float_type: Option<BinaryDataArrayType>,
+    ) -> Result<Cow<'_, DataArray>, ArrayRetrievalError> {
+        match (float_type, array.dtype) {
+            (
+                Some(float_type),
+                BinaryDataArrayType::Float32 | BinaryDataArrayType::Float64,
+            ) if float_type != array.dtype => {
+                let mut array = array.clone();
+                array.store_as(float_type)?;
+                Ok(Cow::Owned(array))
+            }
+            _ => Ok(Cow::Borrowed(array)),
+        }
     }
 
     pub fn write_binary_data_arrays(
@@ -1692,27 +1818,31 @@ where
         #[cfg(feature = "parallelism")]
         {
             let compression = self.data_array_compression;
-            let mut array_pairs: Vec<(&ArrayType, &DataArray, Vec<u8>)> =
+            let float_type = self.data_array_float_type;
+            let array_pairs: Result<Vec<(&ArrayType, Cow<DataArray>, Vec<u8>)>, ArrayRetrievalError> =
                 if arrays.len() < PARALLEL_COMPRESSION_FAN {
                     arrays
                         .iter()
                         .map(|(t, d)| {
+                            let d = Self::recode_array(d, float_type)?;
                             let encoded = d.encode_bytestring(compression);
-                            (t, d, encoded)
+                            Ok((t, d, encoded))
                         })
                         .collect()
                 } else {
                     arrays
                         .par_iter()
                         .map(|(t, d)| {
+                            let d = Self::recode_array(d, float_type)?;
                             let encoded = d.encode_bytestring(compression);
-                            (t, d, encoded)
+                            Ok((t, d, encoded))
                         })
                         .collect()
                 };
+            let mut array_pairs = array_pairs?;
             array_pairs.sort_by_key(|f| f.0);
             for (_tp, array, encoded) in array_pairs {
-                self.write_binary_data_array_pre_encoded(array, default_array_len, &encoded)?
+                self.write_binary_data_array_pre_encoded(&array, default_array_len, &encoded)?
             }
         }
 
@@ -1751,13 +1881,20 @@ where
         outer: &mut BytesStart,
         summary_metrics: &SpectrumHasSummary,
     ) -> Result<usize, MzMLWriterError> {
-        attrib!("id", spectrum.id(), outer);
+        let id = self.transform_id(spectrum.id());
+        attrib!("id", id, outer);
         let count = self.spectrum_counter.to_string();
         attrib!("index", count, outer);
         let default_array_len_u = summary_metrics.count;
         let default_array_len = default_array_len_u.to_string();
 
         attrib!("defaultArrayLength", default_array_len, outer);
+        if let Some(dp_id) = spectrum.description().data_processing_id.as_deref() {
+            // Spectra inherit the `spectrumList` default when they don't name their own
+            if self.default_data_processing_id() != Some(dp_id) {
+                attrib!("dataProcessingRef", dp_id, outer);
+            }
+        }
 
         self.handle.write_event(Event::Start(outer.borrow()))?;
         self.spectrum_counter += 1;
@@ -1862,8 +1999,8 @@ where
             _ => {}
         }
         let pos = self.stream_position()?;
-        self.spectrum_offset_index
-            .insert(spectrum.id().to_string(), pos);
+        let id = self.transform_id(spectrum.id()).into_owned();
+        self.spectrum_offset_index.insert(id, pos);
 
         let summary_metrics = self.spectrum_has_summaries(spectrum);
 
@@ -2032,9 +2169,11 @@ where
         self.handle.write_event(Event::Text(text))?;
         end_event!(self, tag);
 
+        // The indexedmzML checksum covers every byte up to and including the
+        // opening `<fileChecksum>` tag.
         let tag = bstart!("fileChecksum");
         start_event!(self, tag);
-        let content = self.handle.digest();
+        let content = self.handle.digest()?;
         let text = BytesText::new(&content);
         self.handle.write_event(Event::Text(text))?;
         end_event!(self, tag);
@@ -2148,4 +2287,163 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn write_array_encoding_test() -> WriterResult {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut reader = MzMLReader::<_>::open_path(path).expect("Test file doesn't exist?");
+
+        let mut writer = MzMLWriterType::new(io::Cursor::new(Vec::new()));
+        writer.set_array_encoding(BinaryDataArrayType::Float32, BinaryCompressionType::Zlib);
+        writer.copy_metadata_from(&reader);
+        *writer.spectrum_count_mut() = reader.len() as u64;
+        for spectrum in reader.iter() {
+            writer.write(&spectrum)?;
+        }
+        writer.close()?;
+        let buffer = writer.get_mut()?.get_ref().clone();
+
+        let text = String::from_utf8_lossy(&buffer);
+        assert!(!text.contains("MS:1000523"));
+        assert!(!text.contains("MS:1000576"));
+
+        let mut reader2 = MzMLReader::new_indexed(io::Cursor::new(buffer));
+        assert_eq!(reader2.len(), reader.len());
+        for (a, b) in reader.iter().zip(reader2.iter()) {
+            assert_eq!(a.id(), b.id());
+            let arrays_a = a.arrays.as_ref().unwrap();
+            let arrays_b = b.arrays.as_ref().unwrap();
+            let mzs = arrays_b.get(&ArrayType::MZArray).unwrap();
+            assert_eq!(mzs.dtype, BinaryDataArrayType::Float32);
+            for (x, y) in arrays_a.mzs()?.iter().zip(arrays_b.mzs()?.iter()) {
+                assert!((x - y).abs() < 1e-3, "{}: {x} - {y}", a.id());
+            }
+            for (x, y) in arrays_a
+                .intensities()?
+                .iter()
+                .zip(arrays_b.intensities()?.iter())
+            {
+                assert!((x - y).abs() <= x.abs() * 1e-5, "{}: {x} - {y}", a.id());
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn write_default_data_processing_test() -> WriterResult {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut reader = MzMLReader::<_>::open_path(path).expect("Test file doesn't exist?");
+
+        let mut writer = MzMLWriterType::new(io::Cursor::new(Vec::new()));
+        writer.copy_metadata_from(&reader);
+        let mut spectra: Vec<_> = reader.iter().take(2).collect();
+        spectra[1].description.data_processing_id = Some("custom_processing".to_string());
+        *writer.spectrum_count_mut() = spectra.len() as u64;
+        for spectrum in spectra.iter() {
+            writer.write(spectrum)?;
+        }
+        writer.close()?;
+        let buffer = writer.get_mut()?.get_ref().clone();
+
+        let text = String::from_utf8_lossy(&buffer);
+        assert!(text.contains(
+            r#"<spectrumList count="2" defaultDataProcessingRef="pwiz_Reader_Thermo_conversion">"#
+        ));
+        assert_eq!(text.matches(r#" dataProcessingRef="#).count(), 1);
+
+        let reader2 = MzMLReader::new(io::Cursor::new(buffer));
+        let ids: Vec<_> = reader2.map(|s| s.description.data_processing_id).collect();
+        assert_eq!(
+            ids,
+            [
+                Some("pwiz_Reader_Thermo_conversion".to_string()),
+                Some("custom_processing".to_string())
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn write_id_transform_test() -> WriterResult {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut reader = MzMLReader::<_>::open_path(path).expect("Test file doesn't exist?");
+        let n = reader.len();
+
+        let mut writer = MzMLWriterType::new(io::Cursor::new(Vec::new()));
+        writer.copy_metadata_from(&reader);
+        *writer.spectrum_count_mut() = 2 * n as u64;
+        for prefix in ["a", "b"] {
+            writer.set_id_transform(move |id| format!("{prefix}:{id}"));
+            for spectrum in reader.iter() {
+                writer.write(&spectrum)?;
+            }
+        }
+        writer.close()?;
+        let buffer = writer.get_mut()?.get_ref().clone();
+
+        let mut reader2 = MzMLReader::new_indexed(io::Cursor::new(buffer));
+        assert_eq!(reader2.len(), 2 * n);
+        let ids: HashSet<String> = reader2.iter().map(|s| s.id().to_string()).collect();
+        assert_eq!(ids.len(), 2 * n);
+
+        let first = reader.get_spectrum_by_index(0).unwrap();
+        let copy = reader2
+            .get_spectrum_by_id(&format!("b:{}", first.id()))
+            .unwrap();
+        assert_eq!(copy.index(), n);
+
+        let mut n_refs = 0;
+        for spectrum in reader2.iter() {
+            let prefix = &spectrum.id()[..2];
+            for prec in spectrum.precursor_iter() {
+                if let Some(prec_id) = prec.precursor_id.as_ref() {
+                    assert!(prec_id.starts_with(prefix));
+                    assert!(ids.contains(prec_id));
+                    n_refs += 1;
+                }
+            }
+        }
+        assert_eq!(n_refs, 68);
+        Ok(())
+    }
+
+    #[test]
+    fn write_index_test() -> WriterResult {
+        let path = path::Path::new("./test/data/small.mzML");
+        let mut reader = MzMLReader::<_>::open_path(path).expect("Test file doesn't exist?");
+
+        let mut writer = MzMLWriterType::new(io::Cursor::new(Vec::new()));
+        writer.copy_metadata_from(&reader);
+        *writer.spectrum_count_mut() = reader.len() as u64;
+        for spectrum in reader.iter() {
+            writer.write(&spectrum)?;
+        }
+        writer.close()?;
+        let buffer = writer.get_mut()?.get_ref().clone();
+
+        let text = String::from_utf8_lossy(&buffer);
+        assert!(text.starts_with("<?xml"));
+        assert!(text.contains("<indexedmzML"));
+        let checksum_tag = "<fileChecksum>";
+        let checksum_start = text.find(checksum_tag).unwrap() + checksum_tag.len();
+        let checksum_end = text[checksum_start..].find('<').unwrap() + checksum_start;
+
+        let mut hasher = <sha1::Sha1 as sha1::Digest>::new();
+        sha1::Digest::update(&mut hasher, &buffer[..checksum_start]);
+        let expected = base16ct::lower::encode_string(&sha1::Digest::finalize(hasher));
+        assert_eq!(&text[checksum_start..checksum_end], expected);
+
+        let mut reader2 = MzMLReader::new(io::Cursor::new(buffer));
+        let n = reader2.read_index_from_end().expect("Failed to read index");
+        assert!(n > 0);
+        assert_eq!(reader2.len(), reader.len());
+        let scan = reader2.get_spectrum_by_index(10).unwrap();
+        let ref_scan = reader.get_spectrum_by_index(10).unwrap();
+        assert_eq!(scan.id(), ref_scan.id());
+        let scan = reader2
+            .get_spectrum_by_id("controllerType=0 controllerNumber=1 scan=30")
+            .unwrap();
+        assert_eq!(scan.index(), 29);
+        Ok(())
+    }
 }
@@ -1,5 +1,5 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::{self, prelude::*, SeekFrom};
 use std::path::Path;
 use std::{fs, mem};
@@ -24,7 +24,7 @@ use crate::io::{OffsetIndex, RandomAccessSpectrumIterator, SpectrumSource, Spect
 use crate::prelude::{MSDataFileMetadata, ParamLike};
 
 use crate::meta::{
-    DataProcessing, FileDescription, InstrumentConfiguration, MassSpectrometryRun, Sample, Software
+    CvReference, DataProcessing, FileDescription, InstrumentConfiguration, MassSpectrometryRun, Sample, Software
 };
 use crate::params::{ControlledVocabulary, Param, ParamValue};
 use crate::spectrum::bindata::{
@@ -527,7 +527,7 @@ impl<
         state: MzMLParserState,
         reader_position: usize,
     ) -> ParserResult {
-        let elt_name = event.name();
+        let elt_name = event.local_name();
         match elt_name.as_ref() {
             b"cvParam" | b"userParam" => {
                 match &state {
@@ -538,13 +538,14 @@ impl<
                             state,
                         ) {
                             Ok(param) => {
+                                self.inner.check_accession(event, &param, reader_position);
                                 match &state {
                                     MzMLParserState::BinaryDataArray => {
                                         if !param.is_controlled()
-                                            || param.controlled_vocabulary.unwrap()
-                                                != ControlledVocabulary::MS
+                                            || param.controlled_vocabulary
+                                                != Some(ControlledVocabulary::MS)
                                         {
-                                            self.inner.fill_param_into(param, state)
+                                            self.inner.fill_param_into(param, state)?
                                         } else {
                                             match param.accession.unwrap() {
                                                 // external HDF5 dataset
@@ -572,11 +573,11 @@ impl<
                                                     param.value.to_u64().expect(
                                                         "Failed to extract external array length",
                                                     ) as usize,
-                                                _ => self.inner.fill_param_into(param, state),
+                                                _ => self.inner.fill_param_into(param, state)?,
                                             }
                                         }
                                     }
-                                    _ => self.inner.fill_param_into(param, state),
+                                    _ => self.inner.fill_param_into(param, state)?,
                                 }
                                 return Ok(state);
                             }
@@ -596,7 +597,7 @@ impl<
         event: &quick_xml::events::BytesEnd,
         state: MzMLParserState,
     ) -> ParserResult {
-        let elt_name = event.name();
+        let elt_name = event.local_name();
         let res = match elt_name.as_ref() {
             b"binaryDataArray" => {
                 if self.current_data_range_query.name.is_empty() {
@@ -706,6 +707,21 @@ impl<
         self
     }
 
+    fn borrow_reference_param_groups(
+        mut self,
+        reference_param_groups: &'a HashMap<String, Vec<Param>>,
+    ) -> Self {
+        self.inner = self
+            .inner
+            .borrow_reference_param_groups(reference_param_groups);
+        self
+    }
+
+    fn borrow_reported_accessions(mut self, reported_accessions: &'a mut HashSet<String>) -> Self {
+        self.inner = self.inner.borrow_reported_accessions(reported_accessions);
+        self
+    }
+
     fn new_selected_ion(&mut self) -> &mut SelectedIon {
         self.inner.new_selected_ion()
     }
@@ -971,6 +987,10 @@ impl<
         match self._parse_into(accumulator) {
             Ok((accumulator, sz)) => {
                 accumulator.into_spectrum(spectrum);
+                if spectrum.description.data_processing_id.is_none() {
+                    spectrum.description.data_processing_id =
+                        self.mzml_parser.run.default_data_processing_id.clone();
+                }
                 Ok(sz)
             }
             Err(err) => Err(err),
@@ -1235,6 +1255,14 @@ impl<C: CentroidPeakAdapting, D: DeconvolutedPeakAdapting> MSDataFileMetadata
     fn run_description_mut(&mut self) -> Option<&mut MassSpectrometryRun> {
         Some(&mut self.mzml_parser.run)
     }
+
+    fn controlled_vocabularies(&self) -> Option<&Vec<CvReference>> {
+        Some(&self.mzml_parser.cv_list)
+    }
+
+    fn controlled_vocabularies_mut(&mut self) -> Option<&mut Vec<CvReference>> {
+        Some(&mut self.mzml_parser.cv_list)
+    }
 }
 
 pub type MzMLbReader = MzMLbReaderType<CentroidPeak, DeconvolutedPeak>;
@@ -74,6 +74,12 @@ impl OffsetIndex {
         self.offsets.is_empty()
     }
 
+    /// Check if the index has been built or read, even if it turned out to be empty
+    #[inline]
+    pub fn is_initialized(&self) -> bool {
+        self.init
+    }
+
     pub fn keys(&self) -> Keys<Box<str>, u64> {
         self.offsets.keys()
     }
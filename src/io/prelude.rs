@@ -0,0 +1,30 @@
+//! A prelude for reading and writing mass spectrometry data files.
+//!
+//! This re-exports everything in [`mzdata::prelude`](crate::prelude) along with the
+//! reader and writer types for the built-in formats, so that a single glob import is
+//! enough to convert between formats.
+//!
+//! ```rust
+//! use mzdata::io::prelude::*;
+//!
+//! let mut reader = MzMLReader::open_path("./test/data/small.mzML")?;
+//! let mut writer = MGFWriter::new(Vec::new());
+//!
+//! let mut n_written = 0;
+//! for spectrum in reader.iter().filter(|s| s.ms_level() > 1) {
+//!     writer.write(&spectrum)?;
+//!     n_written += 1;
+//! }
+//! writer.flush()?;
+//! assert_eq!(n_written, 34);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+pub use crate::prelude::*;
+
+pub use crate::io::{MGFReader, MGFWriter, MZReader, MzMLReader, MzMLWriter};
+
+#[cfg(feature = "mzmlb")]
+pub use crate::io::mzmlb::{MzMLbReader, MzMLbWriter};
+
+#[cfg(feature = "thermorawfilereader")]
+pub use crate::io::thermo::ThermoRawReader;
@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 use super::usi::USI;
 use crate::params::{ControlledVocabulary, Param, ParamCow, Value, CURIE};
 use crate::spectrum::{ArrayType, BinaryArrayMap, BinaryDataArrayType, DataArray, IsolationWindowState, MultiLayerSpectrum, Precursor, ScanPolarity, SignalContinuity, SpectrumDescription};
+use crate::utils::{minutes_to_seconds, seconds_to_minutes};
 use crate::{curie, prelude::*};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -469,20 +470,17 @@ impl From<&PROXISpectrum> for SpectrumDescription {
 
                 "scan start time" => {
                     if let Some(s) = this.acquisition.first_scan_mut() {
-                        s.start_time = param.value.to_f64().expect("Failed to extract scan start time") / 60.0;
+                        s.start_time = seconds_to_minutes(param.value.to_f64().expect("Failed to extract scan start time"));
                     }
                 },
                 "ion injection time" => {
                     if let Some(s) = this.acquisition.first_scan_mut() {
-                        s.injection_time = param.to_f32().expect("Failed to extract ion injection time");
+                        s.injection_time = Some(param.to_f32().expect("Failed to extract ion injection time"));
                     }
                 }
                 "filter string" => {
                     if let Some(s) = this.acquisition.first_scan_mut() {
-                        let mut param = Param::new_key_value("filter string", param.value.to_string());
-                        param.controlled_vocabulary = Some(ControlledVocabulary::MS);
-                        param.accession = Some(1000512);
-                        s.add_param(param);
+                        s.filter_string = Some(param.value.to_string());
                     }
                 }
 
@@ -707,9 +705,16 @@ where
             let p = PROXIParam {
                 name: "scan start time".into(),
                 accession: curie!(MS:1000016),
-                value: Value::Float(event.start_time * 60.0).into(),
+                value: Value::Float(minutes_to_seconds(event.start_time)).into(),
             };
             this.add_attribute(p);
+            if let Some(filter_string) = event.filter_string.as_deref() {
+                this.add_attribute(PROXIParam {
+                    name: "filter string".into(),
+                    accession: curie!(MS:1000512),
+                    value: Value::String(filter_string.to_string()).into(),
+                });
+            }
             for param in event.params() {
                 if param.is_controlled() {
                     this.add_attribute(param.clone());
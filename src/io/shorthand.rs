@@ -39,7 +39,7 @@ macro_rules! mz_read {
                     $crate::io::MassSpectrometryFormat::MGF => {
                         let handle = std::fs::File::open(read_path)?;
                         if is_gzipped {
-                            let fh = $crate::io::RestartableGzDecoder::new(std::io::BufReader::new(handle));
+                            let fh = $crate::io::RestartableDecoder::new(std::io::BufReader::new(handle))?;
                             #[allow(unused_mut)]
                             let mut $reader: $crate::io::StreamingSpectrumIterator<$C, $D, _, _> = $crate::io::StreamingSpectrumIterator::new($crate::io::mgf::MGFReaderType::<_, $C, $D>::new(fh));
                             Ok($impl)
@@ -53,7 +53,7 @@ macro_rules! mz_read {
                         let handle = std::fs::File::open(read_path)?;
 
                         if is_gzipped {
-                            let fh = $crate::io::RestartableGzDecoder::new(std::io::BufReader::new(handle));
+                            let fh = $crate::io::RestartableDecoder::new(std::io::BufReader::new(handle))?;
                             #[allow(unused_mut)]
                             let mut $reader: $crate::io::StreamingSpectrumIterator<$C, $D, _, _> = $crate::io::StreamingSpectrumIterator::new($crate::io::mzml::MzMLReaderType::<_, $C, $D>::new(fh));
                             Ok($impl)
@@ -91,7 +91,7 @@ macro_rules! mz_read {
                         let handle = std::io::BufReader::new(handle);
                         #[allow(unused_mut)]
                         if is_gzipped {
-                            let fh = $crate::io::RestartableGzDecoder::new(std::io::BufReader::new(handle));
+                            let fh = $crate::io::RestartableDecoder::new(std::io::BufReader::new(handle))?;
                             #[allow(unused_mut)]
                             let mut $reader: $crate::io::StreamingSpectrumIterator<$C, $D, _, _> = $crate::io::StreamingSpectrumIterator::new($crate::io::mgf::MGFReaderType::<_, $C, $D>::new(fh));
                             Ok($impl)
@@ -105,7 +105,7 @@ macro_rules! mz_read {
                         let handle = std::io::BufReader::new(handle);
                         #[allow(unused_mut)]
                         if is_gzipped {
-                            let fh = $crate::io::RestartableGzDecoder::new(std::io::BufReader::new(handle));
+                            let fh = $crate::io::RestartableDecoder::new(std::io::BufReader::new(handle))?;
                             #[allow(unused_mut)]
                             let mut $reader: $crate::io::StreamingSpectrumIterator<$C, $D, _, _> = $crate::io::StreamingSpectrumIterator::new($crate::io::mzml::MzMLReaderType::<_, $C, $D>::new(fh));
                             Ok($impl)
@@ -139,7 +139,7 @@ macro_rules! mz_read {
                             #[allow(unused_mut)]
                             let mut $reader: $crate::io::StreamingSpectrumIterator<$C, $D, _, _> = $crate::io::StreamingSpectrumIterator::new(
                                 $crate::io::mgf::MGFReaderType::new(
-                                    $crate::io::RestartableGzDecoder::new(std::io::BufReader::new(buffered)),
+                                    $crate::io::RestartableDecoder::new(std::io::BufReader::new(buffered))?,
                             ));
                             Ok($impl)
                         } else {
@@ -153,7 +153,7 @@ macro_rules! mz_read {
                         if compressed {
                             #[allow(unused_mut)]
                             let mut $reader: $crate::io::StreamingSpectrumIterator<$C, $D, _, _> = $crate::io::StreamingSpectrumIterator::new(
-                                $crate::io::mzml::MzMLReaderType::new($crate::io::RestartableGzDecoder::new(std::io::BufReader::new(buffered)),
+                                $crate::io::mzml::MzMLReaderType::new($crate::io::RestartableDecoder::new(std::io::BufReader::new(buffered))?,
                             ));
                             Ok($impl)
                         } else {
@@ -316,6 +316,14 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_mz_read_bzipped() -> io::Result<()> {
+        let val: Vec<_> =
+            mz_read!("./test/data/small.mzML.bz2".as_ref(), reader => { reader.collect() })?;
+        assert_eq!(val.len(), 48);
+        Ok(())
+    }
+
     #[test]
     fn test_mz_read_nested() -> io::Result<()> {
         mz_read!("./test/data/small.mzML".as_ref(), reader => {
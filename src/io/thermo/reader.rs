@@ -558,7 +558,7 @@ impl<C: CentroidLike + Default + From<CentroidPeak>, D: DeconvolutedCentroidLike
     }
 
     fn populate_scan_event(&self, vevent: &AcquisitionT, event: &mut ScanEvent) {
-        event.injection_time = vevent.injection_time();
+        event.injection_time = Some(vevent.injection_time());
         let window = ScanWindow::new(vevent.low_mz() as f32, vevent.high_mz() as f32);
         event.scan_windows.push(window);
         if let Some(cv) = vevent.compensation_voltage() {
@@ -668,9 +668,7 @@ impl<C: CentroidLike + Default + From<CentroidPeak>, D: DeconvolutedCentroidLike
         if let Some(vacq) = view.acquisition() {
             self.populate_scan_event(&vacq, event);
             if let Some(filter) = view.filter_string() {
-                let mut p: Param = param!("filter string", 1000512).into();
-                p.value = filter.into();
-                event.add_param(p);
+                event.filter_string = Some(filter.to_string());
             }
         }
 
@@ -1123,7 +1121,7 @@ mod test {
         let event = spec.acquisition().first_scan().unwrap();
         assert_eq!(1, event.instrument_configuration_id);
 
-        assert!((event.injection_time - 68.227486).abs() < 1e-3);
+        assert!((event.injection_time.unwrap() - 68.227486).abs() < 1e-3);
         assert!((event.start_time - 0.004935).abs() < 1e-3);
 
         for (k, v) in reader.instrument_configurations().iter() {
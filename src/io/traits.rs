@@ -4,9 +4,9 @@ mod spectrum;
 mod util;
 
 pub use spectrum::{
-    MZFileReader, MemorySpectrumSource, RandomAccessSpectrumGroupingIterator,
-    RandomAccessSpectrumIterator, RandomAccessSpectrumSource, SpectrumAccessError,
-    SpectrumGrouping, SpectrumIterator, SpectrumReceiver, SpectrumSource,
+    split_by_ms_level, MZFileReader, MemorySpectrumSource, RandomAccessSpectrumGroupingIterator,
+    RandomAccessSpectrumIterator, RandomAccessSpectrumSource, RunStatistics, ScanWriter,
+    SpectrumAccessError, SpectrumGrouping, SpectrumIterator, SpectrumReceiver, SpectrumSource,
     SpectrumSourceWithMetadata, SpectrumWriter, StreamingSpectrumIterator,
 };
 pub use util::SeekRead;
@@ -29,4 +29,164 @@ mod test {
         // couldn't compile.
         let _f = |_x: &dyn SpectrumSource| {};
     }
+
+    #[test]
+    fn test_scan_writer_dyn() -> std::io::Result<()> {
+        use crate::io::{MGFWriter, MzMLReader, MzMLWriter};
+        use crate::spectrum::SpectrumLike;
+        use std::io::Cursor;
+
+        let spectra: Vec<_> = MzMLReader::open_path("./test/data/small.mzML")?
+            .filter(|s| s.ms_level() == 2)
+            .take(5)
+            .collect();
+
+        let mut writers: Vec<Box<dyn ScanWriter>> = vec![
+            Box::new(MGFWriter::new(Cursor::new(Vec::new()))),
+            Box::new(MzMLWriter::new(Cursor::new(Vec::new()))),
+        ];
+        for writer in writers.iter_mut() {
+            for spectrum in spectra.iter() {
+                writer.write_scan(spectrum)?;
+            }
+            writer.flush_writer()?;
+            writer.close_writer()?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_spectra_by_ids_unindexed() {
+        use crate::prelude::*;
+        use crate::spectrum::Spectrum;
+
+        let spectra = (1..=4).map(|i| {
+            let mut spectrum = Spectrum::default();
+            spectrum.description.id = format!("scan={i}");
+            spectrum
+        });
+        let mut source = StreamingSpectrumIterator::new(spectra);
+        assert!(!source.has_index());
+
+        let found = source.get_spectra_by_ids(&["scan=2", "scan=4"]);
+        let ids: Vec<_> = found.iter().map(|s| s.as_ref().map(|s| s.id())).collect();
+        assert_eq!(ids, [Some("scan=2"), Some("scan=4")]);
+
+        // IDs out of file order are still found in a single pass
+        let spectra = (1..=4).map(|i| {
+            let mut spectrum = Spectrum::default();
+            spectrum.description.id = format!("scan={i}");
+            spectrum
+        });
+        let mut source = StreamingSpectrumIterator::new(spectra);
+        let found = source.get_spectra_by_ids(&["scan=3", "scan=missing", "scan=1", "scan=3"]);
+        let ids: Vec<_> = found.iter().map(|s| s.as_ref().map(|s| s.id())).collect();
+        assert_eq!(ids, [Some("scan=3"), None, Some("scan=1"), None]);
+        assert!(source.next().is_none());
+    }
+
+    #[test]
+    fn test_split_by_ms_level() -> std::io::Result<()> {
+        use crate::io::{MGFReader, MGFWriter, MzMLReader};
+        use crate::spectrum::{SignalContinuity, SpectrumLike};
+        use std::fs;
+
+        let levels: Vec<u8> = MzMLReader::open_path("./test/data/small.mzML")?
+            .map(|s| s.ms_level())
+            .collect();
+        let n_ms1 = levels.iter().filter(|level| **level == 1).count();
+        let n_ms2 = levels.iter().filter(|level| **level == 2).count();
+
+        let tmpdir = tempfile::tempdir()?;
+        let ms1_path = tmpdir.path().join("small.ms1.mgf");
+        let ms2_path = tmpdir.path().join("small.ms2.mgf");
+        // MGF only holds peak lists, so write the MS1 profile points as they are
+        let reader = MzMLReader::open_path("./test/data/small.mzML")?.map(|mut s| {
+            s.description.signal_continuity = SignalContinuity::Centroid;
+            s
+        });
+        let writer_factory = |ms_level: u8| -> std::io::Result<Box<dyn ScanWriter>> {
+            let path = if ms_level == 1 { &ms1_path } else { &ms2_path };
+            let mut writer = MGFWriter::new(fs::File::create(path)?);
+            writer.set_include_ms1(ms_level == 1);
+            Ok(Box::new(writer))
+        };
+        let counts = split_by_ms_level(reader, writer_factory)?;
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[&1], n_ms1);
+        assert_eq!(counts[&2], n_ms2);
+
+        let ms1_spectra: Vec<_> = MGFReader::open_path(&ms1_path)?.collect();
+        assert_eq!(ms1_spectra.len(), n_ms1);
+        assert!(ms1_spectra.iter().all(|s| s.ms_level() == 1));
+        let ms2_spectra: Vec<_> = MGFReader::open_path(&ms2_path)?.collect();
+        assert_eq!(ms2_spectra.len(), n_ms2);
+        assert!(ms2_spectra.iter().all(|s| s.ms_level() == 2));
+
+        // The writers already opened are still closed when a later one can't be created
+        let reader = MzMLReader::open_path("./test/data/small.mzML")?.map(|mut s| {
+            s.description.signal_continuity = SignalContinuity::Centroid;
+            s
+        });
+        let writer_factory = |ms_level: u8| -> std::io::Result<Box<dyn ScanWriter>> {
+            if ms_level == 1 {
+                let mut writer = MGFWriter::new(fs::File::create(&ms1_path)?);
+                writer.set_include_ms1(true);
+                Ok(Box::new(writer))
+            } else {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "no MSn output",
+                ))
+            }
+        };
+        let err = split_by_ms_level(reader, writer_factory).unwrap_err();
+        assert_eq!(err.to_string(), "no MSn output");
+        let ms1_spectra: Vec<_> = MGFReader::open_path(&ms1_path)?.collect();
+        assert!(!ms1_spectra.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_precursor_chain() {
+        use crate::prelude::*;
+        use crate::spectrum::{Precursor, Spectrum};
+        use std::collections::VecDeque;
+
+        let make = |id: &str, ms_level: u8, precursor_id: Option<&str>| {
+            let mut spectrum = Spectrum::default();
+            spectrum.description.id = id.to_string();
+            spectrum.description.ms_level = ms_level;
+            spectrum.description.precursor = precursor_id.map(|pid| Precursor {
+                precursor_id: Some(pid.to_string()),
+                ..Default::default()
+            });
+            spectrum
+        };
+
+        let spectra: VecDeque<Spectrum> = vec![
+            make("scan=1", 1, None),
+            make("scan=2", 2, Some("scan=1")),
+            make("scan=3", 3, Some("scan=2")),
+            make("scan=4", 2, Some("scan=5")),
+            make("scan=5", 2, Some("scan=4")),
+        ]
+        .into();
+        let mut source = MemorySpectrumSource::new(spectra);
+
+        let ms3 = source.get_spectrum_by_id("scan=3").unwrap();
+        let chain = source.precursor_chain(&ms3);
+        let ids: Vec<_> = chain.iter().map(|s| s.id()).collect();
+        assert_eq!(ids, ["scan=2", "scan=1"]);
+        assert_eq!(chain.last().unwrap().ms_level(), 1);
+
+        let ms1 = source.get_spectrum_by_id("scan=1").unwrap();
+        assert!(source.precursor_chain(&ms1).is_empty());
+
+        // A cyclic reference terminates rather than looping forever
+        let cyclic = source.get_spectrum_by_id("scan=4").unwrap();
+        let chain = source.precursor_chain(&cyclic);
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain[0].id(), "scan=5");
+    }
 }
@@ -1,14 +1,15 @@
 
-use std::collections::{HashMap, VecDeque};
+use std::collections::{hash_map::Entry, HashMap, HashSet, VecDeque};
 use std::path::PathBuf;
 use std::sync::mpsc::Receiver;
+use std::sync::Arc;
 use std::{fs, io, path};
 use std::ops::Index;
 use std::marker::PhantomData;
 
 use log::warn;
 use mzpeaks::{
-    CentroidLike, CentroidPeak, DeconvolutedCentroidLike, DeconvolutedPeak,
+    CentroidLike, CentroidPeak, DeconvolutedCentroidLike, DeconvolutedPeak, Tolerance,
 };
 use thiserror::Error;
 
@@ -37,6 +38,93 @@ pub trait SpectrumSource<
     /// Retrieve a spectrum by it's integer index
     fn get_spectrum_by_index(&mut self, index: usize) -> Option<S>;
 
+    /// Retrieve a spectrum by its position in the source, which is the same as its
+    /// index unless the source declares indices that skip values.
+    fn get_spectrum_by_position(&mut self, position: usize) -> Option<S> {
+        self.get_spectrum_by_index(position)
+    }
+
+    /// Retrieve several spectra by their native IDs, returned in the same order as `ids`.
+    ///
+    /// The indexed spectra are read in the order they occur in the file to minimize seeking.
+    /// An entry is [`None`] if that ID could not be found.
+    ///
+    /// If the source has no index, the remaining spectra are read once, in file order, and
+    /// matched against `ids`, so `ids` may be given in any order. Spectra that were already
+    /// consumed are not found, and an ID given more than once is only filled in at its first
+    /// position.
+    fn get_spectra_by_ids(&mut self, ids: &[&str]) -> Vec<Option<S>> {
+        let mut spectra: Vec<Option<S>> = (0..ids.len()).map(|_| None).collect();
+        if !self.has_index() {
+            let mut wanted: HashMap<&str, usize> = HashMap::with_capacity(ids.len());
+            for (i, id) in ids.iter().enumerate().rev() {
+                wanted.insert(*id, i);
+            }
+            while !wanted.is_empty() {
+                match self.next() {
+                    Some(spectrum) => {
+                        if let Some(i) = wanted.remove(spectrum.id()) {
+                            spectra[i] = Some(spectrum);
+                        }
+                    }
+                    None => break,
+                }
+            }
+            return spectra;
+        }
+
+        let mut order: Vec<(usize, Option<u64>)> = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (i, self._offset_of_id(id)))
+            .collect();
+        // `None` sorts first, so move the IDs without an offset to the end
+        order.sort_by_key(|(_, offset)| (offset.is_none(), *offset));
+
+        for (i, _) in order {
+            spectra[i] = self.get_spectrum_by_id(ids[i]);
+        }
+        spectra
+    }
+
+    /// Retrieve the chain of precursor spectra of `spectrum`, from its immediate precursor
+    /// up to the MS1 spectrum, by repeatedly looking up each precursor's `precursor_id`.
+    ///
+    /// The chain stops early if a precursor cannot be found or if a spectrum would be
+    /// visited twice, so malformed cyclic references cannot loop forever.
+    fn precursor_chain(&mut self, spectrum: &S) -> Vec<S> {
+        let mut chain = Vec::new();
+        let mut seen = HashSet::new();
+        seen.insert(spectrum.id().to_string());
+        let mut next_id = spectrum.precursor().and_then(|p| p.precursor_id.clone());
+        while let Some(id) = next_id {
+            if !seen.insert(id.clone()) {
+                warn!("Cycle detected in the precursor chain at {id}");
+                break;
+            }
+            match self.get_spectrum_by_id(&id) {
+                Some(parent) => {
+                    next_id = parent.precursor().and_then(|p| p.precursor_id.clone());
+                    chain.push(parent);
+                }
+                None => break,
+            }
+        }
+        chain
+    }
+
+    /// Retrieve a spectrum by it's native ID, wrapped in an [`Arc`] so that it can
+    /// be shared between consumers or threads without copying its peak data.
+    fn get_spectrum_arc_by_id(&mut self, id: &str) -> Option<Arc<S>> {
+        self.get_spectrum_by_id(id).map(Arc::new)
+    }
+
+    /// Retrieve a spectrum by it's integer index, wrapped in an [`Arc`] so that it can
+    /// be shared between consumers or threads without copying its peak data.
+    fn get_spectrum_arc_by_index(&mut self, index: usize) -> Option<Arc<S>> {
+        self.get_spectrum_by_index(index).map(Arc::new)
+    }
+
     /// Retrieve a spectrum by its scan start time
     /// Considerably more complex than seeking by ID or index, this involves
     /// a binary search over the spectrum index and assumes that spectra are stored
@@ -44,7 +132,7 @@ pub trait SpectrumSource<
     fn get_spectrum_by_time(&mut self, time: f64) -> Option<S> {
         let n = self.len();
         if n == 0 {
-            if !self.get_index().init {
+            if !self.has_index() {
                 warn!("Attempting to use `get_spectrum_by_time` when the spectrum index has not been initialized.");
                 return None;
             }
@@ -60,7 +148,7 @@ pub trait SpectrumSource<
         }
         while hi != lo {
             let mid = (hi + lo) / 2;
-            let scan = self.get_spectrum_by_index(mid)?;
+            let scan = self.get_spectrum_by_position(mid)?;
             let scan_time = scan.start_time();
             let err = (scan_time - time).abs();
 
@@ -91,6 +179,12 @@ pub trait SpectrumSource<
         self.len() == 0
     }
 
+    /// Check if the spectrum offset index has been initialized. Random access
+    /// requires an index, so callers may want to build one first if not.
+    fn has_index(&self) -> bool {
+        self.get_index().is_initialized()
+    }
+
     /// Access the spectrum offset index to enumerate all spectra by ID
     fn get_index(&self) -> &OffsetIndex;
 
@@ -98,6 +192,15 @@ pub trait SpectrumSource<
     /// a new adapter
     fn set_index(&mut self, index: OffsetIndex);
 
+    /// Iterate over the native IDs of all spectra in file order using the offset index,
+    /// without reading any spectra. If the index isn't initialized, this will be empty.
+    fn spectrum_ids(&self) -> impl Iterator<Item = &str>
+    where
+        Self: Sized,
+    {
+        self.get_index().keys().map(|k| k.as_ref())
+    }
+
     /// Helper method to support seeking to an ID
     fn _offset_of_id(&self, id: &str) -> Option<u64> {
         self.get_index().get(id)
@@ -127,6 +230,128 @@ pub trait SpectrumSource<
         SpectrumIterator::new(self)
     }
 
+    /// Open a new iterator over this stream which yields each spectrum wrapped in an [`Arc`]
+    /// for cheap sharing, e.g. when fanning spectra out to multiple workers.
+    fn iter_arc(&mut self) -> impl Iterator<Item = Arc<S>>
+    where
+        Self: Sized,
+    {
+        SpectrumIterator::new(self).map(Arc::new)
+    }
+
+    /// Iterate over only those spectra whose scan start time falls within `start_time..=end_time`,
+    /// in minutes.
+    ///
+    /// This uses [`SpectrumSource::get_spectrum_by_time`] to seek to the first spectrum in the
+    /// window and stops after the last one, so the rest of the run is never read. It assumes that
+    /// spectra are stored in chronological order.
+    fn iter_time_range(&mut self, start_time: f64, end_time: f64) -> impl Iterator<Item = S>
+    where
+        Self: Sized,
+    {
+        let start_index = match self.get_spectrum_by_time(start_time) {
+            // The closest spectrum may lie after `start_time`, so back up one step
+            // and let the iterator skip over anything before the window. The iterator
+            // walks spectra by position, which may differ from their declared index.
+            Some(scan) => self
+                .get_index()
+                .index_of(scan.id())
+                .unwrap_or_else(|| scan.index())
+                .saturating_sub(1),
+            None => self.len(),
+        };
+        let mut iter = SpectrumIterator::new(self);
+        iter.index = start_index;
+        iter.skip_while(move |s| s.start_time() < start_time)
+            .take_while(move |s| s.start_time() <= end_time)
+    }
+
+    /// Iterate over the MSn spectra in the run with a selected precursor ion whose m/z falls
+    /// within `low..=high`.
+    ///
+    /// The default implementation reads each spectrum in full. Formats which can read a
+    /// spectrum's metadata separately override this to decode peak data only for matches.
+    fn iter_precursor_range(&mut self, low: f64, high: f64) -> impl Iterator<Item = S>
+    where
+        Self: Sized,
+    {
+        SpectrumIterator::new(self).filter(move |s| {
+            s.precursor().is_some_and(|prec| {
+                prec.ions.iter().any(|ion| low <= ion.mz && ion.mz <= high)
+            })
+        })
+    }
+
+    /// Trace a precursor across the run, collecting the retention time and total ion current
+    /// of each MSn spectrum with a selected ion within `error_tolerance` of `precursor_mz`,
+    /// in file order.
+    ///
+    /// # See also
+    /// [`SpectrumSource::iter_precursor_range`]
+    fn precursor_trace(&mut self, precursor_mz: f64, error_tolerance: Tolerance) -> Vec<(f64, f32)>
+    where
+        Self: Sized,
+    {
+        let (low, high) = error_tolerance.bounds(precursor_mz);
+        self.iter_precursor_range(low, high)
+            .map(|s| (s.start_time(), s.peaks().tic()))
+            .collect()
+    }
+
+    /// Compute summary statistics over every spectrum in the run in a single pass,
+    /// without keeping the spectra themselves in memory.
+    ///
+    /// The source is reset before and after reading.
+    fn run_statistics(&mut self) -> RunStatistics {
+        self.reset();
+        let mut stats = RunStatistics::default();
+        let mut time_sum = 0.0;
+        let mut peak_counts = Vec::new();
+        while let Some(spectrum) = self.next() {
+            *stats.ms_level_counts.entry(spectrum.ms_level()).or_default() += 1;
+            let time = spectrum.start_time();
+            stats.min_time = Some(stats.min_time.map_or(time, |t| t.min(time)));
+            stats.max_time = Some(stats.max_time.map_or(time, |t| t.max(time)));
+            time_sum += time;
+
+            let summary = spectrum.peaks().fetch_summaries();
+            peak_counts.push(summary.count);
+            stats.total_ion_current += summary.tic as f64;
+        }
+        self.reset();
+
+        let n = peak_counts.len();
+        if n > 0 {
+            stats.mean_time = Some(time_sum / n as f64);
+            peak_counts.sort_unstable();
+            stats.median_peak_count = Some(if n % 2 == 0 {
+                (peak_counts[n / 2 - 1] + peak_counts[n / 2]) as f64 / 2.0
+            } else {
+                peak_counts[n / 2] as f64
+            });
+        }
+        stats
+    }
+
+    /// Count the peaks or data points across every spectrum in the run, optionally only
+    /// those spectra with the given `ms_level`.
+    ///
+    /// The default implementation reads each spectrum in full. Formats which declare the
+    /// number of points up front override this to avoid decoding peak data.
+    ///
+    /// The source is reset before and after reading.
+    fn total_peak_count(&mut self, ms_level: Option<u8>) -> u64 {
+        self.reset();
+        let mut total = 0;
+        while let Some(spectrum) = self.next() {
+            if ms_level.map_or(true, |level| spectrum.ms_level() == level) {
+                total += spectrum.peaks().len() as u64;
+            }
+        }
+        self.reset();
+        total
+    }
+
     /// Create a new `SpectrumIterator` over `self` and use that state to drive a `SpectrumGroupIterator`
     fn groups(&mut self) -> SpectrumGroupingIterator<SpectrumIterator<'_, C, D, S, Self>, C, D, S>
     where
@@ -145,6 +370,35 @@ pub trait SpectrumSource<
     }
 }
 
+/// Run-level summary statistics produced by [`SpectrumSource::run_statistics`]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RunStatistics {
+    /// The number of spectra at each MS level
+    pub ms_level_counts: HashMap<u8, usize>,
+    /// The earliest scan start time, in minutes
+    pub min_time: Option<f64>,
+    /// The latest scan start time, in minutes
+    pub max_time: Option<f64>,
+    /// The mean scan start time, in minutes
+    pub mean_time: Option<f64>,
+    /// The median number of peaks or data points per spectrum
+    pub median_peak_count: Option<f64>,
+    /// The sum of the total ion current of every spectrum
+    pub total_ion_current: f64,
+}
+
+impl RunStatistics {
+    /// The total number of spectra across all MS levels
+    pub fn spectrum_count(&self) -> usize {
+        self.ms_level_counts.values().sum()
+    }
+
+    /// The number of spectra at `ms_level`
+    pub fn count_for_level(&self, ms_level: u8) -> usize {
+        self.ms_level_counts.get(&ms_level).copied().unwrap_or_default()
+    }
+}
+
 /// A generic iterator over a [`SpectrumSource`] implementer that assumes the
 /// source has already been indexed. Otherwise, the source's own iterator
 /// behavior should be used.
@@ -181,6 +435,15 @@ impl<
             deconvoluted_type: PhantomData,
         }
     }
+
+    /// Find the position of `spectrum` in the source, falling back to its index
+    /// if the offset index doesn't know its ID.
+    fn position_of(&self, spectrum: &S) -> usize {
+        self.source
+            .get_index()
+            .index_of(spectrum.id())
+            .unwrap_or_else(|| spectrum.index())
+    }
 }
 
 impl<
@@ -197,7 +460,7 @@ impl<
         if self.index + self.back_index >= self.len() {
             return None;
         }
-        let result = self.source.get_spectrum_by_index(self.index);
+        let result = self.source.get_spectrum_by_position(self.index);
         self.index += 1;
         result
     }
@@ -229,7 +492,7 @@ impl<
             return None;
         };
         let i = self.len() - (self.back_index + 1);
-        let result = self.source.get_spectrum_by_index(i);
+        let result = self.source.get_spectrum_by_position(i);
         self.back_index += 1;
         result
     }
@@ -256,6 +519,10 @@ impl<
         self.source.get_spectrum_by_index(index)
     }
 
+    fn get_spectrum_by_position(&mut self, position: usize) -> Option<S> {
+        self.source.get_spectrum_by_position(position)
+    }
+
     fn get_spectrum_by_time(&mut self, time: f64) -> Option<S> {
         self.source.get_spectrum_by_time(time)
     }
@@ -443,7 +710,7 @@ impl<
     /// Start iterating from the spectrum whose native ID matches `id`
     fn start_from_id(&mut self, id: &str) -> Result<&mut Self, SpectrumAccessError> {
         if let Some(scan) = self.get_spectrum_by_id(id) {
-            self.index = scan.index();
+            self.index = self.position_of(&scan);
             self.back_index = 0;
             Ok(self)
         } else if self.get_index().contains_key(id) {
@@ -454,8 +721,8 @@ impl<
     }
 
     fn start_from_index(&mut self, index: usize) -> Result<&mut Self, SpectrumAccessError> {
-        if index < self.len() {
-            self.index = index;
+        if let Some(scan) = self.get_spectrum_by_index(index) {
+            self.index = self.position_of(&scan);
             self.back_index = 0;
             Ok(self)
         } else {
@@ -465,7 +732,7 @@ impl<
 
     fn start_from_time(&mut self, time: f64) -> Result<&mut Self, SpectrumAccessError> {
         if let Some(scan) = self.get_spectrum_by_time(time) {
-            self.index = scan.index();
+            self.index = self.position_of(&scan);
             self.back_index = 0;
             Ok(self)
         } else if self
@@ -580,6 +847,14 @@ impl<
         )
     }
 
+    fn iter_time_range(&mut self, start_time: f64, end_time: f64) -> impl Iterator<Item = S>
+    where
+        Self: Sized,
+    {
+        self.skip_while(move |s| s.start_time() < start_time)
+            .take_while(move |s| s.start_time() <= end_time)
+    }
+
     fn get_spectrum_by_id(&mut self, id: &str) -> Option<S> {
         self.find(|s| s.id() == id)
     }
@@ -1195,4 +1470,124 @@ pub trait SpectrumWriter<
     /// Completes the data file format, preventing new data from being able incorporate additional
     /// data. Does not formally close the underlying writing stream.
     fn close(&mut self) -> io::Result<()>;
-}
\ No newline at end of file
+}
+
+/// An object-safe counterpart to [`SpectrumWriter`] for a single spectrum type `S`.
+///
+/// [`SpectrumWriter`] is generic over the spectrum type in each method, so it cannot be
+/// used as a trait object. [`ScanWriter`] fixes the spectrum type instead, permitting code
+/// that chooses the output format at run time to hold a `Box<dyn ScanWriter>`. Every
+/// [`SpectrumWriter`] implements [`ScanWriter`].
+///
+/// The methods are named differently from [`SpectrumWriter`]'s so that both traits can be
+/// in scope through the [`prelude`](crate::prelude) without making calls ambiguous.
+///
+/// ```rust
+/// use std::io;
+/// use mzdata::prelude::*;
+/// use mzdata::{MGFWriter, MzMLReader, MzMLWriter, Spectrum};
+///
+/// fn write_to(writer: &mut dyn ScanWriter, spectra: &[Spectrum]) -> io::Result<()> {
+///     for spectrum in spectra {
+///         writer.write_scan(spectrum)?;
+///     }
+///     writer.close_writer()
+/// }
+///
+/// let spectra: Vec<_> = MzMLReader::open_path("./test/data/small.mzML")?.collect();
+/// let write_mgf = false;
+/// let mut writer: Box<dyn ScanWriter> = if !write_mgf {
+///     Box::new(MzMLWriter::new(io::sink()))
+/// } else {
+///     Box::new(MGFWriter::new(io::sink()))
+/// };
+/// write_to(writer.as_mut(), &spectra)?;
+/// # Ok::<(), io::Error>(())
+/// ```
+pub trait ScanWriter<
+    C: CentroidLike + Default = CentroidPeak,
+    D: DeconvolutedCentroidLike + Default = DeconvolutedPeak,
+    S: SpectrumLike<C, D> = MultiLayerSpectrum<C, D>,
+>
+{
+    /// Write out a single spectrum, as [`SpectrumWriter::write`]
+    fn write_scan(&mut self, spectrum: &S) -> io::Result<usize>;
+
+    /// As [`std::io::Write::flush`]
+    fn flush_writer(&mut self) -> io::Result<()>;
+
+    /// Completes the data file format, writing any format-specific trailers
+    /// like an indexedmzML index. Does not formally close the underlying writing stream.
+    fn close_writer(&mut self) -> io::Result<()>;
+}
+
+impl<
+        C: CentroidLike + Default,
+        D: DeconvolutedCentroidLike + Default,
+        S: SpectrumLike<C, D> + 'static,
+        T: SpectrumWriter<C, D>,
+    > ScanWriter<C, D, S> for T
+{
+    fn write_scan(&mut self, spectrum: &S) -> io::Result<usize> {
+        SpectrumWriter::write(self, spectrum)
+    }
+
+    fn flush_writer(&mut self) -> io::Result<()> {
+        SpectrumWriter::flush(self)
+    }
+
+    fn close_writer(&mut self) -> io::Result<()> {
+        SpectrumWriter::close(self)
+    }
+}
+
+/// Demultiplex the spectra of `reader` into separate outputs by MS level, e.g. to write
+/// MS1 and MSn spectra to different files.
+///
+/// `writer_factory` is called with an MS level the first time a spectrum of that level is
+/// read, and every spectrum of that level is written to the [`ScanWriter`] it returns.
+/// All writers are closed once `reader` is exhausted, or when creating or writing to a
+/// writer fails, in which case the first error is returned.
+///
+/// Returns the number of spectra written for each MS level.
+pub fn split_by_ms_level<'w, C, D, S, I, F>(
+    reader: I,
+    mut writer_factory: F,
+) -> io::Result<HashMap<u8, usize>>
+where
+    C: CentroidLike + Default,
+    D: DeconvolutedCentroidLike + Default,
+    S: SpectrumLike<C, D>,
+    I: IntoIterator<Item = S>,
+    F: FnMut(u8) -> io::Result<Box<dyn ScanWriter<C, D, S> + 'w>>,
+{
+    let mut writers: HashMap<u8, Box<dyn ScanWriter<C, D, S> + 'w>> = HashMap::new();
+    let mut counts = HashMap::new();
+    let mut result = Ok(());
+    for spectrum in reader {
+        let ms_level = spectrum.ms_level();
+        let writer = match writers.entry(ms_level) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => match writer_factory(ms_level) {
+                Ok(writer) => entry.insert(writer),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            },
+        };
+        if let Err(e) = writer.write_scan(&spectrum) {
+            result = Err(e);
+            break;
+        }
+        *counts.entry(ms_level).or_default() += 1;
+    }
+    // Close the writers opened so far even if a spectrum could not be written
+    for writer in writers.values_mut() {
+        let closed = writer.close_writer();
+        if result.is_ok() {
+            result = closed;
+        }
+    }
+    result.map(|_| counts)
+}
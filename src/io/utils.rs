@@ -6,10 +6,17 @@ use std::path;
 use std::path::PathBuf;
 use std::io::prelude::*;
 
-use md5::Context as MD5Context;
-use md5::Digest;
+use mzpeaks::{
+    CentroidLike, DeconvolutedCentroidLike, MZPeakSetType, MassPeakSetType, PeakCollection,
+};
 use sha1::{self, Digest as _};
 
+use crate::curie;
+use crate::params::Param;
+use crate::spectrum::{
+    ArrayType, MultiLayerSpectrum, RefPeakDataLevel, SignalContinuity, SpectrumLike,
+};
+
 type ByteBuffer = io::Cursor<Vec<u8>>;
 
 #[derive(Debug, Clone, Default)]
@@ -32,6 +39,120 @@ pub enum DetailLevel {
     MetadataOnly,
 }
 
+/// Controls what a reader does when it fails to parse a spectrum while iterating
+#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    #[default]
+    /// Stop iterating at the first spectrum that fails to parse. This is the default
+    Stop,
+    /// Log the error, skip to the start of the next spectrum and keep iterating
+    SkipAndContinue,
+}
+
+/// Controls whether a reader checks that each decoded data array has as many entries as
+/// its spectrum declared, which catches truncated or corrupted arrays early
+#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum ArrayLengthValidation {
+    #[default]
+    /// Do not check array lengths. This is the default
+    Skip,
+    /// Log a warning when an array's length does not match the declared length
+    Warn,
+    /// Fail to parse the spectrum when an array's length does not match the declared length
+    Error,
+}
+
+/// Store the total ion current, base peak and m/z range of `spectrum` in its parameters
+/// if it has peak data and does not already carry a total ion current.
+pub(crate) fn cache_spectrum_summaries<C, D, S>(spectrum: &mut S)
+where
+    C: CentroidLike,
+    D: DeconvolutedCentroidLike,
+    S: SpectrumLike<C, D>,
+{
+    let tic_curie = curie!(MS:1000285);
+    if spectrum.params().iter().any(|p| *p == tic_curie) {
+        return;
+    }
+    if matches!(spectrum.peaks(), RefPeakDataLevel::Missing) {
+        return;
+    }
+    spectrum.update_summaries();
+}
+
+/// The name of the user parameter recording how many peaks a spectrum had before a reader
+/// truncated it to its most intense peaks
+pub(crate) const TRUNCATED_FROM_PEAK_COUNT: &str = "truncated from peak count";
+
+/// Keep only the `max_peaks` most intense peaks of each peak layer of `spectrum` that has
+/// more than that, in their original m/z order. The data arrays are truncated the same way
+/// only when the spectrum is centroided, since dropping points from a profile spectrum would
+/// distort its signal. If anything was removed, the original number of points is recorded
+/// in a [`TRUNCATED_FROM_PEAK_COUNT`] user parameter.
+///
+/// This runs after the spectrum has been fully read, so it bounds the memory the spectrum
+/// keeps, not the peak memory used while reading it.
+pub(crate) fn truncate_to_most_intense<C, D>(
+    spectrum: &mut MultiLayerSpectrum<C, D>,
+    max_peaks: usize,
+) where
+    C: CentroidLike + Default,
+    D: DeconvolutedCentroidLike + Default,
+{
+    let mut original_count = None;
+    if let Some(peaks) = spectrum.peaks.as_mut() {
+        if peaks.len() > max_peaks {
+            original_count = Some(peaks.len());
+            let mut kept = std::mem::take(&mut peaks.peaks);
+            kept.sort_by(|a, b| b.intensity().total_cmp(&a.intensity()));
+            kept.truncate(max_peaks);
+            *peaks = MZPeakSetType::new(kept);
+        }
+    }
+    if let Some(peaks) = spectrum.deconvoluted_peaks.as_mut() {
+        if peaks.len() > max_peaks {
+            original_count = original_count.max(Some(peaks.len()));
+            let mut kept = std::mem::take(&mut peaks.peaks);
+            kept.sort_by(|a, b| b.intensity().total_cmp(&a.intensity()));
+            kept.truncate(max_peaks);
+            *peaks = MassPeakSetType::new(kept);
+        }
+    }
+    let is_profile = spectrum.description.signal_continuity == SignalContinuity::Profile;
+    if let Some(arrays) = spectrum.arrays.as_mut().filter(|_| !is_profile) {
+        if arrays.has_array(&ArrayType::IntensityArray) {
+            let mask = match arrays.intensities() {
+                Ok(intensities) if intensities.len() > max_peaks => {
+                    original_count = original_count.max(Some(intensities.len()));
+                    let mut order: Vec<usize> = (0..intensities.len()).collect();
+                    order.sort_by(|a, b| intensities[*b].total_cmp(&intensities[*a]));
+                    let mut mask = vec![false; intensities.len()];
+                    for i in order.into_iter().take(max_peaks) {
+                        mask[i] = true;
+                    }
+                    Some(mask)
+                }
+                Ok(_) => None,
+                Err(e) => {
+                    log::warn!("Failed to decode intensities to truncate spectrum: {e}");
+                    None
+                }
+            };
+            if let Some(mask) = mask {
+                if let Err(e) = arrays.retain_points(&mask) {
+                    log::warn!("Failed to truncate data arrays of spectrum: {e}");
+                }
+            }
+        }
+    }
+    if let Some(count) = original_count {
+        spectrum
+            .description
+            .params
+            .push(Param::new_key_value(TRUNCATED_FROM_PEAK_COUNT, count));
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub(crate) struct FileSource<T: io::Read> {
     pub source: FileWrapper<T>,
@@ -106,23 +227,24 @@ where
     }
 }
 
-/// A writable stream that keeps a running MD5 checksum of all bytes
+/// A writable stream that keeps a running SHA-1 checksum of all bytes
 #[derive(Clone)]
-pub(crate) struct MD5HashingStream<T: io::Write> {
+pub(crate) struct SHA1HashingStream<T: io::Write> {
     pub stream: T,
-    pub context: MD5Context,
+    pub context: sha1::Sha1,
 }
 
-impl<T: io::Write> MD5HashingStream<T> {
-    pub fn new(file: T) -> MD5HashingStream<T> {
+impl<T: io::Write> SHA1HashingStream<T> {
+    pub fn new(file: T) -> SHA1HashingStream<T> {
         Self {
             stream: file,
-            context: MD5Context::new(),
+            context: sha1::Sha1::new(),
         }
     }
 
-    pub fn compute(&self) -> Digest {
-        self.context.clone().compute()
+    /// Compute the lowercase hexadecimal digest of all bytes written so far
+    pub fn compute(&self) -> String {
+        base16ct::lower::encode_string(&self.context.clone().finalize())
     }
 
     pub fn get_mut(&mut self) -> &mut T {
@@ -134,10 +256,11 @@ impl<T: io::Write> MD5HashingStream<T> {
     }
 }
 
-impl<T: io::Write> io::Write for MD5HashingStream<T> {
+impl<T: io::Write> io::Write for SHA1HashingStream<T> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.context.consume(buf);
-        self.stream.write(buf)
+        let wrote = self.stream.write(buf)?;
+        self.context.update(&buf[..wrote]);
+        Ok(wrote)
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -145,7 +268,7 @@ impl<T: io::Write> io::Write for MD5HashingStream<T> {
     }
 }
 
-impl<T: io::Seek + io::Write> io::Seek for MD5HashingStream<T> {
+impl<T: io::Seek + io::Write> io::Seek for SHA1HashingStream<T> {
     fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
         self.stream.seek(pos)
     }
@@ -8,6 +8,7 @@ mod run;
 mod sample;
 mod software;
 mod activation;
+mod cv;
 #[macro_use]
 mod traits;
 
@@ -26,10 +27,30 @@ pub use instrument::{Component, ComponentType, InstrumentConfiguration, Detector
 pub use run::MassSpectrometryRun;
 pub use traits::MSDataFileMetadata;
 pub use sample::Sample;
+pub use cv::CvReference;
 pub use activation::{DissociationMethodTerm, DissociationEnergyTerm, DissociationEnergy};
 
 use crate::params::{ParamValueParseError, Value, ValueRef};
 
+/// Look up the name of a PSI-MS controlled vocabulary term by its accession number,
+/// searching every term set generated with [`cvmap!`](crate::cvmap) in this module.
+///
+/// If the accession is not part of any of these term sets, [`None`] is returned.
+pub fn resolve_ms_term(accession: u32) -> Option<&'static str> {
+    MassAnalyzerTerm::from_accession(accession)
+        .map(|t| t.name())
+        .or_else(|| IonizationTypeTerm::from_accession(accession).map(|t| t.name()))
+        .or_else(|| InletTypeTerm::from_accession(accession).map(|t| t.name()))
+        .or_else(|| DetectorTypeTerm::from_accession(accession).map(|t| t.name()))
+        .or_else(|| DissociationMethodTerm::from_accession(accession).map(|t| t.name()))
+        .or_else(|| DissociationEnergyTerm::from_accession(accession, 0.0).map(|t| t.name()))
+        .or_else(|| {
+            NativeSpectrumIdentifierFormatTerm::from_accession(accession).map(|t| t.name())
+        })
+        .or_else(|| MassSpectrometerFileFormatTerm::from_accession(accession).map(|t| t.name()))
+        .or_else(|| software::SoftwareTerm::from_accession(accession).map(|t| t.name()))
+}
+
 #[macro_export]
 macro_rules! cvmap {
     (
@@ -406,4 +427,23 @@ impl ValueType {
         };
         Ok(v)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_resolve_ms_term() {
+        assert_eq!(resolve_ms_term(1000080), Some("magnetic sector"));
+        assert_eq!(resolve_ms_term(1000071), Some("chemical ionization"));
+        assert_eq!(resolve_ms_term(1000056), Some("direct inlet"));
+        assert_eq!(resolve_ms_term(1000108), Some("conversion dynode electron multiplier"));
+        assert_eq!(resolve_ms_term(1000134), Some("plasma desorption"));
+        assert_eq!(resolve_ms_term(1002013), Some("collision energy ramp start"));
+        assert_eq!(resolve_ms_term(1000769), Some("Waters nativeID format"));
+        assert_eq!(resolve_ms_term(1000562), Some("ABI WIFF format"));
+        assert_eq!(resolve_ms_term(1000533), Some("Bioworks"));
+        assert_eq!(resolve_ms_term(0), None);
+    }
+}
@@ -225,3 +225,24 @@ pub enum DissociationEnergy {
     Combined { primary: DissociationEnergyTerm, supplementary: DissociationEnergyTerm}
 }
 
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dissociation_method_hcd() {
+        let term = DissociationMethodTerm::from_accession(1000422).unwrap();
+        assert_eq!(term, DissociationMethodTerm::BeamTypeCollisionInducedDissociation);
+        assert_eq!(term.name(), "beam-type collision-induced dissociation");
+        assert_eq!(term.parents(), vec![DissociationMethodTerm::CollisionInducedDissociation]);
+        let curie = crate::curie!(MS:1000422);
+        assert_eq!(DissociationMethodTerm::from_curie(&curie), Some(term));
+        assert_eq!(
+            DissociationMethodTerm::from_name("collision-induced dissociation")
+                .unwrap()
+                .parents(),
+            vec![DissociationMethodTerm::DissociationMethod]
+        );
+    }
+}
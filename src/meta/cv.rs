@@ -0,0 +1,17 @@
+/// A controlled vocabulary declared by a data file, as listed in the mzML `<cvList>`,
+/// along with the version of the ontology that the file's accessions were drawn from.
+#[derive(Debug, Default, PartialEq, Hash, Eq, Clone)]
+pub struct CvReference {
+    /// The short identifier used in `cvRef` attributes, e.g. `MS` or `UO`
+    pub id: String,
+    /// The location the ontology can be retrieved from
+    pub uri: String,
+    /// The version of the ontology, if given
+    pub version: Option<String>,
+}
+
+impl CvReference {
+    pub fn new(id: String, uri: String, version: Option<String>) -> Self {
+        Self { id, uri, version }
+    }
+}
@@ -243,6 +243,25 @@ crate::cvmap! {
     //[[[end]]] (checksum: ec2eb148ac1dd4696c0be8740825ce25)
 }
 
+impl MassAnalyzerTerm {
+    /// A conventional short label for this analyzer type, falling back to
+    /// [`MassAnalyzerTerm::name`] when there isn't one.
+    pub const fn short_name(&self) -> &'static str {
+        match self {
+            Self::Orbitrap => "Orbitrap",
+            Self::Quadrupole => "Quadrupole",
+            Self::TimeOfFlight => "TOF",
+            Self::FourierTransformIonCyclotronResonanceMassSpectrometer => "FT-ICR",
+            Self::IonTrap => "IT",
+            Self::QuadrupoleIonTrap => "QIT",
+            Self::LinearIonTrap
+            | Self::RadialEjectionLinearIonTrap
+            | Self::AxialEjectionLinearIonTrap => "LIT",
+            _ => self.name(),
+        }
+    }
+}
+
 crate::cvmap! {
     #[flag_type=i32]
     #[allow(unused)]
@@ -357,6 +376,23 @@ crate::cvmap! {
     // [[[end]]] (checksum: 698624c65fdd3d93821efcc08a36fa94)
 }
 
+impl IonizationTypeTerm {
+    /// A conventional abbreviation for this ionization type, falling back to
+    /// [`IonizationTypeTerm::name`] when there isn't one.
+    pub const fn short_name(&self) -> &'static str {
+        match self {
+            Self::ElectrosprayIonization => "ESI",
+            Self::Nanoelectrospray => "nanoESI",
+            Self::MatrixAssistedLaserDesorptionIonization => "MALDI",
+            Self::AtmosphericPressureChemicalIonization => "APCI",
+            Self::AtmosphericPressurePhotoionization => "APPI",
+            Self::ElectronIonization => "EI",
+            Self::ChemicalIonization => "CI",
+            _ => self.name(),
+        }
+    }
+}
+
 crate::cvmap! {
     #[flag_type=i32]
     #[allow(unused)]
@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+
 use crate::impl_param_described;
 use crate::params::{ParamDescribed, ParamList, ParamValue, ValueRef};
 
@@ -18,6 +20,17 @@ impl Sample {
         let p = self.get_param_by_curie(&crate::curie!(MS:1000001));
         p.map(|p| p.value.as_ref())
     }
+
+    /// The name of the sample, taken from the `name` attribute if it is not empty,
+    /// otherwise from the `sample name` parameter.
+    pub fn sample_name(&self) -> Option<Cow<'_, str>> {
+        match self.name.as_deref() {
+            Some(name) if !name.is_empty() => Some(Cow::Borrowed(name)),
+            _ => self
+                .get_param_by_curie(&crate::curie!(MS:1000002))
+                .map(|p| p.value.as_str()),
+        }
+    }
 }
 
 
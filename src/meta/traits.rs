@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 
 use super::{
-    DataProcessing, FileDescription, InstrumentConfiguration, MassSpectrometryRun, Sample, Software
+    ComponentType, CvReference, DataProcessing, FileDescription, InstrumentConfiguration,
+    MassSpectrometryRun, Sample, Software,
 };
 
 /// Mass spectrometry data files have several facets of descriptive metadata
@@ -55,6 +56,13 @@ pub trait MSDataFileMetadata {
                 desc.take();
             }
         }
+
+        if let (Some(cvs), Some(dest)) = (
+            source.controlled_vocabularies(),
+            self.controlled_vocabularies_mut(),
+        ) {
+            *dest = cvs.clone();
+        }
     }
 
     /// A hint about how many spectra are in this data file
@@ -72,6 +80,52 @@ pub trait MSDataFileMetadata {
         None
     }
 
+    /// Access the controlled vocabularies declared by this data file, with their versions,
+    /// if the format records them
+    fn controlled_vocabularies(&self) -> Option<&Vec<CvReference>> {
+        None
+    }
+
+    /// Mutably access the controlled vocabularies declared by this data file if the format
+    /// records them
+    fn controlled_vocabularies_mut(&mut self) -> Option<&mut Vec<CvReference>> {
+        None
+    }
+
+    /// A short label naming the ionization type and mass analyzer(s) of the first
+    /// instrument configuration, like `"ESI-Orbitrap"`. Multiple analyzers are
+    /// joined with `/`, as in `"ESI-Quadrupole/Orbitrap"`.
+    ///
+    /// Returns an empty string if no instrument configuration is available.
+    fn instrument_summary(&self) -> String {
+        let config = match self
+            .instrument_configurations()
+            .iter()
+            .min_by_key(|(id, _)| **id)
+        {
+            Some((_, config)) => config,
+            None => return String::new(),
+        };
+        let sources: Vec<_> = config
+            .iter()
+            .filter(|c| c.component_type == ComponentType::IonSource)
+            .flat_map(|c| c.ionization_type())
+            .map(|t| t.short_name())
+            .collect();
+        let analyzers: Vec<_> = config
+            .iter()
+            .filter(|c| c.component_type == ComponentType::Analyzer)
+            .flat_map(|c| c.mass_analyzer())
+            .map(|t| t.short_name())
+            .collect();
+        let parts: Vec<_> = [sources, analyzers]
+            .iter()
+            .filter(|names| !names.is_empty())
+            .map(|names| names.join("/"))
+            .collect();
+        parts.join("-")
+    }
+
     /// Get the name of the primary source file, if available
     fn source_file_name(&self) -> Option<&str> {
         self.file_description().source_files.first().map(|s| s.name.as_str())
@@ -214,5 +268,13 @@ macro_rules! delegate_impl_metadata_trait {
             self.$src.run_description_mut()
         }
 
+        fn controlled_vocabularies(&self) -> Option<&Vec<$crate::meta::CvReference>> {
+            self.$src.controlled_vocabularies()
+        }
+
+        fn controlled_vocabularies_mut(&mut self) -> Option<&mut Vec<$crate::meta::CvReference>> {
+            self.$src.controlled_vocabularies_mut()
+        }
+
     };
 }
\ No newline at end of file
@@ -1245,13 +1245,9 @@ pub trait ParamLike {
     }
 
     fn curie(&self) -> Option<CURIE> {
-        if !self.is_controlled() {
-            None
-        } else {
-            let cv = self.controlled_vocabulary().unwrap();
-            let acc = self.accession().unwrap();
-            // let accession_str = format!("{}:{:07}", cv.prefix(), acc);
-            Some(CURIE::new(cv, acc))
+        match (self.controlled_vocabulary(), self.accession()) {
+            (Some(cv), Some(acc)) => Some(CURIE::new(cv, acc)),
+            _ => None,
         }
     }
 }
@@ -1851,7 +1847,10 @@ pub trait ParamDescribed {
     }
 
     /// Add all parameters from an iterator of [`Param`] to the entity
-    fn extend_params(&mut self, it: impl IntoIterator<Item = Param>) {
+    fn extend_params(&mut self, it: impl IntoIterator<Item = Param>)
+    where
+        Self: Sized,
+    {
         self.params_mut().extend(it)
     }
 
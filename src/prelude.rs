@@ -2,7 +2,7 @@
 pub use crate::io::traits::{
     MZFileReader, RandomAccessSpectrumGroupingIterator, RandomAccessSpectrumIterator,
     RandomAccessSpectrumSource as _, SpectrumSourceWithMetadata as _, SpectrumSource,
-    SpectrumWriter, SeekRead, SpectrumAccessError, SpectrumGrouping, IonMobilityFrameSource,
+    SpectrumWriter, ScanWriter, SeekRead, SpectrumAccessError, SpectrumGrouping, IonMobilityFrameSource,
     IonMobilityFrameGrouping, RandomAccessIonMobilityFrameIterator, ChromatogramSource,
     IonMobilityFrameWriter,
 };
@@ -13,6 +13,7 @@ pub use crate::spectrum::bindata::{
     BuildArrayMapFrom, BuildFromArrayMap, ByteArrayView, ByteArrayViewMut,
 };
 pub use crate::spectrum::{IonProperties, PrecursorSelection, SpectrumLike, IonMobilityMeasure, IonMobilityFrameLike};
+pub use crate::utils::CentroidPeakMassExt;
 
 #[cfg(feature = "mzsignal")]
 pub use crate::spectrum::group::SpectrumGroupAveraging;
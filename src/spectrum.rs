@@ -57,6 +57,7 @@ More examples can be found in the [spectrum tutorial](crate::tutorial::spectrum)
 
 pub mod bindata;
 pub(crate) mod chromatogram;
+pub(crate) mod deconvolution;
 pub(crate) mod frame;
 pub(crate) mod group;
 pub(crate) mod peaks;
@@ -66,16 +67,23 @@ pub mod utils;
 
 pub use crate::spectrum::bindata::{ArrayType, BinaryArrayMap, BinaryDataArrayType, DataArray};
 pub use crate::spectrum::chromatogram::{Chromatogram, ChromatogramLike};
+pub use crate::spectrum::deconvolution::{
+    DeconvolutionParams, DeconvolvingIter, IsotopeModel, PeptideAveragine, SODIUM,
+};
 pub use crate::spectrum::scan_properties::*;
 pub use crate::spectrum::spectrum_types::{
     CentroidPeakAdapting, CentroidSpectrum, CentroidSpectrumType, DeconvolutedPeakAdapting,
-    DeconvolutedSpectrum, DeconvolutedSpectrumType, MultiLayerSpectrum, RawSpectrum, Spectrum,
-    SpectrumConversionError, SpectrumLike, SpectrumProcessingError,
+    DeconvolutedSpectrum, DeconvolutedSpectrumType, MultiLayerSpectrum, PeakAnnotation,
+    RawSpectrum, Spectrum, SpectrumConversionError, SpectrumLike, SpectrumProcessingError,
+    waters_lock_mass_function,
 };
 
+#[cfg(feature = "mzsignal")]
+pub use crate::spectrum::spectrum_types::CentroidingMethod;
+
 pub use crate::spectrum::peaks::{
-    PeakDataIter, PeakDataIterDispatch, PeakDataLevel, RawIter, RefPeakDataIter, RefPeakDataLevel,
-    SpectrumSummary,
+    CentroidPeakBuilder, PeakDataIter, PeakDataIterDispatch, PeakDataLevel, RawIter,
+    RefPeakDataIter, RefPeakDataLevel, SpectrumSummary,
 };
 
 pub use frame::{IonMobilityFrameDescription, IonMobilityFrameLike, MultiLayerIonMobilityFrame};
@@ -15,3 +15,21 @@ pub use encodings::{
 };
 pub use map::{BinaryArrayMap, BinaryArrayMap3D};
 pub use traits::{ByteArrayView, ByteArrayViewMut};
+
+/// Build a [`BinaryArrayMap`] holding `mzs` as a 64-bit m/z array and `intensities` as a
+/// 32-bit intensity array, for tests which need signal without reading a file
+#[cfg(test)]
+pub(crate) fn mz_intensity_arrays(mzs: &[f64], intensities: &[f32]) -> BinaryArrayMap {
+    let mut arrays = BinaryArrayMap::new();
+    arrays.add(DataArray::wrap(
+        &ArrayType::MZArray,
+        BinaryDataArrayType::Float64,
+        to_bytes(mzs),
+    ));
+    arrays.add(DataArray::wrap(
+        &ArrayType::IntensityArray,
+        BinaryDataArrayType::Float32,
+        to_bytes(intensities),
+    ));
+    arrays
+}
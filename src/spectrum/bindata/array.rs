@@ -232,6 +232,12 @@ impl<'transient, 'lifespan: 'transient> DataArray {
         }
     }
 
+    fn decode_base64(data: &[u8]) -> Result<Bytes, ArrayRetrievalError> {
+        base64_simd::STANDARD.decode_type::<Bytes>(data).map_err(|e| {
+            ArrayRetrievalError::DecompressionError(format!("Failed to decode base64 array: {}", e))
+        })
+    }
+
     #[cfg(feature = "numpress")]
     pub fn decompres_numpress_linear(data: &[u8]) -> Result<Vec<f64>, ArrayRetrievalError> {
         match numpress::numpress_decompress(data) {
@@ -240,6 +246,68 @@ impl<'transient, 'lifespan: 'transient> DataArray {
         }
     }
 
+    fn typed_decoded_slice<T: Pod>(
+        &self,
+        dtype: BinaryDataArrayType,
+    ) -> Result<&[T], ArrayRetrievalError> {
+        if self.dtype != dtype {
+            return Err(ArrayRetrievalError::DataTypeSizeMismatch);
+        }
+        if self.data.is_empty() {
+            return Ok(&[]);
+        }
+        if self.compression != BinaryCompressionType::Decoded {
+            return Err(ArrayRetrievalError::DecompressionError(format!(
+                "{} must be decoded before it can be viewed as a slice",
+                self.name
+            )));
+        }
+        Ok(bytemuck::try_cast_slice(self.data.as_slice())?)
+    }
+
+    /// Borrow the decoded bytes as a `&[f64]` without copying.
+    ///
+    /// The array must already be decoded and stored as [`BinaryDataArrayType::Float64`]. The
+    /// cast is checked, so a buffer that is not aligned for `f64` returns an error instead of
+    /// being reinterpreted.
+    pub fn as_f64_slice(&self) -> Result<&[f64], ArrayRetrievalError> {
+        self.typed_decoded_slice(BinaryDataArrayType::Float64)
+    }
+
+    /// Borrow the decoded bytes as a `&[f32]` without copying.
+    ///
+    /// The array must already be decoded and stored as [`BinaryDataArrayType::Float32`]. The
+    /// cast is checked, so a buffer that is not aligned for `f32` returns an error instead of
+    /// being reinterpreted.
+    pub fn as_f32_slice(&self) -> Result<&[f32], ArrayRetrievalError> {
+        self.typed_decoded_slice(BinaryDataArrayType::Float32)
+    }
+
+    /// Keep only the elements whose entry in `mask` is `true`, decoding the data first if needed.
+    ///
+    /// This fails with [`ArrayRetrievalError::ArrayLengthMismatch`] if `mask` is not the same
+    /// length as the array.
+    pub fn retain_by_mask(&mut self, mask: &[bool]) -> Result<(), ArrayRetrievalError> {
+        self.decode_and_store()?;
+        let size = self.dtype.size_of();
+        if self.data.len() != mask.len() * size {
+            return Err(ArrayRetrievalError::ArrayLengthMismatch(
+                mask.len(),
+                self.data.len() / size,
+            ));
+        }
+        let data: Bytes = self
+            .data
+            .chunks_exact(size)
+            .zip(mask)
+            .filter(|(_, keep)| **keep)
+            .flat_map(|(chunk, _)| chunk.iter().copied())
+            .collect();
+        self.item_count = Some(data.len() / size);
+        self.data = data;
+        Ok(())
+    }
+
     /// Decode the compressed data, if needed, and store that buffer in `self.data`. After
     /// decoding `self.compression` will always be [`BinaryCompressionType::Decoded`].
     ///
@@ -276,20 +344,17 @@ impl<'transient, 'lifespan: 'transient> DataArray {
         match self.compression {
             BinaryCompressionType::Decoded => Ok(Cow::Borrowed(self.data.as_slice())),
             BinaryCompressionType::NoCompression => {
-                let bytestring = base64_simd::STANDARD.decode_type::<Bytes>(&self.data)
-                    .unwrap_or_else(|e| panic!("Failed to decode base64 array: {}", e));
+                let bytestring = Self::decode_base64(&self.data)?;
                 Ok(Cow::Owned(bytestring))
             }
             BinaryCompressionType::Zlib => {
-                let bytestring = base64_simd::STANDARD.decode_type::<Bytes>(&self.data)
-                    .unwrap_or_else(|e| panic!("Failed to decode base64 array: {}", e));
+                let bytestring = Self::decode_base64(&self.data)?;
                 Ok(Cow::Owned(Self::decompres_zlib(&bytestring)))
             }
             #[cfg(feature = "numpress")]
             BinaryCompressionType::NumpressLinear => match self.dtype {
                 BinaryDataArrayType::Float64 => {
-                    let mut bytestring = base64_simd::STANDARD.decode_type::<Bytes>(&self.data)
-                        .unwrap_or_else(|e| panic!("Failed to decode base64 array: {}", e));
+                    let mut bytestring = Self::decode_base64(&self.data)?;
                     let decoded = Self::decompres_numpress_linear(&mut bytestring)?;
                     let view = vec_as_bytes(decoded);
                     Ok(Cow::Owned(view))
@@ -318,13 +383,11 @@ impl<'transient, 'lifespan: 'transient> DataArray {
         match self.compression {
             BinaryCompressionType::Decoded => Ok(Cow::Borrowed(&self.data.as_slice()[start..end])),
             BinaryCompressionType::NoCompression => {
-                let bytestring = base64_simd::STANDARD.decode_type::<Bytes>(&self.data)
-                    .unwrap_or_else(|e| panic!("Failed to decode base64 array: {}", e));
+                let bytestring = Self::decode_base64(&self.data)?;
                 Ok(Cow::Owned(bytestring[start..end].to_vec()))
             }
             BinaryCompressionType::Zlib => {
-                let bytestring = base64_simd::STANDARD.decode_type::<Bytes>(&self.data)
-                    .unwrap_or_else(|e| panic!("Failed to decode base64 array: {}", e));
+                let bytestring = Self::decode_base64(&self.data)?;
                 Ok(Cow::Owned(
                     Self::decompres_zlib(&bytestring)[start..end].to_vec(),
                 ))
@@ -343,15 +406,13 @@ impl<'transient, 'lifespan: 'transient> DataArray {
         match self.compression {
             BinaryCompressionType::Decoded => Ok(&mut self.data),
             BinaryCompressionType::NoCompression => {
-                let bytestring = base64_simd::STANDARD.decode_type::<Bytes>(&self.data)
-                    .unwrap_or_else(|e| panic!("Failed to decode base64 array: {}", e));
+                let bytestring = Self::decode_base64(&self.data)?;
                 self.data = bytestring;
                 self.compression = BinaryCompressionType::Decoded;
                 Ok(&mut self.data)
             }
             BinaryCompressionType::Zlib => {
-                let bytestring = base64_simd::STANDARD.decode_type::<Bytes>(&self.data)
-                    .unwrap_or_else(|e| panic!("Failed to decode base64 array: {}", e));
+                let bytestring = Self::decode_base64(&self.data)?;
                 self.data = bytestring;
                 self.compression = BinaryCompressionType::Decoded;
                 Ok(&mut self.data)
@@ -359,8 +420,7 @@ impl<'transient, 'lifespan: 'transient> DataArray {
             #[cfg(feature = "numpress")]
             BinaryCompressionType::NumpressLinear => match self.dtype {
                 BinaryDataArrayType::Float64 => {
-                    let mut bytestring = base64_simd::STANDARD.decode_type::<Bytes>(&self.data)
-                        .unwrap_or_else(|e| panic!("Failed to decode base64 array: {}", e));
+                    let mut bytestring = Self::decode_base64(&self.data)?;
                     let decoded = Self::decompres_numpress_linear(&mut bytestring)?;
                     let view = vec_as_bytes(decoded);
                     self.data = view;
@@ -399,47 +459,28 @@ impl<'transient, 'lifespan: 'transient> DataArray {
     }
 
     /// Recode the stored data as the requested binary data type.
+    ///
+    /// The data will be decoded in the process, leaving `self.compression` as
+    /// [`BinaryCompressionType::Decoded`].
     pub fn store_as(&mut self, dtype: BinaryDataArrayType) -> Result<usize, ArrayRetrievalError> {
         if self.dtype == dtype {
             return Ok(self.data.len());
         }
-        let result = match dtype {
-            BinaryDataArrayType::Float32 => {
-                let view = match self.to_f32() {
-                    Ok(view) => view,
-                    Err(err) => return Err(err),
-                };
-                let recast = to_bytes(&view);
-                self.update_buffer(&recast)
-            }
-            BinaryDataArrayType::Float64 => {
-                let view = match self.to_f64() {
-                    Ok(view) => view,
-                    Err(err) => return Err(err),
-                };
-                let recast = to_bytes(&view);
-                self.update_buffer(&recast)
-            }
-            BinaryDataArrayType::Int32 => {
-                let view = match self.to_i32() {
-                    Ok(view) => view,
-                    Err(err) => return Err(err),
-                };
-                let recast = to_bytes(&view);
-                self.update_buffer(&recast)
-            }
-            BinaryDataArrayType::Int64 => {
-                let view = match self.to_i64() {
-                    Ok(view) => view,
-                    Err(err) => return Err(err),
-                };
-                let recast = to_bytes(&view);
-                self.update_buffer(&recast)
+        let recast = match dtype {
+            BinaryDataArrayType::Float32 => to_bytes(&self.to_f32()?),
+            BinaryDataArrayType::Float64 => to_bytes(&self.to_f64()?),
+            BinaryDataArrayType::Int32 => to_bytes(&self.to_i32()?),
+            BinaryDataArrayType::Int64 => to_bytes(&self.to_i64()?),
+            _ => {
+                self.dtype = dtype;
+                return Ok(0);
             }
-            _ => Ok(0),
         };
+        self.item_count = Some(recast.len() / dtype.size_of());
+        self.data = recast;
         self.dtype = dtype;
-        result
+        self.compression = BinaryCompressionType::Decoded;
+        Ok(self.data.len())
     }
 
     /// Test if the the array describes an ion mobility quantity.
@@ -587,4 +628,32 @@ mod test {
         assert_eq!(da.decode().unwrap().len(), 0);
         assert_eq!(da.to_f64().unwrap().len(), 0);
     }
+
+    #[test]
+    fn test_retain_by_mask() -> io::Result<()> {
+        let mut da = make_array_from_file()?;
+        let mask: Vec<bool> = (0..19800).map(|i| i % 2 == 0).collect();
+        da.retain_by_mask(&mask)?;
+        assert_eq!(da.to_f64()?.len(), 9900);
+
+        let err = da.retain_by_mask(&mask).unwrap_err();
+        assert!(matches!(err, ArrayRetrievalError::ArrayLengthMismatch(19800, 9900)));
+        assert_eq!(da.to_f64()?.len(), 9900);
+        Ok(())
+    }
+
+    #[test]
+    fn test_store_as() -> io::Result<()> {
+        let mut da = make_array_from_file()?;
+        let reference = da.to_f64()?.into_owned();
+        da.store_as(BinaryDataArrayType::Float32)?;
+        assert_eq!(da.dtype, BinaryDataArrayType::Float32);
+        assert_eq!(da.compression, BinaryCompressionType::Decoded);
+        assert_eq!(da.data_len()?, 19800);
+        let view = da.to_f32()?;
+        for (a, b) in reference.iter().zip(view.iter()) {
+            assert!((a - *b as f64).abs() < 1e-3);
+        }
+        Ok(())
+    }
 }
\ No newline at end of file
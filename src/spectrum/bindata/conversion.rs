@@ -65,14 +65,7 @@ impl<C: CentroidLike + From<CentroidPeak>> From<&BinaryArrayMap> for MZPeakSetTy
         let mut peaks = Vec::with_capacity(mz_array.len());
 
         for (i, (mz, intensity)) in mz_array.iter().zip(intensity_array.iter()).enumerate() {
-            peaks.push(
-                CentroidPeak {
-                    mz: *mz,
-                    intensity: *intensity,
-                    index: i as u32,
-                }
-                .into(),
-            )
+            peaks.push(CentroidPeak::new(*mz, *intensity, i as u32).into())
         }
         MZPeakSetType::<C>::new(peaks)
     }
@@ -293,11 +286,7 @@ impl BuildFromArrayMap for CentroidPeak {
         let mut peaks = Vec::with_capacity(mz_array.len());
 
         for (i, (mz, intensity)) in mz_array.iter().zip(intensity_array.iter()).enumerate() {
-            peaks.push(CentroidPeak {
-                mz: *mz,
-                intensity: *intensity,
-                index: i as u32,
-            })
+            peaks.push(CentroidPeak::new(*mz, *intensity, i as u32))
         }
         Ok(peaks)
     }
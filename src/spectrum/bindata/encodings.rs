@@ -66,6 +66,23 @@ impl ArrayType {
         }
     }
 
+    /// The name of the non-standard array holding a second, centroided m/z array stored
+    /// alongside profile data, e.g. from vendor peak picking
+    pub const CENTROID_MZ_ARRAY: &'static str = "centroid m/z array";
+
+    /// The name of the non-standard array holding the intensities paired with
+    /// [`ArrayType::CENTROID_MZ_ARRAY`]
+    pub const CENTROID_INTENSITY_ARRAY: &'static str = "centroid intensity array";
+
+    /// The name of the non-standard array holding m/z and intensity values interleaved as
+    /// `(m/z, intensity)` pairs in a single buffer, as written by a few tools.
+    ///
+    /// When reading mzML, an array is given this name if it declares both the `m/z array`
+    /// and `intensity array` terms, or carries a user parameter with this name.
+    ///
+    /// See [`BinaryArrayMap::deinterleave`](crate::spectrum::bindata::BinaryArrayMap::deinterleave)
+    pub const INTERLEAVED_MZ_INTENSITY_ARRAY: &'static str = "interleaved m/z intensity array";
+
     /// Create a [`ArrayType::NonStandardDataArray`] with the provided name.
     pub fn nonstandard<S: ToString>(name: S) -> ArrayType {
         ArrayType::NonStandardDataArray {
@@ -295,6 +312,8 @@ pub enum ArrayRetrievalError {
     DecompressionError(String),
     #[error("The requested data type does not match the number of bytes available in the buffer")]
     DataTypeSizeMismatch,
+    #[error("Expected {0} entries but the array has {1}")]
+    ArrayLengthMismatch(usize, usize),
 }
 
 impl From<bytemuck::PodCastError> for ArrayRetrievalError {
@@ -320,6 +339,9 @@ impl From<ArrayRetrievalError> for io::Error {
             ArrayRetrievalError::DataTypeSizeMismatch => {
                 io::Error::new(io::ErrorKind::InvalidData, value)
             }
+            ArrayRetrievalError::ArrayLengthMismatch(_, _) => {
+                io::Error::new(io::ErrorKind::InvalidInput, value)
+            }
         }
     }
 }
@@ -88,19 +88,23 @@ impl BinaryArrayMap {
     /// Decode all [`DataArray`] in this map if they have not been decoded already so
     /// that they are ready for use. If there are many arrays and the `parallelism` feature
     /// is enabled, arrays may be decoded in parallel.
+    ///
+    /// An interleaved m/z and intensity array is split, see [`BinaryArrayMap::deinterleave`].
     pub fn decode_all_arrays(&mut self) -> Result<(), ArrayRetrievalError> {
         #[cfg(not(feature = "parallelism"))]
         {
-            self._decode_all_arrays()
+            self._decode_all_arrays()?;
         }
         #[cfg(feature = "parallelism")]
         {
             if self.len() > 2 {
-                self._decode_all_arrays_parallel()
+                self._decode_all_arrays_parallel()?;
             } else {
-                self._decode_all_arrays()
+                self._decode_all_arrays()?;
             }
         }
+        self.deinterleave()?;
+        Ok(())
     }
 
     fn _decode_all_arrays(&mut self) -> Result<(), ArrayRetrievalError> {
@@ -165,6 +169,77 @@ impl BinaryArrayMap {
         self.byte_buffer_map.contains_key(array_type)
     }
 
+    /// Split an array of interleaved `(m/z, intensity)` pairs, stored as the non-standard
+    /// array [`ArrayType::INTERLEAVED_MZ_INTENSITY_ARRAY`], into separate [`ArrayType::MZArray`]
+    /// and [`ArrayType::IntensityArray`] arrays, replacing them if already present.
+    ///
+    /// Returns `true` if an interleaved array was found and split.
+    ///
+    /// # Errors
+    /// If the interleaved array cannot be decoded or holds an odd number of values
+    pub fn deinterleave(&mut self) -> Result<bool, ArrayRetrievalError> {
+        let (mzs, intensities) = match self.split_interleaved() {
+            Some(values) => values?,
+            None => return Ok(false),
+        };
+        let n = mzs.len();
+        let mut mz_array = DataArray::from_name_type_size(
+            &ArrayType::MZArray,
+            BinaryDataArrayType::Float64,
+            n * BinaryDataArrayType::Float64.size_of(),
+        );
+        let mut intensity_array = DataArray::from_name_type_size(
+            &ArrayType::IntensityArray,
+            BinaryDataArrayType::Float32,
+            n * BinaryDataArrayType::Float32.size_of(),
+        );
+        for (mz, intensity) in mzs.into_iter().zip(intensities) {
+            mz_array.push(mz)?;
+            intensity_array.push(intensity as f32)?;
+        }
+        mz_array.unit = Unit::MZ;
+        intensity_array.unit = Unit::DetectorCounts;
+        self.byte_buffer_map.remove(&ArrayType::nonstandard(
+            ArrayType::INTERLEAVED_MZ_INTENSITY_ARRAY,
+        ));
+        self.add(mz_array);
+        self.add(intensity_array);
+        Ok(true)
+    }
+
+    /// Decode the interleaved m/z and intensity array, if there is one, into separate
+    /// m/z and intensity values without modifying the map
+    fn split_interleaved(&self) -> Option<Result<(Vec<f64>, Vec<f64>), ArrayRetrievalError>> {
+        let interleaved = self.get(&ArrayType::nonstandard(
+            ArrayType::INTERLEAVED_MZ_INTENSITY_ARRAY,
+        ))?;
+        let values = match interleaved.to_f64() {
+            Ok(values) => values,
+            Err(e) => return Some(Err(e)),
+        };
+        if values.len() % 2 != 0 {
+            return Some(Err(ArrayRetrievalError::DataTypeSizeMismatch));
+        }
+        Some(Ok(values
+            .chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            .unzip()))
+    }
+
+    /// Keep only the points whose entry in `mask` is `true` in every array that has one
+    /// entry per point. Arrays of a different length than `mask` are left unchanged.
+    ///
+    /// # See also
+    /// [`DataArray::retain_by_mask`]
+    pub fn retain_points(&mut self, mask: &[bool]) -> Result<(), ArrayRetrievalError> {
+        for (_, array) in self.iter_mut() {
+            if array.data_len()? == mask.len() {
+                array.retain_by_mask(mask)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Clear the map, discarding any array data
     pub fn clear(&mut self) {
         self.byte_buffer_map.clear();
@@ -199,13 +274,18 @@ impl BinaryArrayMap {
         }
     }
 
-    /// Get a reference to the m/z array if it is present
+    /// Get a reference to the m/z array if it is present.
+    ///
+    /// If there is only an interleaved m/z and intensity array that hasn't been split with
+    /// [`BinaryArrayMap::deinterleave`], the m/z values are copied out of it.
     pub fn mzs(&'_ self) -> Result<Cow<'_, [f64]>, ArrayRetrievalError> {
-        let mz_array = self
-            .get(&ArrayType::MZArray)
-            .ok_or(ArrayRetrievalError::NotFound(ArrayType::MZArray))?
-            .to_f64()?;
-        Ok(mz_array)
+        match self.get(&ArrayType::MZArray) {
+            Some(mz_array) => mz_array.to_f64(),
+            None => match self.split_interleaved() {
+                Some(values) => Ok(Cow::Owned(values?.0)),
+                None => Err(ArrayRetrievalError::NotFound(ArrayType::MZArray)),
+            },
+        }
     }
 
     /// Get a mutable reference to the m/z array if it is present
@@ -219,13 +299,34 @@ impl BinaryArrayMap {
         }
     }
 
-    /// Get a reference to the intensity array if it is present
+    /// Get a reference to the intensity array if it is present.
+    ///
+    /// If there is only an interleaved m/z and intensity array that hasn't been split with
+    /// [`BinaryArrayMap::deinterleave`], the intensities are copied out of it.
     pub fn intensities(&'_ self) -> Result<Cow<'_, [f32]>, ArrayRetrievalError> {
-        let intensities = self
-            .get(&ArrayType::IntensityArray)
-            .ok_or(ArrayRetrievalError::NotFound(ArrayType::IntensityArray))?
-            .to_f32()?;
-        Ok(intensities)
+        match self.get(&ArrayType::IntensityArray) {
+            Some(intensities) => intensities.to_f32(),
+            None => match self.split_interleaved() {
+                Some(values) => Ok(Cow::Owned(
+                    values?.1.into_iter().map(|v| v as f32).collect(),
+                )),
+                None => Err(ArrayRetrievalError::NotFound(ArrayType::IntensityArray)),
+            },
+        }
+    }
+
+    /// Get a reference to the intensity array as `f64` if it is present.
+    ///
+    /// Unlike [`BinaryArrayMap::intensities`], an array stored as 64-bit floats is returned
+    /// without losing precision.
+    pub fn intensities_f64(&'_ self) -> Result<Cow<'_, [f64]>, ArrayRetrievalError> {
+        match self.get(&ArrayType::IntensityArray) {
+            Some(intensities) => intensities.to_f64(),
+            None => match self.split_interleaved() {
+                Some(values) => Ok(Cow::Owned(values?.1)),
+                None => Err(ArrayRetrievalError::NotFound(ArrayType::IntensityArray)),
+            },
+        }
     }
 
     /// Get a mutable reference to the intensity array if it is present
@@ -0,0 +1,344 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use mzpeaks::{
+    prelude::*, CentroidLike, DeconvolutedCentroidLike, DeconvolutedPeak, MZPeakSetType,
+    MassPeakSetType, Tolerance,
+};
+
+use crate::spectrum::bindata::{BuildArrayMapFrom, BuildFromArrayMap};
+use crate::spectrum::scan_properties::ScanPolarity;
+use crate::spectrum::spectrum_types::{
+    DeconvolutedSpectrum, MultiLayerSpectrum, SpectrumConversionError, SpectrumLike,
+};
+use crate::utils::PROTON;
+
+/// The average mass difference between successive isotopic peaks
+pub(crate) const ISOTOPIC_SPACING: f64 = 1.00286864;
+
+/// The mass of a sodium cation, for sodiated species
+pub const SODIUM: f64 = 22.98922070;
+
+/// A model of the isotopic pattern expected for a molecule of a given mass, used to
+/// decide which peaks belong to the same isotopic envelope during deconvolution.
+pub trait IsotopeModel: Debug + Send + Sync {
+    /// Produce the expected isotopic pattern of a molecule with monoisotopic `neutral_mass`
+    /// as `(mass shift from the monoisotopic peak, relative abundance)` pairs, ordered by
+    /// mass shift and starting with the monoisotopic peak at `0.0`.
+    fn isotopic_pattern(&self, neutral_mass: f64) -> Vec<(f64, f64)>;
+
+    /// The mass difference between the `k`th and `k + 1`th peaks of the isotopic envelope
+    /// of a molecule with monoisotopic `neutral_mass`, counting from the monoisotopic peak
+    /// at `k = 0`, or [`None`] if the envelope is not expected to extend past the `k`th peak.
+    ///
+    /// The default implementation reads the spacing off of [`IsotopeModel::isotopic_pattern`].
+    fn isotopic_spacing(&self, neutral_mass: f64, k: usize) -> Option<f64> {
+        let pattern = self.isotopic_pattern(neutral_mass);
+        let (shift, _) = pattern.get(k)?;
+        let (next_shift, _) = pattern.get(k + 1)?;
+        Some(next_shift - shift)
+    }
+}
+
+/// The isotopic pattern of a peptide of "average" amino acid composition (averagine),
+/// approximated by a Poisson distribution over the number of heavy isotopes.
+///
+/// Every step of the envelope uses the same average spacing and, during deconvolution, the
+/// envelope is not limited to the length of the pattern, so an isotopic series is followed
+/// for as long as peaks are found.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PeptideAveragine;
+
+impl PeptideAveragine {
+    /// The mass of one averagine unit, C4.9384 H7.7583 N1.3577 O1.4773 S0.0417
+    const UNIT_MASS: f64 = 111.1254;
+
+    /// The expected number of heavy isotopes in one averagine unit, from the natural
+    /// abundances of 13C, 2H, 15N, 17O and 33S
+    const UNIT_HEAVY_ISOTOPES: f64 = 4.9384 * 0.0107
+        + 7.7583 * 0.000115
+        + 1.3577 * 0.00364
+        + 1.4773 * 0.00038
+        + 0.0417 * 0.0075;
+
+    /// Isotopic peaks less abundant than this fraction of the most abundant peak are
+    /// left out of the pattern
+    const MIN_ABUNDANCE: f64 = 0.01;
+}
+
+impl IsotopeModel for PeptideAveragine {
+    fn isotopic_pattern(&self, neutral_mass: f64) -> Vec<(f64, f64)> {
+        let lambda = neutral_mass.max(0.0) / Self::UNIT_MASS * Self::UNIT_HEAVY_ISOTOPES;
+        let mut abundances = vec![(-lambda).exp()];
+        let mut apex = abundances[0];
+        let mut k = 1;
+        // Extend until past the mode of the distribution and below the abundance threshold
+        loop {
+            let next = abundances[k - 1] * lambda / k as f64;
+            if (k as f64) > lambda && next <= apex * Self::MIN_ABUNDANCE {
+                break;
+            }
+            apex = apex.max(next);
+            abundances.push(next);
+            k += 1;
+        }
+        let total: f64 = abundances.iter().sum();
+        abundances
+            .into_iter()
+            .enumerate()
+            .map(|(i, a)| (i as f64 * ISOTOPIC_SPACING, a / total))
+            .collect()
+    }
+
+    fn isotopic_spacing(&self, _neutral_mass: f64, _k: usize) -> Option<f64> {
+        Some(ISOTOPIC_SPACING)
+    }
+}
+
+/// Parameters controlling how a centroided peak list is deisotoped and charge state
+/// deconvolved by [`CentroidSpectrumType::deconvolute`](crate::spectrum::CentroidSpectrumType::deconvolute).
+#[derive(Debug, Clone)]
+pub struct DeconvolutionParams {
+    /// The inclusive range of absolute charge states to consider
+    pub charge_range: (i32, i32),
+    /// The mass of the charge carrier, e.g. [`PROTON`] or [`SODIUM`]
+    pub adduct_mass: f64,
+    /// Whether charges are gained (positive mode) or lost (negative mode). An
+    /// [`ScanPolarity::Unknown`] polarity is treated as positive.
+    pub polarity: ScanPolarity,
+    /// The mass accuracy used to match isotopic peaks
+    pub tolerance: Tolerance,
+    /// The expected isotopic pattern, which determines the spacing and maximum length of
+    /// an isotopic envelope. Defaults to [`PeptideAveragine`].
+    pub isotope_model: Arc<dyn IsotopeModel>,
+}
+
+impl Default for DeconvolutionParams {
+    fn default() -> Self {
+        Self {
+            charge_range: (1, 8),
+            adduct_mass: PROTON,
+            polarity: ScanPolarity::Positive,
+            tolerance: Tolerance::PPM(10.0),
+            isotope_model: Arc::new(PeptideAveragine),
+        }
+    }
+}
+
+impl DeconvolutionParams {
+    pub fn new(
+        charge_range: (i32, i32),
+        adduct_mass: f64,
+        polarity: ScanPolarity,
+        tolerance: Tolerance,
+    ) -> Self {
+        Self {
+            charge_range,
+            adduct_mass,
+            polarity,
+            tolerance,
+            ..Default::default()
+        }
+    }
+
+    /// Use `isotope_model` instead of [`PeptideAveragine`], e.g. for lipids or glycans
+    pub fn with_isotope_model(mut self, isotope_model: Arc<dyn IsotopeModel>) -> Self {
+        self.isotope_model = isotope_model;
+        self
+    }
+
+    /// Compute the neutral mass of an ion observed at `mz` with absolute charge `z`
+    pub fn neutral_mass(&self, mz: f64, z: i32) -> f64 {
+        let z = z.abs() as f64;
+        (mz - self.polarity.sign() as f64 * self.adduct_mass) * z
+    }
+
+    fn charges(&self) -> impl Iterator<Item = i32> {
+        let (low, high) = self.charge_range;
+        let (low, high) = (low.abs().max(1), high.abs().max(1));
+        low.min(high)..=low.max(high)
+    }
+}
+
+/// Walk along the isotopic series of the peak at `index` in one `direction`, collecting
+/// the indices of the unassigned peaks found until the series is interrupted or grows longer
+/// than `model` expects the envelope of a molecule of `neutral_mass` to be.
+///
+/// Walking upwards steps through the model's spacings in order. Walking downwards towards
+/// the monoisotopic peak always uses the first spacing.
+#[allow(clippy::too_many_arguments)]
+fn isotopic_series<C: CentroidLike>(
+    peaks: &MZPeakSetType<C>,
+    assigned: &[bool],
+    index: usize,
+    z: i32,
+    direction: f64,
+    model: &dyn IsotopeModel,
+    neutral_mass: f64,
+    tolerance: Tolerance,
+) -> Vec<usize> {
+    let mut found = Vec::new();
+    let first = match model.isotopic_spacing(neutral_mass, 0) {
+        Some(spacing) => spacing,
+        None => return found,
+    };
+    let mut mz = peaks[index].mz();
+    let mut k = 0;
+    while let Some(spacing) = model.isotopic_spacing(neutral_mass, k) {
+        let spacing = if direction > 0.0 { spacing } else { first };
+        mz += direction * spacing / z as f64;
+        match peaks.has_peak(mz, tolerance) {
+            Some(p) if !assigned[p.get_index() as usize] => {
+                found.push(p.get_index() as usize);
+                mz = p.mz();
+            }
+            _ => break,
+        }
+        k += 1;
+    }
+    found
+}
+
+/// Greedily deisotope and charge state deconvolve `peaks`, starting from the most intense
+/// peak and choosing the charge state from `params` that explains the longest isotopic
+/// series. The monoisotopic peak of each series determines the neutral mass and the
+/// intensities of the series are summed.
+///
+/// Peaks without any isotopic partner are assigned the lowest charge state in range.
+pub(crate) fn deconvolute_peaks<C: CentroidLike>(
+    peaks: &MZPeakSetType<C>,
+    params: &DeconvolutionParams,
+) -> MassPeakSetType<DeconvolutedPeak> {
+    let mut order: Vec<usize> = (0..peaks.len()).collect();
+    order.sort_by(|a, b| peaks[*b].intensity().total_cmp(&peaks[*a].intensity()));
+
+    let sign = params.polarity.sign();
+    let lowest_charge = params.charges().next().unwrap_or(1);
+    let mut assigned = vec![false; peaks.len()];
+    let mut result = Vec::new();
+
+    for i in order {
+        if assigned[i] {
+            continue;
+        }
+        assigned[i] = true;
+
+        let mut best: Option<(i32, Vec<usize>, Vec<usize>)> = None;
+        for z in params.charges() {
+            let mass = params.neutral_mass(peaks[i].mz(), z);
+            let (model, tol) = (params.isotope_model.as_ref(), params.tolerance);
+            let above = isotopic_series(peaks, &assigned, i, z, 1.0, model, mass, tol);
+            let below = isotopic_series(peaks, &assigned, i, z, -1.0, model, mass, tol);
+            let size = above.len() + below.len();
+            let improved = match best.as_ref() {
+                Some((_, a, b)) => size > a.len() + b.len(),
+                None => size > 0,
+            };
+            if improved {
+                best = Some((z, above, below));
+            }
+        }
+
+        let (z, members, monoisotopic) = match best {
+            Some((z, above, below)) => {
+                let monoisotopic = below.last().copied().unwrap_or(i);
+                let members: Vec<usize> = above.into_iter().chain(below).collect();
+                (z, members, monoisotopic)
+            }
+            None => (lowest_charge, Vec::new(), i),
+        };
+
+        let mut intensity = peaks[i].intensity();
+        for j in members {
+            assigned[j] = true;
+            intensity += peaks[j].intensity();
+        }
+
+        result.push(DeconvolutedPeak {
+            neutral_mass: params.neutral_mass(peaks[monoisotopic].mz(), z),
+            intensity,
+            charge: sign * z,
+            ..Default::default()
+        });
+    }
+
+    result.into_iter().collect()
+}
+
+/// An iterator adapter which deconvolves each spectrum produced by a [`SpectrumSource`](crate::io::traits::SpectrumSource)
+/// or any other iterator over [`MultiLayerSpectrum`] as it is read.
+///
+/// MS1 spectra are deconvolved with `ms1_params` and all other spectra with `msn_params`.
+/// Spectra which are not centroided yield a [`SpectrumConversionError`] rather than being
+/// silently dropped.
+pub struct DeconvolvingIter<C, D, I>
+where
+    C: CentroidLike + Default + BuildFromArrayMap + BuildArrayMapFrom,
+    D: DeconvolutedCentroidLike + Default + BuildFromArrayMap + BuildArrayMapFrom,
+    I: Iterator<Item = MultiLayerSpectrum<C, D>>,
+{
+    source: I,
+    pub ms1_params: DeconvolutionParams,
+    pub msn_params: DeconvolutionParams,
+}
+
+impl<C, D, I> DeconvolvingIter<C, D, I>
+where
+    C: CentroidLike + Default + BuildFromArrayMap + BuildArrayMapFrom,
+    D: DeconvolutedCentroidLike + Default + BuildFromArrayMap + BuildArrayMapFrom,
+    I: Iterator<Item = MultiLayerSpectrum<C, D>>,
+{
+    pub fn new(
+        source: I,
+        ms1_params: DeconvolutionParams,
+        msn_params: DeconvolutionParams,
+    ) -> Self {
+        Self {
+            source,
+            ms1_params,
+            msn_params,
+        }
+    }
+
+    /// Deconvolve every spectrum with the same `params`, regardless of MS level
+    pub fn with_params(source: I, params: DeconvolutionParams) -> Self {
+        Self::new(source, params.clone(), params)
+    }
+
+    /// Get the parameters used for spectra of `ms_level`
+    pub fn params_for(&self, ms_level: u8) -> &DeconvolutionParams {
+        if ms_level == 1 {
+            &self.ms1_params
+        } else {
+            &self.msn_params
+        }
+    }
+
+    /// Recover the wrapped iterator
+    pub fn into_inner(self) -> I {
+        self.source
+    }
+}
+
+impl<C, D, I> Iterator for DeconvolvingIter<C, D, I>
+where
+    C: CentroidLike + Default + BuildFromArrayMap + BuildArrayMapFrom,
+    D: DeconvolutedCentroidLike + Default + BuildFromArrayMap + BuildArrayMapFrom,
+    I: Iterator<Item = MultiLayerSpectrum<C, D>>,
+{
+    type Item = Result<DeconvolutedSpectrum, SpectrumConversionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let spectrum = self.source.next()?;
+        let params = self.params_for(spectrum.ms_level());
+        Some(
+            spectrum
+                .into_centroid()
+                .map(|centroid| centroid.deconvolute(params)),
+        )
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.source.size_hint()
+    }
+}
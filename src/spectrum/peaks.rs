@@ -576,6 +576,57 @@ impl SpectrumSummary {
     }
 }
 
+/// A builder for [`CentroidPeak`], checking in debug builds that the peak's m/z is not negative.
+///
+/// [`CentroidPeak`] is defined by `mzpeaks`, so this stands in for a validating constructor
+/// on the peak type itself.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CentroidPeakBuilder {
+    mz: f64,
+    intensity: f32,
+    index: IndexType,
+}
+
+impl CentroidPeakBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mz(mut self, mz: f64) -> Self {
+        self.mz = mz;
+        self
+    }
+
+    pub fn intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn index(mut self, index: IndexType) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Create the [`CentroidPeak`].
+    ///
+    /// # Panics
+    /// In debug builds, if the m/z is negative or NaN.
+    pub fn build(self) -> CentroidPeak {
+        debug_assert!(
+            self.mz >= 0.0,
+            "A centroid peak's m/z must not be negative, got {}",
+            self.mz
+        );
+        CentroidPeak::new(self.mz, self.intensity, self.index)
+    }
+}
+
+impl From<CentroidPeakBuilder> for CentroidPeak {
+    fn from(value: CentroidPeakBuilder) -> Self {
+        value.build()
+    }
+}
+
 impl<'a, C: CentroidLike, D: DeconvolutedCentroidLike> RefPeakDataLevel<'a, C, D> {
     /// Compute the base peak of a spectrum
     pub fn base_peak(&self) -> CentroidPeak {
@@ -1,13 +1,20 @@
 use std::borrow::Cow;
 use std::fmt::Display;
+use std::str::FromStr;
 
 use log::warn;
 use num_traits::Float;
+use thiserror::Error;
 
-use super::spectrum_types::{CentroidPeakAdapting, DeconvolutedPeakAdapting, SpectrumLike};
+use mzpeaks::{CentroidLike, MZPeakSetType};
+
+use super::spectrum_types::{
+    CentroidPeakAdapting, CentroidSpectrumType, DeconvolutedPeakAdapting, SpectrumLike,
+};
 use crate::io::traits::SpectrumSource;
 use crate::params::{
-    ControlledVocabulary, Param, ParamDescribed, ParamLike, ParamValue, Unit, ValueRef, CURIE,
+    ControlledVocabulary, Param, ParamDescribed, ParamLike, ParamValue, Unit, Value, ValueRef,
+    CURIE,
 };
 use crate::meta::DissociationMethodTerm;
 use crate::{curie, impl_param_described, ParamList};
@@ -50,6 +57,8 @@ pub struct IsolationWindow {
     /// Describes the decision making process used to establish the bounds of the
     /// window from the source file.
     pub flags: IsolationWindowState,
+    /// Any other parameters describing the isolation window
+    pub params: Option<Box<ParamList>>,
 }
 
 impl IsolationWindow {
@@ -64,6 +73,7 @@ impl IsolationWindow {
             lower_bound,
             upper_bound,
             flags,
+            params: None,
         }
     }
 
@@ -129,8 +139,19 @@ type ScanWindowList = Vec<ScanWindow>;
 /// Describes a single scan event. Unless additional post-processing is done,
 /// there is usually only one event per spectrum.
 pub struct ScanEvent {
+    /// The scan start time in minutes. `NaN` marks a start time that wasn't recorded,
+    /// see [`SpectrumLike::retention_time`](crate::spectrum::SpectrumLike::retention_time)
     pub start_time: f64,
-    pub injection_time: f32,
+    /// The time spent accumulating ions for this scan event in milliseconds, if reported
+    pub injection_time: Option<f32>,
+    /// The vendor's human-readable scan filter describing the analyzer, polarity, and
+    /// precursor of this scan event, like a Thermo filter string, if reported
+    pub filter_string: Option<String>,
+    /// The `id` of the [`SourceFile`](crate::meta::SourceFile) this scan came from, when a
+    /// spectrum was combined from scans of other files
+    pub source_file_ref: Option<String>,
+    /// The native ID of this scan within the file named by `source_file_ref`, if given
+    pub external_spectrum_id: Option<String>,
     pub scan_windows: ScanWindowList,
     pub instrument_configuration_id: u32,
     pub params: Option<Box<ParamList>>,
@@ -167,13 +188,12 @@ pub trait IonMobilityMeasure: ParamDescribed {
 
 pub(crate) const PRESET_SCAN_CONFIGURATION: CURIE = curie!(MS:1000616);
 pub(crate) const MASS_RESOLUTION: CURIE = curie!(MS:1000011);
-pub(crate) const FILTER_STRING: CURIE = curie!(MS:1000512);
 pub(crate) const SCAN_TITLE: CURIE = curie!(MS:1000499);
 
 impl ScanEvent {
     pub fn new(
         start_time: f64,
-        injection_time: f32,
+        injection_time: Option<f32>,
         scan_windows: ScanWindowList,
         instrument_configuration_id: u32,
         params: Option<Box<ParamList>>,
@@ -181,14 +201,28 @@ impl ScanEvent {
         Self {
             start_time,
             injection_time,
+            filter_string: None,
+            source_file_ref: None,
+            external_spectrum_id: None,
             scan_windows,
             instrument_configuration_id,
             params,
         }
     }
 
+    /// Whether the ion injection time was reported for this scan event
+    pub fn has_injection_time(&self) -> bool {
+        self.injection_time.is_some()
+    }
+
+    /// The vendor's scan filter string, if reported, e.g. `FTMS + p NSI Full ms [200.00-2000.00]`
+    pub fn scan_filter(&self) -> Option<&str> {
+        self.filter_string.as_deref()
+    }
+
+    /// The vendor's scan filter string, if reported. See [`ScanEvent::scan_filter`].
     pub fn filter_string(&self) -> Option<Cow<'_, str>> {
-        self.get_param_by_curie(&FILTER_STRING).map(|p| p.as_str())
+        self.scan_filter().map(Cow::Borrowed)
     }
 
     pub fn resolution(&self) -> Option<ValueRef> {
@@ -199,6 +233,26 @@ impl ScanEvent {
         self.get_param_by_curie(&PRESET_SCAN_CONFIGURATION)
             .map(|p| p.value())
     }
+
+    /// The preset scan configuration number of this scan event, which distinguishes the
+    /// scan types of the acquisition method in a scheduled run
+    pub fn preset_scan_configuration(&self) -> Option<i64> {
+        self.get_param_by_curie(&PRESET_SCAN_CONFIGURATION)
+            .and_then(|p| p.to_i64().ok())
+    }
+
+    /// Set the preset scan configuration number, replacing any already present
+    pub fn set_preset_scan_configuration(&mut self, configuration: i64) {
+        let params = self.params_mut();
+        if let Some(p) = params.iter_mut().find(|p| **p == PRESET_SCAN_CONFIGURATION) {
+            p.value = Value::Int(configuration);
+        } else {
+            let mut p = PRESET_SCAN_CONFIGURATION.as_param();
+            p.name = "preset scan configuration".to_string();
+            p.value = Value::Int(configuration);
+            params.push(p);
+        }
+    }
 }
 
 impl IonMobilityMeasure for ScanEvent {}
@@ -407,6 +461,15 @@ impl Activation {
         self._methods.len() > 1
     }
 
+    /// Get the supplemental collision energy of a combined activation like EThcD, if one
+    /// was given. Unlike the primary [`Activation::energy`], this is kept in [`Activation::params`].
+    pub fn supplemental_energy(&self) -> Option<f32> {
+        self.params
+            .iter()
+            .find(|p| p.curie() == Some(curie!(MS:1002680)))
+            .and_then(|p| p.value.to_f32().ok())
+    }
+
     /// Check if a [`ParamLike`] type references an activation method
     pub fn is_param_activation<P: ParamLike>(p: &P) -> bool {
         if p.is_controlled() && p.controlled_vocabulary().unwrap() == ControlledVocabulary::MS {
@@ -451,6 +514,30 @@ pub struct Precursor {
 }
 
 impl Precursor {
+    /// The m/z the instrument centered its isolation window on, if recorded.
+    ///
+    /// This is the m/z the acquisition software scheduled for isolation, often the most
+    /// abundant isotopic peak seen in the survey scan, and it is stored at single precision.
+    /// It is not necessarily the m/z of the ion that was identified as the precursor, see
+    /// [`Precursor::selected_ion_mz`] for that.
+    pub fn isolation_target_mz(&self) -> Option<f64> {
+        let target = self.isolation_window.target;
+        if target > 0.0 {
+            Some(target as f64)
+        } else {
+            None
+        }
+    }
+
+    /// The m/z of the first selected ion, if there is one.
+    ///
+    /// This is the m/z reported for the precursor ion itself, which may have been corrected to
+    /// the monoisotopic peak after acquisition and so can lie away from the center of the
+    /// isolation window, see [`Precursor::isolation_target_mz`].
+    pub fn selected_ion_mz(&self) -> Option<f64> {
+        self.ions.first().map(|ion| ion.mz)
+    }
+
     /// Given a SpectrumSource object, look up the precursor scan in it.
     /// This is useful when examining the area *around* where the precursor
     /// ion was or to obtain a snapshot of the retention time when the spectrum
@@ -625,7 +712,36 @@ impl ScanPolarity {
 
 impl Display for ScanPolarity {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        let name = match self {
+            ScanPolarity::Unknown => "unknown",
+            ScanPolarity::Positive => "positive",
+            ScanPolarity::Negative => "negative",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Errors that may arise when parsing a scan property like [`ScanPolarity`] or
+/// [`SignalContinuity`] from a string
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ScanPropertyParseError {
+    #[error("{0:?} is not a recognized scan polarity")]
+    UnknownPolarity(String),
+    #[error("{0:?} is not a recognized signal continuity")]
+    UnknownSignalContinuity(String),
+}
+
+impl FromStr for ScanPolarity {
+    type Err = ScanPropertyParseError;
+
+    /// Parse a polarity from its name, ignoring case, or from a `+` or `-` sign
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "positive" | "pos" | "+" => Ok(Self::Positive),
+            "negative" | "neg" | "-" => Ok(Self::Negative),
+            "unknown" => Ok(Self::Unknown),
+            _ => Err(ScanPropertyParseError::UnknownPolarity(s.to_string())),
+        }
     }
 }
 
@@ -651,7 +767,29 @@ pub enum SignalContinuity {
 
 impl Display for SignalContinuity {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        let name = match self {
+            SignalContinuity::Unknown => "unknown",
+            SignalContinuity::Centroid => "centroid",
+            SignalContinuity::Profile => "profile",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for SignalContinuity {
+    type Err = ScanPropertyParseError;
+
+    /// Parse a signal continuity from its name, ignoring case. `centroided` and
+    /// `continuous` are accepted as synonyms.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "centroid" | "centroided" => Ok(Self::Centroid),
+            "profile" | "continuous" => Ok(Self::Profile),
+            "unknown" => Ok(Self::Unknown),
+            _ => Err(ScanPropertyParseError::UnknownSignalContinuity(
+                s.to_string(),
+            )),
+        }
     }
 }
 
@@ -684,6 +822,10 @@ pub struct SpectrumDescription {
     pub acquisition: Acquisition,
     /// The parent ion or ions and their isolation and activation description
     pub precursor: Option<Precursor>,
+
+    /// The identifier of the [`DataProcessing`](crate::meta::DataProcessing) workflow
+    /// applied to this spectrum, if it was given explicitly
+    pub data_processing_id: Option<String>,
 }
 
 impl SpectrumDescription {
@@ -707,16 +849,39 @@ impl SpectrumDescription {
             params,
             acquisition,
             precursor,
+            data_processing_id: None,
         }
     }
 
     pub fn title(&self) -> Option<Cow<'_, str>> {
         self.get_param_by_curie(&SCAN_TITLE).map(|p| p.as_str())
     }
+
+    /// Pair this description with a set of centroid peaks, moving it into a
+    /// [`CentroidSpectrumType`] rather than copying it.
+    pub fn with_peaks<C: CentroidLike + Default>(
+        mut self,
+        peaks: impl Into<MZPeakSetType<C>>,
+    ) -> CentroidSpectrumType<C> {
+        self.signal_continuity = SignalContinuity::Centroid;
+        CentroidSpectrumType::new(self, peaks.into())
+    }
+}
+
+/// mzML has no place for parameters directly on `<precursor>`, so the parameters
+/// of a precursor are those of its [`IsolationWindow`].
+impl ParamDescribed for Precursor {
+    fn params(&self) -> &[Param] {
+        self.isolation_window.params()
+    }
+
+    fn params_mut(&mut self) -> &mut ParamList {
+        self.isolation_window.params_mut()
+    }
 }
 
 impl_param_described!(Activation, SpectrumDescription);
-impl_param_described_deferred!(SelectedIon, Acquisition, ScanEvent);
+impl_param_described_deferred!(IsolationWindow, SelectedIon, Acquisition, ScanEvent);
 
 /// Types of chromatograms enumerated in the PSI-MS controlled vocabulary
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
@@ -829,3 +994,91 @@ impl ChromatogramDescription {
 }
 
 impl_param_described!(ChromatogramDescription);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn count_params(described: &dyn ParamDescribed) -> usize {
+        described.params().len()
+    }
+
+    #[test]
+    fn test_scan_property_strings() {
+        for polarity in [
+            ScanPolarity::Unknown,
+            ScanPolarity::Positive,
+            ScanPolarity::Negative,
+        ] {
+            assert_eq!(polarity.to_string().parse::<ScanPolarity>(), Ok(polarity));
+        }
+        assert_eq!(ScanPolarity::Positive.to_string(), "positive");
+        assert_eq!(
+            "Negative".parse::<ScanPolarity>(),
+            Ok(ScanPolarity::Negative)
+        );
+        assert_eq!("+".parse::<ScanPolarity>(), Ok(ScanPolarity::Positive));
+        assert!("sideways".parse::<ScanPolarity>().is_err());
+
+        for continuity in [
+            SignalContinuity::Unknown,
+            SignalContinuity::Centroid,
+            SignalContinuity::Profile,
+        ] {
+            assert_eq!(
+                continuity.to_string().parse::<SignalContinuity>(),
+                Ok(continuity)
+            );
+        }
+        assert_eq!(SignalContinuity::Profile.to_string(), "profile");
+        assert_eq!(
+            "CENTROID".parse::<SignalContinuity>(),
+            Ok(SignalContinuity::Centroid)
+        );
+        assert_eq!(
+            "smooth".parse::<SignalContinuity>(),
+            Err(ScanPropertyParseError::UnknownSignalContinuity(
+                "smooth".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_isolation_target_and_selected_ion_mz() {
+        let mut precursor = Precursor::default();
+        assert_eq!(precursor.isolation_target_mz(), None);
+        assert_eq!(precursor.selected_ion_mz(), None);
+
+        precursor.isolation_window = IsolationWindow::around(810.79, 1.0);
+        precursor.add_ion(SelectedIon {
+            mz: 810.2875,
+            charge: Some(2),
+            ..Default::default()
+        });
+        let target = precursor.isolation_target_mz().unwrap();
+        assert!((target - 810.79).abs() < 1e-4);
+        assert_eq!(precursor.selected_ion_mz(), Some(810.2875));
+    }
+
+    #[test]
+    fn test_param_described_dyn() {
+        let mut description = SpectrumDescription::default();
+        description.add_param(Param::new_key_value("filter", "FTMS + p NSI Full ms"));
+        assert_eq!(count_params(&description), 1);
+
+        let mut precursor = Precursor::default();
+        assert_eq!(count_params(&precursor), 0);
+        precursor.add_param(Param::new_key_value("monoisotopic correction", "true"));
+
+        let mut event = ScanEvent::default();
+        assert_eq!(count_params(&event), 0);
+        event.add_param(Param::new_key_value("scan rate", "normal"));
+
+        let described: [&dyn ParamDescribed; 3] = [&description, &precursor, &event];
+        let total: usize = described.iter().map(|d| count_params(*d)).sum();
+        assert_eq!(total, 3);
+        assert!(described
+            .iter()
+            .all(|d| d.get_param_by_name("missing").is_none()));
+    }
+}
@@ -1,4 +1,5 @@
 use std::convert::TryFrom;
+use std::iter::FromIterator;
 use std::{borrow::Cow, ops::Index};
 
 use mzpeaks::Mass;
@@ -6,7 +7,7 @@ use thiserror::Error;
 
 use mzpeaks::{
     peak_set::PeakSetVec, prelude::*, CentroidLike, CentroidPeak, DeconvolutedCentroidLike,
-    DeconvolutedPeak, MZPeakSetType, MassPeakSetType, PeakCollection, PeakSet, MZ,
+    DeconvolutedPeak, MZPeakSetType, MassPeakSetType, PeakCollection, PeakSet, Tolerance, MZ,
 };
 
 #[cfg(feature = "mzsignal")]
@@ -17,13 +18,17 @@ use mzsignal::{
     FittedPeak,
 };
 
-use crate::params::{ParamDescribed, ParamList, Unit, Value};
+use crate::params::{
+    ControlledVocabulary, Param, ParamDescribed, ParamList, ParamValue, Unit, Value, CURIE,
+};
 #[allow(unused)]
 use crate::spectrum::bindata::{ArrayType, BinaryArrayMap, BinaryDataArrayType};
+use crate::spectrum::deconvolution::{deconvolute_peaks, DeconvolutionParams, ISOTOPIC_SPACING};
 use crate::spectrum::peaks::{PeakDataLevel, RefPeakDataLevel, SpectrumSummary};
 use crate::spectrum::scan_properties::{
     Acquisition, IonMobilityMeasure, Precursor, ScanPolarity, SignalContinuity, SpectrumDescription,
 };
+use crate::utils::{mass_charge_ratio, neutral_mass};
 
 use super::bindata::{ArrayRetrievalError, ArraysAvailable, BuildArrayMapFrom, BuildFromArrayMap};
 #[allow(unused)]
@@ -96,13 +101,34 @@ pub trait SpectrumLike<
     }
 
     /// A shortcut method to retrieve the scan start time of a spectrum
+    ///
+    /// If the spectrum has no scan events, e.g. when an MGF file omits `RTINSECONDS`,
+    /// this is `0.0`. Use [`SpectrumLike::retention_time`] to tell that apart from a
+    /// spectrum acquired at the start of the run.
     #[inline]
     fn start_time(&self) -> f64 {
-        let acq = self.acquisition();
-        match acq.scans.first() {
-            Some(evt) => evt.start_time,
-            None => 0.0,
-        }
+        self.retention_time().unwrap_or_default()
+    }
+
+    /// Retrieve the scan start time of a spectrum in minutes, or [`None`] if it is unknown
+    /// because the spectrum has no scan events or its start time is `NaN`
+    #[inline]
+    fn retention_time(&self) -> Option<f64> {
+        self.acquisition()
+            .scans
+            .first()
+            .map(|evt| evt.start_time)
+            .filter(|t| !t.is_nan())
+    }
+
+    /// A shortcut method to retrieve the ion injection time of the first scan event
+    /// of a spectrum in milliseconds, if it was reported
+    #[inline]
+    fn injection_time(&self) -> Option<f32> {
+        self.acquisition()
+            .scans
+            .first()
+            .and_then(|evt| evt.injection_time)
     }
 
     /// Access the MS exponentiation level
@@ -117,7 +143,10 @@ pub trait SpectrumLike<
         &self.description().id
     }
 
-    /// Access the index of the spectrum in the source file
+    /// Access the zero-based index of the spectrum in the order it was acquired in the source file.
+    ///
+    /// Unlike [`SpectrumLike::id`], which is whatever native identifier the source format uses,
+    /// this is the spectrum's ordinal position, which some tools refer to as the "scan number".
     #[inline]
     fn index(&self) -> usize {
         self.description().index
@@ -155,10 +184,74 @@ pub trait SpectrumLike<
         self.ion_mobility().is_some()
     }
 
+    /// Check if this spectrum was acquired to calibrate the instrument rather than from
+    /// the sample, such as a Waters lock mass scan.
+    ///
+    /// This looks for the `calibration spectrum` term, or a `scan description`, `spectrum title`
+    /// or `function description` parameter mentioning a lock mass or calibration scan, among
+    /// the spectrum and scan event parameters. Formats that only mark these scans some other
+    /// way, like by the `function=` number of the lock mass function in a Waters native ID,
+    /// which depends on the acquisition method, can be handled with
+    /// [`SpectrumLike::is_calibration_by`] and a rule like [`waters_lock_mass_function`].
+    fn is_calibration(&self) -> bool {
+        self.params().iter().any(is_calibration_marker)
+            || self
+                .acquisition()
+                .iter()
+                .filter_map(|evt| evt.params.as_deref())
+                .flatten()
+                .any(is_calibration_marker)
+    }
+
+    /// Check if this spectrum is a calibration scan according to [`SpectrumLike::is_calibration`]
+    /// or the custom `rule`, e.g. one matching the lock mass function of a particular
+    /// instrument's native IDs
+    fn is_calibration_by<F: Fn(&Self) -> bool>(&self, rule: F) -> bool
+    where
+        Self: Sized,
+    {
+        self.is_calibration() || rule(self)
+    }
+
+    /// Check if this spectrum's intensities have already been normalized, e.g. by
+    /// [`RawSpectrum::normalize_tic`], so that a processing pipeline does not
+    /// normalize them twice.
+    ///
+    /// This is recorded as a user parameter on the spectrum description, so it is
+    /// preserved when the spectrum is written and read back.
+    fn is_normalized(&self) -> bool {
+        self.params()
+            .iter()
+            .any(|p| p.name == NORMALIZED_INTENSITIES && p.accession.is_none())
+    }
+
     /// Retrieve the most processed representation of the mass spectrum's
     /// signal
     fn peaks(&'_ self) -> RefPeakDataLevel<'_, C, D>;
 
+    /// The number of peaks, or data points for profile spectra, in the most processed
+    /// representation of the signal
+    fn peak_count(&self) -> usize {
+        self.peaks().len()
+    }
+
+    /// Check if the most processed representation of the signal has no peaks or data
+    /// points, as when a scan recorded no signal or its peak data is missing
+    fn is_empty(&self) -> bool {
+        self.peaks().is_empty()
+    }
+
+    /// The minimum and maximum m/z observed in the most processed representation of the
+    /// signal, or [`None`] if there are no peaks
+    fn mz_range(&self) -> Option<(f64, f64)> {
+        let peaks = self.peaks();
+        if peaks.is_empty() {
+            None
+        } else {
+            Some(peaks.mz_range())
+        }
+    }
+
     fn into_peaks_and_description(self) -> (PeakDataLevel<C, D>, SpectrumDescription);
 
     fn raw_arrays(&'_ self) -> Option<&'_ BinaryArrayMap>;
@@ -310,6 +403,100 @@ pub enum SpectrumProcessingError {
     ),
 }
 
+/// The PSI-MS term for a spectrum derived from a calibration source rather than the sample
+const CALIBRATION_SPECTRUM: CURIE = ControlledVocabulary::MS.curie(1000928);
+
+/// The names of free-text parameters that may describe the kind of scan
+const SCAN_DESCRIPTION_NAMES: [&str; 3] =
+    ["scan description", "spectrum title", "function description"];
+
+/// The words marking a calibration scan in a free-text scan description
+const CALIBRATION_MARKERS: [&str; 4] = ["lockmass", "lock mass", "lockspray", "calibration"];
+
+/// Check if `param` marks a calibration scan, either with the `calibration spectrum` term or
+/// in a free-text scan description
+fn is_calibration_marker(param: &Param) -> bool {
+    if param.curie() == Some(CALIBRATION_SPECTRUM) {
+        return true;
+    }
+    if !SCAN_DESCRIPTION_NAMES.contains(&param.name.as_str()) {
+        return false;
+    }
+    let text = param.value.as_str().to_lowercase();
+    CALIBRATION_MARKERS
+        .iter()
+        .any(|marker| text.contains(marker))
+}
+
+/// A rule for [`SpectrumLike::is_calibration_by`] which marks the scans of the Waters lock mass
+/// `function`, read from native IDs like `function=3 process=0 scan=12`.
+///
+/// Waters instruments acquire the lock mass reference in its own function, usually the last
+/// one, but which function that is depends on the acquisition method.
+///
+/// ```
+/// use mzdata::prelude::*;
+/// use mzdata::spectrum::{waters_lock_mass_function, CentroidSpectrum};
+///
+/// let mut spectrum = CentroidSpectrum::default();
+/// spectrum.description.id = "function=3 process=0 scan=12".to_string();
+/// assert!(spectrum.is_calibration_by(waters_lock_mass_function(3)));
+/// assert!(!spectrum.is_calibration_by(waters_lock_mass_function(2)));
+/// ```
+pub fn waters_lock_mass_function<C, D, S>(function: u32) -> impl Fn(&S) -> bool
+where
+    C: CentroidLike,
+    D: DeconvolutedCentroidLike,
+    S: SpectrumLike<C, D>,
+{
+    move |spectrum: &S| {
+        spectrum
+            .id()
+            .split_whitespace()
+            .filter_map(|token| token.strip_prefix("function="))
+            .any(|value| value.parse::<u32>() == Ok(function))
+    }
+}
+
+/// The name of the user parameter recording how a spectrum's intensities were normalized
+const NORMALIZED_INTENSITIES: &str = "normalized intensities";
+
+/// Record that the intensities described by `params` were normalized by `method`
+fn mark_normalized(params: &mut ParamList, method: &str) {
+    if let Some(p) = params
+        .iter_mut()
+        .find(|p| p.name == NORMALIZED_INTENSITIES && p.accession.is_none())
+    {
+        p.value = Value::String(method.to_string());
+    } else {
+        params.push(Param::new_key_value(NORMALIZED_INTENSITIES, method));
+    }
+}
+
+/// Scale `intensities` so that they sum to one, leaving them unchanged if they sum to zero
+fn normalize_by_tic<'a>(intensities: impl Iterator<Item = &'a mut f32>) {
+    let intensities: Vec<&mut f32> = intensities.collect();
+    let total: f64 = intensities.iter().map(|i| **i as f64).sum();
+    if total > 0.0 {
+        for i in intensities {
+            *i = (*i as f64 / total) as f32;
+        }
+    }
+}
+
+/// Compute the `q`th quantile of `values` by linear interpolation between the closest ranks.
+fn intensity_quantile_of(mut values: Vec<f32>, q: f64) -> Option<f32> {
+    if values.is_empty() || !(0.0..=1.0).contains(&q) {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let rank = q * (values.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let frac = (rank - lower as f64) as f32;
+    Some(values[lower] + (values[upper] - values[lower]) * frac)
+}
+
 impl<'transient, 'lifespan: 'transient> RawSpectrum {
     pub fn new(description: SpectrumDescription, arrays: BinaryArrayMap) -> Self {
         Self {
@@ -318,6 +505,31 @@ impl<'transient, 'lifespan: 'transient> RawSpectrum {
         }
     }
 
+    /// Get the centroids stored alongside the profile arrays of this spectrum, if any.
+    ///
+    /// Some files carry a second m/z and intensity array pair from the instrument vendor's
+    /// own peak picking. These are kept as the non-standard arrays [`ArrayType::CENTROID_MZ_ARRAY`]
+    /// and [`ArrayType::CENTROID_INTENSITY_ARRAY`]. Returns [`None`] if either is missing or
+    /// can't be decoded.
+    pub fn centroids(&self) -> Option<CentroidSpectrum> {
+        let mut arrays = BinaryArrayMap::new();
+        for (name, array_type) in [
+            (ArrayType::CENTROID_MZ_ARRAY, ArrayType::MZArray),
+            (
+                ArrayType::CENTROID_INTENSITY_ARRAY,
+                ArrayType::IntensityArray,
+            ),
+        ] {
+            let mut array = self.arrays.get(&ArrayType::nonstandard(name))?.clone();
+            array.name = array_type;
+            arrays.add(array);
+        }
+        let peaks = CentroidPeak::try_from_arrays(&arrays).ok()?;
+        let mut centroid = CentroidSpectrum::new(self.description.clone(), peaks.into());
+        centroid.description.signal_continuity = SignalContinuity::Centroid;
+        Some(centroid)
+    }
+
     /// Convert a spectrum into a [`CentroidSpectrumType`].
     ///
     /// # Errors
@@ -343,10 +555,7 @@ impl<'transient, 'lifespan: 'transient> RawSpectrum {
             Ok(peaks) => peaks.into(),
             Err(e) => return Err(e.into()),
         };
-        let mut centroid = CentroidSpectrumType::<C> {
-            description: self.description,
-            peaks,
-        };
+        let mut centroid = CentroidSpectrumType::<C>::new(self.description, peaks);
         centroid.description.signal_continuity = SignalContinuity::Centroid;
 
         Ok(centroid)
@@ -370,6 +579,154 @@ impl<'transient, 'lifespan: 'transient> RawSpectrum {
         self.arrays.intensities().unwrap()
     }
 
+    /// Linearly interpolate the intensity of a profile spectrum at each m/z in `axis`, so that
+    /// spectra from different scans can be compared point by point. Points outside the
+    /// spectrum's m/z range are zero.
+    ///
+    /// # Panics
+    /// This function will panic if [`ArrayType::MZArray`] or [`ArrayType::IntensityArray`]
+    /// are not present in [`RawSpectrum::arrays`]
+    pub fn resample(&self, axis: &[f64]) -> Vec<f32> {
+        let mzs = self.mzs();
+        let intensities = self.intensities();
+        let (first, last) = match (mzs.first(), mzs.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return vec![0.0; axis.len()],
+        };
+        axis.iter()
+            .map(|&mz| {
+                if mz < first || mz > last {
+                    return 0.0;
+                }
+                let i = mzs.partition_point(|x| *x < mz);
+                if mzs[i] == mz || i == 0 {
+                    return intensities[i];
+                }
+                let (x0, x1) = (mzs[i - 1], mzs[i]);
+                let (y0, y1) = (intensities[i - 1], intensities[i]);
+                let t = ((mz - x0) / (x1 - x0)) as f32;
+                y0 + (y1 - y0) * t
+            })
+            .collect()
+    }
+
+    /// Compute the mean difference between consecutive m/z samples, a measure of the
+    /// instrument's sampling density for choosing resampling grids or smoothing windows.
+    ///
+    /// Returns [`None`] if there are fewer than two samples or the m/z array is missing.
+    pub fn mean_mz_spacing(&self) -> Option<f64> {
+        let mzs = self.arrays.mzs().ok()?;
+        if mzs.len() < 2 {
+            return None;
+        }
+        // The consecutive differences telescope to the span of the array
+        Some((mzs[mzs.len() - 1] - mzs[0]) / (mzs.len() - 1) as f64)
+    }
+
+    /// Reduce the signal to about `target_points` `(m/z, intensity)` pairs for drawing, e.g.
+    /// to plot a large profile spectrum.
+    ///
+    /// The points are split into `target_points / 2` buckets of consecutive points, and the
+    /// least and most intense point of each bucket are kept in m/z order, so narrow peaks
+    /// and the base peak survive. If there are no more than `target_points` points, all of
+    /// them are returned.
+    pub fn downsample_for_display(&self, target_points: usize) -> Vec<(f64, f32)> {
+        let (mzs, intensities) = match (self.arrays.mzs(), self.arrays.intensities()) {
+            (Ok(mzs), Ok(intensities)) => (mzs, intensities),
+            _ => return Vec::new(),
+        };
+        let n = mzs.len().min(intensities.len());
+        if n <= target_points {
+            return mzs
+                .iter()
+                .copied()
+                .zip(intensities.iter().copied())
+                .take(n)
+                .collect();
+        }
+        let n_buckets = (target_points / 2).max(1);
+        let mut points = Vec::with_capacity(n_buckets * 2);
+        for bucket in 0..n_buckets {
+            let start = bucket * n / n_buckets;
+            let end = (bucket + 1) * n / n_buckets;
+            if start == end {
+                continue;
+            }
+            let (mut lowest, mut highest) = (start, start);
+            for (i, inten) in intensities[start..end].iter().enumerate() {
+                if *inten < intensities[lowest] {
+                    lowest = start + i;
+                }
+                if *inten > intensities[highest] {
+                    highest = start + i;
+                }
+            }
+            let (first, second) = if lowest <= highest {
+                (lowest, highest)
+            } else {
+                (highest, lowest)
+            };
+            points.push((mzs[first], intensities[first]));
+            if second != first {
+                points.push((mzs[second], intensities[second]));
+            }
+        }
+        points
+    }
+
+    /// Borrow the m/z array without copying it.
+    ///
+    /// Unlike [`RawSpectrum::mzs`], this never allocates. It fails if the array is missing,
+    /// has not been decoded yet, is not stored as 64-bit floats, or is not aligned for `f64`.
+    /// [`RawSpectrum::mzs_mut`] will put the array into a form this can borrow.
+    pub fn mz_slice(&self) -> Result<&[f64], ArrayRetrievalError> {
+        self.arrays
+            .get(&ArrayType::MZArray)
+            .ok_or(ArrayRetrievalError::NotFound(ArrayType::MZArray))?
+            .as_f64_slice()
+    }
+
+    /// Borrow the intensity array without copying it.
+    ///
+    /// Unlike [`RawSpectrum::intensities`], this never allocates. It fails if the array is
+    /// missing, has not been decoded yet, is not stored as 32-bit floats, or is not aligned
+    /// for `f32`. [`RawSpectrum::intensities_mut`] will put the array into a form this can borrow.
+    pub fn intensity_slice(&self) -> Result<&[f32], ArrayRetrievalError> {
+        self.arrays
+            .get(&ArrayType::IntensityArray)
+            .ok_or(ArrayRetrievalError::NotFound(ArrayType::IntensityArray))?
+            .as_f32_slice()
+    }
+
+    /// Compute the `q`th quantile of the intensity array, e.g. the median when `q` is `0.5`,
+    /// interpolating linearly between points.
+    ///
+    /// Returns [`None`] if the intensity array is missing or empty, or if `q` is not in `[0, 1]`.
+    pub fn intensity_quantile(&self, q: f64) -> Option<f32> {
+        let intensities = self.arrays.intensities().ok()?;
+        intensity_quantile_of(intensities.into_owned(), q)
+    }
+
+    /// Drop the points whose intensity is zero from every array of the spectrum.
+    ///
+    /// When `keep_flanking` is `true`, zero-intensity points immediately next to a non-zero
+    /// point are kept so that profile peaks retain their shape.
+    pub fn remove_zero_intensity(
+        &mut self,
+        keep_flanking: bool,
+    ) -> Result<(), ArrayRetrievalError> {
+        let mask: Vec<bool> = {
+            let intensities = self.arrays.intensities()?;
+            let nonzero = |i: usize| intensities.get(i).map(|v| *v != 0.0).unwrap_or(false);
+            (0..intensities.len())
+                .map(|i| {
+                    nonzero(i) || (keep_flanking && ((i > 0 && nonzero(i - 1)) || nonzero(i + 1)))
+                })
+                .collect()
+        };
+        self.arrays.retain_points(&mask)
+    }
+
     pub fn mzs_mut(&mut self) -> Result<&mut [f64], ArrayRetrievalError> {
         self.arrays.mzs_mut()
     }
@@ -378,6 +735,17 @@ impl<'transient, 'lifespan: 'transient> RawSpectrum {
         self.arrays.intensities_mut()
     }
 
+    /// Scale the intensity array so that it sums to one, and mark the spectrum
+    /// as normalized.
+    ///
+    /// # See also
+    /// [`SpectrumLike::is_normalized`]
+    pub fn normalize_tic(&mut self) -> Result<(), ArrayRetrievalError> {
+        normalize_by_tic(self.arrays.intensities_mut()?.iter_mut());
+        mark_normalized(&mut self.description.params, "total ion current");
+        Ok(())
+    }
+
     /// Explicitly decode any [`DataArray`] that is encoded or compressed still
     /// so that they are ready for use.
     ///
@@ -515,7 +883,32 @@ pub struct CentroidSpectrumType<C: CentroidLike + Default> {
     /// The spectrum metadata describing acquisition conditions and details.
     pub description: SpectrumDescription,
     /// The picked centroid peaks, sorted by m/z in a fast searchable structure.
+    ///
+    /// Peak intensities are stored as `f32`, so 64-bit intensities read from a file lose
+    /// precision when converted into peaks. Keep the [`RawSpectrum`] arrays and use
+    /// [`BinaryArrayMap::intensities_f64`] to retain them.
+    ///
+    /// Annotations refer to these peaks by index, so adding or removing peaks directly
+    /// leaves them labelling the wrong peaks. See [`CentroidSpectrumType::annotate`].
     pub peaks: MZPeakSetType<C>,
+    /// Labels attached to individual peaks, ordered by peak index.
+    /// See [`CentroidSpectrumType::annotate`].
+    annotations: Vec<PeakAnnotation>,
+}
+
+/// A label attached to the peak at `index` of a [`CentroidSpectrumType`], such as the
+/// name of a matched fragment ion, for visualization tools to draw
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PeakAnnotation {
+    /// The index of the annotated peak in m/z order
+    pub index: usize,
+    pub label: String,
+}
+
+impl PeakAnnotation {
+    pub fn new(index: usize, label: String) -> Self {
+        Self { index, label }
+    }
 }
 
 #[cfg(feature = "mzsignal")]
@@ -575,7 +968,331 @@ impl<C: CentroidLike + Default> SpectrumLike<C> for CentroidSpectrumType<C> {
 
 impl<C: CentroidLike + Default> CentroidSpectrumType<C> {
     pub fn new(description: SpectrumDescription, peaks: MZPeakSetType<C>) -> Self {
-        Self { description, peaks }
+        Self {
+            description,
+            peaks,
+            annotations: Vec::new(),
+        }
+    }
+
+    /// Check if the spectrum has no peaks.
+    ///
+    /// This is the same as [`SpectrumLike::is_empty`] and takes precedence over
+    /// [`PeakCollection::is_empty`] so the call isn't ambiguous.
+    pub fn is_empty(&self) -> bool {
+        self.peaks.peaks.is_empty()
+    }
+
+    /// Get mutable access to the peak list to edit it in place.
+    ///
+    /// Peaks may be added, removed, or have their intensities changed freely, but if
+    /// any m/z values are changed, call [`CentroidSpectrumType::resort`] afterwards or
+    /// searching the peaks will give wrong answers.
+    ///
+    /// Adding or removing peaks invalidates the spectrum's annotations, which refer to
+    /// peaks by index. Neither [`CentroidSpectrumType::resort`] nor anything else can
+    /// repair them afterwards, so clear them with [`CentroidSpectrumType::clear_annotations`]
+    /// and annotate the spectrum again.
+    pub fn peaks_mut(&mut self) -> &mut MZPeakSetType<C> {
+        &mut self.peaks
+    }
+
+    /// Restore the m/z ordering of the peaks and their indices after editing them through
+    /// [`CentroidSpectrumType::peaks_mut`]. Annotations move with the peaks they label, so long
+    /// as no peaks were added or removed.
+    pub fn resort(&mut self) {
+        let mut peaks: Vec<(usize, C)> = std::mem::take(&mut self.peaks.peaks)
+            .into_iter()
+            .enumerate()
+            .collect();
+        peaks.sort_by(|(_, a), (_, b)| a.mz().total_cmp(&b.mz()));
+
+        let mut new_positions = vec![0; peaks.len()];
+        for (new_position, (old_position, _)) in peaks.iter().enumerate() {
+            new_positions[*old_position] = new_position;
+        }
+        self.peaks = MZPeakSetType::new(peaks.into_iter().map(|(_, p)| p).collect());
+
+        self.annotations.retain(|a| a.index < new_positions.len());
+        for annotation in self.annotations.iter_mut() {
+            annotation.index = new_positions[annotation.index];
+        }
+        self.annotations.sort_by_key(|a| a.index);
+    }
+
+    /// Attach labels to peaks, given as `(peak index, label)` pairs. A peak may carry
+    /// more than one label. Pairs whose index is past the last peak are ignored.
+    ///
+    /// Annotations refer to peaks by index, so they are not carried over by operations that
+    /// build a new peak list like [`CentroidSpectrumType::map_peaks`] or conversion to
+    /// another spectrum type. Adding or removing peaks through the `peaks` field or
+    /// [`CentroidSpectrumType::peaks_mut`] invalidates them.
+    pub fn annotate(&mut self, annotations: Vec<(usize, String)>) {
+        let n = self.peaks.len();
+        self.annotations.extend(
+            annotations
+                .into_iter()
+                .filter(|(index, _)| *index < n)
+                .map(|(index, label)| PeakAnnotation::new(index, label)),
+        );
+        self.annotations.sort_by_key(|a| a.index);
+    }
+
+    /// Iterate over the labels attached to the peak at `index`
+    pub fn annotations_of(&self, index: usize) -> impl Iterator<Item = &str> {
+        let start = self.annotations.partition_point(|a| a.index < index);
+        self.annotations[start..]
+            .iter()
+            .take_while(move |a| a.index == index)
+            .map(|a| a.label.as_str())
+    }
+
+    /// Iterate over each annotated peak together with one of its labels, skipping
+    /// annotations whose peak no longer exists
+    pub fn annotated_peaks(&self) -> impl Iterator<Item = (&C, &str)> {
+        self.annotations
+            .iter()
+            .filter_map(move |a| Some((self.peaks.peaks.get(a.index)?, a.label.as_str())))
+    }
+
+    /// Remove all annotations from the spectrum
+    pub fn clear_annotations(&mut self) {
+        self.annotations.clear();
+    }
+
+    /// Get the `index`th peak in m/z order, or [`None`] if `index` is out of bounds
+    pub fn get(&self, index: usize) -> Option<&C> {
+        self.peaks.peaks.get(index)
+    }
+
+    /// The peak with the lowest m/z, or [`None`] if there are no peaks
+    pub fn first(&self) -> Option<&C> {
+        self.peaks.peaks.first()
+    }
+
+    /// The peak with the highest m/z, or [`None`] if there are no peaks
+    pub fn last(&self) -> Option<&C> {
+        self.peaks.peaks.last()
+    }
+
+    /// Find the peak nearest to `mz` regardless of how far away it is, using a binary search.
+    /// When `mz` is exactly halfway between two peaks, the lower one is returned.
+    ///
+    /// Returns [`None`] only when there are no peaks.
+    pub fn find_closest(&self, mz: f64) -> Option<&C> {
+        let peaks = &self.peaks.peaks;
+        let i = peaks.partition_point(|p| p.mz() < mz);
+        let below = i.checked_sub(1).map(|j| &peaks[j]);
+        match (below, peaks.get(i)) {
+            (Some(below), Some(above)) => {
+                if (mz - below.mz()) <= (above.mz() - mz) {
+                    Some(below)
+                } else {
+                    Some(above)
+                }
+            }
+            (below, above) => below.or(above),
+        }
+    }
+
+    /// Find all peaks matching the m/z at which an ion of `neutral_mass` would be observed
+    /// with `charge` protons gained (or lost, when negative), within `error_tolerance`
+    pub fn peaks_for_mass(
+        &self,
+        neutral_mass: f64,
+        charge: i32,
+        error_tolerance: Tolerance,
+    ) -> Vec<&C> {
+        let mz = mass_charge_ratio(neutral_mass, charge);
+        self.peaks
+            .all_peaks_for(mz, error_tolerance)
+            .iter()
+            .collect()
+    }
+
+    /// Estimate the charge of the ion observed at `mz` from the spacing of the isotopic
+    /// peaks around it, trying each charge magnitude from 1 to `max_charge`.
+    ///
+    /// For each candidate charge, the isotopic peaks expected on either side of `mz` are
+    /// matched within `error_tolerance` until one is missing, and the charge whose matched
+    /// peaks have the greatest total intensity is chosen. Returns [`None`] if there is no peak
+    /// at `mz` or no candidate charge matches any neighbouring isotopic peak.
+    pub fn estimate_charge_at(
+        &self,
+        mz: f64,
+        error_tolerance: Tolerance,
+        max_charge: i32,
+    ) -> Option<i32> {
+        self.peaks.has_peak(mz, error_tolerance)?;
+        let mut best: Option<(i32, f32)> = None;
+        for charge in 1..=max_charge.abs() {
+            let spacing = ISOTOPIC_SPACING / charge as f64;
+            let mut score = 0.0;
+            for direction in [1.0, -1.0] {
+                let mut step = 1.0;
+                while let Some(peak) = self
+                    .peaks
+                    .has_peak(mz + direction * step * spacing, error_tolerance)
+                {
+                    score += peak.intensity();
+                    step += 1.0;
+                }
+            }
+            if score > 0.0 && best.map_or(true, |(_, best_score)| score > best_score) {
+                best = Some((charge, score));
+            }
+        }
+        best.map(|(charge, _)| charge)
+    }
+
+    /// Copy the m/z of each peak, in m/z order, into a contiguous array
+    pub fn mz_array(&self) -> Vec<f64> {
+        self.peaks.iter().map(|p| p.mz()).collect()
+    }
+
+    /// Copy the intensity of each peak, in m/z order, into a contiguous array
+    pub fn intensity_array(&self) -> Vec<f32> {
+        self.peaks.iter().map(|p| p.intensity()).collect()
+    }
+
+    /// Copy the intensity of each peak, in m/z order, into a contiguous array of `f64`
+    pub fn intensities_f64(&self) -> Vec<f64> {
+        self.peaks.iter().map(|p| p.intensity() as f64).collect()
+    }
+
+    /// Compute the Shannon entropy, using the natural logarithm, of the peak intensities
+    /// after scaling them to sum to one, as used to judge spectral library matches.
+    ///
+    /// Returns 0 for a spectrum without any intensity.
+    pub fn spectral_entropy(&self) -> f32 {
+        let tic: f64 = self.peaks.iter().map(|p| p.intensity() as f64).sum();
+        if tic <= 0.0 {
+            return 0.0;
+        }
+        let entropy: f64 = self
+            .peaks
+            .iter()
+            .map(|p| p.intensity() as f64 / tic)
+            .filter(|p| *p > 0.0)
+            .map(|p| -p * p.ln())
+            .sum();
+        entropy as f32
+    }
+
+    /// Compute the [`spectral_entropy`](CentroidSpectrumType::spectral_entropy) divided by
+    /// its largest possible value for this many peaks, `ln(n)`, so that it ranges from 0
+    /// for a single peak to 1 for peaks of equal intensity.
+    ///
+    /// Returns 0 for a spectrum with fewer than two peaks.
+    pub fn normalized_entropy(&self) -> f32 {
+        let n = self.peaks.len();
+        if n < 2 {
+            return 0.0;
+        }
+        (self.spectral_entropy() as f64 / (n as f64).ln()) as f32
+    }
+
+    /// Test whether `other` has the same number of peaks as this spectrum, and each pair
+    /// of peaks in m/z order matches within `mz_tol` and within `intensity_rel_tol` of
+    /// the larger intensity. The spectrum descriptions are not compared.
+    pub fn approx_eq(&self, other: &Self, mz_tol: Tolerance, intensity_rel_tol: f32) -> bool {
+        if self.peaks.len() != other.peaks.len() {
+            return false;
+        }
+        self.peaks.iter().zip(other.peaks.iter()).all(|(a, b)| {
+            let mz_err = (a.mz() - b.mz()).abs();
+            let mz_ok = match mz_tol {
+                Tolerance::PPM(tol) => mz_err / b.mz().abs() * 1e6 <= tol,
+                Tolerance::Da(tol) => mz_err <= tol,
+            };
+            let intensity_err = (a.intensity() - b.intensity()).abs();
+            let intensity_ok =
+                intensity_err <= intensity_rel_tol * a.intensity().abs().max(b.intensity().abs());
+            mz_ok && intensity_ok
+        })
+    }
+
+    /// Convert the peaks into `(m/z, intensity)` pairs, in m/z order
+    pub fn into_tuples(self) -> Vec<(f64, f32)> {
+        self.peaks
+            .into_iter()
+            .map(|p| (p.mz(), p.intensity()))
+            .collect()
+    }
+
+    /// Compute the `q`th quantile of the peak intensities, e.g. the median when `q` is `0.5`,
+    /// interpolating linearly between peaks.
+    ///
+    /// Returns [`None`] if there are no peaks or if `q` is not in `[0, 1]`.
+    pub fn intensity_quantile(&self, q: f64) -> Option<f32> {
+        intensity_quantile_of(self.intensity_array(), q)
+    }
+
+    /// Drop all peaks with zero intensity, re-indexing the remaining peaks and their annotations
+    pub fn remove_zero_intensity(&mut self) {
+        let peaks = std::mem::take(&mut self.peaks.peaks);
+        let mut kept = 0;
+        let new_indices: Vec<Option<usize>> = peaks
+            .iter()
+            .map(|p| {
+                (p.intensity() != 0.0).then(|| {
+                    kept += 1;
+                    kept - 1
+                })
+            })
+            .collect();
+        self.peaks = peaks.into_iter().filter(|p| p.intensity() != 0.0).collect();
+        self.annotations.retain_mut(|a| match new_indices.get(a.index).copied().flatten() {
+            Some(index) => {
+                a.index = index;
+                true
+            }
+            None => false,
+        });
+    }
+
+    /// Apply `f` to each peak, producing a new spectrum that keeps this spectrum's
+    /// description. The peaks are re-sorted afterwards in case `f` changed their m/z.
+    pub fn map_peaks<F: FnMut(C) -> C>(self, f: F) -> Self {
+        let peaks = self.peaks.into_iter().map(f).collect();
+        Self::new(self.description, peaks)
+    }
+
+    /// Convert a spectrum into a [`DeconvolutedSpectrum`] by assigning every peak the
+    /// same `charge` and computing its neutral mass from its m/z.
+    ///
+    /// No isotopic pattern fitting is done, so this is only appropriate when the charge
+    /// state of every peak is already known, as with singly charged fragment peak lists.
+    pub fn into_deconvoluted(self, charge: i32) -> DeconvolutedSpectrum {
+        let peaks = self
+            .peaks
+            .iter()
+            .map(|p| DeconvolutedPeak {
+                neutral_mass: neutral_mass(p.mz(), charge),
+                intensity: p.intensity(),
+                charge,
+                ..Default::default()
+            })
+            .collect();
+        DeconvolutedSpectrum::new(self.description, peaks)
+    }
+
+    /// Deisotope and charge state deconvolve the peaks of a spectrum into a [`DeconvolutedSpectrum`],
+    /// using the charge states, charge carrier and polarity described by `params`.
+    pub fn deconvolute(self, params: &DeconvolutionParams) -> DeconvolutedSpectrum {
+        let peaks = deconvolute_peaks(&self.peaks, params);
+        DeconvolutedSpectrum::new(self.description, peaks)
+    }
+
+    /// Convert a spectrum into a [`RawSpectrum`], encoding the peaks as m/z and intensity arrays
+    pub fn into_raw(self) -> RawSpectrum
+    where
+        C: BuildArrayMapFrom,
+    {
+        let arrays = C::as_arrays(&self.peaks[0..]);
+        let mut result = RawSpectrum::new(self.description, arrays);
+        result.description.signal_continuity = SignalContinuity::Centroid;
+        result
     }
 
     /// Convert a spectrum into a [`MultiLayerSpectrum`]
@@ -594,6 +1311,32 @@ impl<C: CentroidLike + Default> CentroidSpectrumType<C> {
 
 pub type CentroidSpectrum = CentroidSpectrumType<CentroidPeak>;
 
+/// Build a spectrum with a default description from `(m/z, intensity)` pairs in any order.
+/// The peaks are sorted by m/z.
+impl<C: CentroidLike + Default + From<CentroidPeak>> FromIterator<(f64, f32)>
+    for CentroidSpectrumType<C>
+{
+    fn from_iter<T: IntoIterator<Item = (f64, f32)>>(iter: T) -> Self {
+        let peaks = iter
+            .into_iter()
+            .map(|(mz, intensity)| CentroidPeak::new(mz, intensity, 0).into())
+            .collect();
+        let mut description = SpectrumDescription::default();
+        description.signal_continuity = SignalContinuity::Centroid;
+        Self::new(description, peaks)
+    }
+}
+
+/// Exact equality of descriptions and peaks. Prefer [`CentroidSpectrumType::approx_eq`]
+/// when comparing the results of floating point transformations.
+impl<C: CentroidLike + Default + PartialEq> PartialEq for CentroidSpectrumType<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.description == other.description
+            && self.peaks.peaks == other.peaks.peaks
+            && self.annotations == other.annotations
+    }
+}
+
 impl<C: CentroidPeakAdapting> Index<usize> for CentroidSpectrumType<C> {
     type Output = <MZPeakSetType<C> as Index<usize>>::Output;
 
@@ -631,6 +1374,38 @@ where
     }
 }
 
+impl<'a, C: CentroidLike + Default> IntoIterator for &'a CentroidSpectrumType<C> {
+    type Item = &'a C;
+    type IntoIter = std::slice::Iter<'a, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.peaks.peaks.iter()
+    }
+}
+
+impl<C: CentroidLike + Default + IntensityMeasurementMut> CentroidSpectrumType<C> {
+    /// Scale the peak intensities so that they sum to one, and mark the spectrum
+    /// as normalized.
+    ///
+    /// # See also
+    /// [`SpectrumLike::is_normalized`]
+    pub fn normalize_tic(&mut self) {
+        normalize_by_tic(self.peaks.peaks.iter_mut().map(|p| p.intensity_mut()));
+        mark_normalized(&mut self.description.params, "total ion current");
+    }
+}
+
+/// Iterate over the peaks mutably. Changing a peak's m/z such that the peaks are
+/// no longer sorted will break searching.
+impl<'a, C: CentroidLike + Default> IntoIterator for &'a mut CentroidSpectrumType<C> {
+    type Item = &'a mut C;
+    type IntoIter = std::slice::IterMut<'a, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.peaks.peaks.iter_mut()
+    }
+}
+
 /// Represents a spectrum that has been centroided, deisotoped, and charge state deconvolved.
 ///
 /// This type of spectrum represents data in exactly one format.
@@ -651,6 +1426,43 @@ impl<D: DeconvolutedCentroidLike + Default> DeconvolutedSpectrumType<D> {
         }
     }
 
+    /// Check if the spectrum has no peaks.
+    ///
+    /// This is the same as [`SpectrumLike::is_empty`] and takes precedence over
+    /// [`PeakCollection::is_empty`] so the call isn't ambiguous.
+    pub fn is_empty(&self) -> bool {
+        self.deconvoluted_peaks.peaks.is_empty()
+    }
+
+    /// Apply `f` to each peak, producing a new spectrum that keeps this spectrum's
+    /// description. The peaks are re-sorted afterwards in case `f` changed their mass.
+    pub fn map_peaks<F: FnMut(D) -> D>(self, f: F) -> Self {
+        let peaks = self.deconvoluted_peaks.into_iter().map(f).collect();
+        Self::new(self.description, peaks)
+    }
+
+    /// Get the `index`th peak in neutral mass order, or [`None`] if `index` is out of bounds
+    pub fn get(&self, index: usize) -> Option<&D> {
+        self.deconvoluted_peaks.peaks.get(index)
+    }
+
+    /// The peak with the lowest neutral mass, or [`None`] if there are no peaks
+    pub fn first(&self) -> Option<&D> {
+        self.deconvoluted_peaks.peaks.first()
+    }
+
+    /// The peak with the highest neutral mass, or [`None`] if there are no peaks
+    pub fn last(&self) -> Option<&D> {
+        self.deconvoluted_peaks.peaks.last()
+    }
+
+    /// The most intense deconvoluted peak, or [`None`] if there are no peaks
+    pub fn base_peak(&self) -> Option<&D> {
+        self.deconvoluted_peaks
+            .iter()
+            .max_by(|a, b| a.intensity().total_cmp(&b.intensity()))
+    }
+
     /// Convert a spectrum into a [`MultiLayerSpectrum`]
     pub fn into_spectrum<C>(self) -> Result<MultiLayerSpectrum<C, D>, SpectrumConversionError>
     where
@@ -705,6 +1517,13 @@ impl<D: DeconvolutedCentroidLike + Default> SpectrumLike<CentroidPeak, D>
 
 pub type DeconvolutedSpectrum = DeconvolutedSpectrumType<DeconvolutedPeak>;
 
+impl<D: DeconvolutedCentroidLike + Default + PartialEq> PartialEq for DeconvolutedSpectrumType<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.description == other.description
+            && self.deconvoluted_peaks.peaks == other.deconvoluted_peaks.peaks
+    }
+}
+
 impl<D: DeconvolutedPeakAdapting> Index<usize> for DeconvolutedSpectrumType<D> {
     type Output = <MassPeakSetType<D> as Index<usize>>::Output;
 
@@ -742,6 +1561,28 @@ where
     }
 }
 
+impl<'a, D: DeconvolutedCentroidLike + Default> IntoIterator for &'a DeconvolutedSpectrumType<D> {
+    type Item = &'a D;
+    type IntoIter = std::slice::Iter<'a, D>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.deconvoluted_peaks.peaks.iter()
+    }
+}
+
+/// Iterate over the peaks mutably. Changing a peak's neutral mass such that the peaks
+/// are no longer sorted will break searching.
+impl<'a, D: DeconvolutedCentroidLike + Default> IntoIterator
+    for &'a mut DeconvolutedSpectrumType<D>
+{
+    type Item = &'a mut D;
+    type IntoIter = std::slice::IterMut<'a, D>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.deconvoluted_peaks.peaks.iter_mut()
+    }
+}
+
 #[derive(Default, Debug, Clone)]
 /// Represent a spectrum with multiple layers of representation of the
 /// peak data.
@@ -840,26 +1681,18 @@ where
     /// Convert a spectrum into a [`CentroidSpectrumType`]
     pub fn into_centroid(self) -> Result<CentroidSpectrumType<C>, SpectrumConversionError> {
         if let Some(peaks) = self.peaks {
-            let mut result = CentroidSpectrumType::<C> {
-                peaks,
-                description: self.description,
-            };
+            let mut result = CentroidSpectrumType::<C>::new(self.description, peaks);
             result.description.signal_continuity = SignalContinuity::Centroid;
             return Ok(result);
         } else if self.signal_continuity() == SignalContinuity::Centroid {
             if let Some(arrays) = &self.arrays {
                 let peaks = C::try_from_arrays(arrays)?.into();
-                let mut centroid = CentroidSpectrumType::<C> {
-                    description: self.description,
-                    peaks,
-                };
+                let mut centroid = CentroidSpectrumType::<C>::new(self.description, peaks);
                 centroid.description.signal_continuity = SignalContinuity::Centroid;
                 return Ok(centroid);
             } else {
-                let mut result = CentroidSpectrumType::<C> {
-                    peaks: MZPeakSetType::<C>::empty(),
-                    description: self.description,
-                };
+                let mut result =
+                    CentroidSpectrumType::<C>::new(self.description, MZPeakSetType::<C>::empty());
                 result.description.signal_continuity = SignalContinuity::Centroid;
                 return Ok(result);
             }
@@ -935,6 +1768,14 @@ where
         Self::from_peaks_data_levels_and_description(peaks, description)
     }
 
+    /// Copy only the spectrum's metadata, leaving its data arrays and peak lists behind.
+    ///
+    /// Cloning the whole spectrum copies every array and peak list, which can be expensive for
+    /// large spectra. Use this when only the metadata are needed, and [`Clone`] for a full copy.
+    pub fn shallow_clone_description(&self) -> SpectrumDescription {
+        self.description.clone()
+    }
+
     #[cfg(feature = "mzsignal")]
     pub fn denoise(&mut self, scale: f32) -> Result<(), SpectrumProcessingError> {
         match &mut self.arrays {
@@ -1027,6 +1868,104 @@ where
     }
 }
 
+impl<C: CentroidLike + Default, D: DeconvolutedCentroidLike + Default> MultiLayerSpectrum<C, D>
+where
+    C: IntensityMeasurementMut,
+    D: IntensityMeasurementMut,
+{
+    /// Scale the intensities of every available layer so that each sums to one, and
+    /// mark the spectrum as normalized.
+    ///
+    /// # Errors
+    /// If the intensity array cannot be decoded
+    ///
+    /// # See also
+    /// [`SpectrumLike::is_normalized`]
+    pub fn normalize_tic(&mut self) -> Result<(), ArrayRetrievalError> {
+        if let Some(arrays) = self.arrays.as_mut() {
+            if arrays.has_array(&ArrayType::IntensityArray) {
+                normalize_by_tic(arrays.intensities_mut()?.iter_mut());
+            }
+        }
+        if let Some(peaks) = self.peaks.as_mut() {
+            normalize_by_tic(peaks.peaks.iter_mut().map(|p| p.intensity_mut()));
+        }
+        if let Some(peaks) = self.deconvoluted_peaks.as_mut() {
+            normalize_by_tic(peaks.peaks.iter_mut().map(|p| p.intensity_mut()));
+        }
+        mark_normalized(&mut self.description.params, "total ion current");
+        Ok(())
+    }
+}
+
+impl<C: CentroidLike + Default, D: DeconvolutedCentroidLike + Default> MultiLayerSpectrum<C, D>
+where
+    C: BuildFromArrayMap + IntensityMeasurementMut + Clone,
+    D: BuildFromArrayMap,
+{
+    /// Merge the centroid peaks and precursor of `other` into this spectrum, as when
+    /// combining co-isolated scans of a chimeric MS2 spectrum.
+    ///
+    /// Peaks of `other` within `tolerance` of a peak in this spectrum add their intensity
+    /// to it, and the rest are added as new peaks. The selected ions of `other`'s precursor
+    /// are appended to this spectrum's precursor, so both are retained. The data arrays and
+    /// deconvoluted peaks no longer describe the merged peak list, so they are dropped.
+    ///
+    /// **Note**: A spectrum holds a single [`Precursor`], so only the selected ions of
+    /// `other`'s precursor are kept. Its isolation window, activation and parameters are
+    /// lost, and those of this spectrum's precursor describe the merged spectrum. If this
+    /// spectrum has no precursor, `other`'s precursor is copied whole.
+    ///
+    /// # Errors
+    /// If either spectrum has no centroid peaks and they cannot be built from its data arrays
+    pub fn merge_with(
+        &mut self,
+        other: &Self,
+        tolerance: Tolerance,
+    ) -> Result<(), SpectrumConversionError> {
+        self.try_build_centroids()?;
+        let built: MZPeakSetType<C>;
+        let other_peaks = match (&other.peaks, &other.arrays) {
+            (Some(peaks), _) => peaks,
+            (None, Some(arrays)) => match other.signal_continuity() {
+                SignalContinuity::Centroid => {
+                    built = C::try_from_arrays(arrays)?.into();
+                    &built
+                }
+                _ => return Err(SpectrumConversionError::NotCentroided),
+            },
+            (None, None) => return Err(SpectrumConversionError::NoPeakData),
+        };
+
+        let peaks = self.peaks.as_mut().unwrap();
+        let mut unmatched = Vec::new();
+        for peak in other_peaks.iter() {
+            match peaks.has_peak(peak.mz(), tolerance) {
+                Some(matched) => {
+                    let index = matched.get_index() as usize;
+                    *peaks.peaks[index].intensity_mut() += peak.intensity();
+                }
+                None => unmatched.push(peak.clone()),
+            }
+        }
+        if !unmatched.is_empty() {
+            let mut merged = std::mem::take(&mut peaks.peaks);
+            merged.extend(unmatched);
+            *peaks = MZPeakSetType::new(merged);
+        }
+        self.arrays = None;
+        self.deconvoluted_peaks = None;
+
+        if let Some(other_precursor) = other.precursor() {
+            match self.description.precursor.as_mut() {
+                Some(precursor) => precursor.ions.extend(other_precursor.ions.iter().cloned()),
+                None => self.description.precursor = Some(other_precursor.clone()),
+            }
+        }
+        Ok(())
+    }
+}
+
 impl<C: CentroidLike + Default, D: DeconvolutedCentroidLike + Default> MultiLayerSpectrum<C, D>
 where
     C: BuildFromArrayMap,
@@ -1100,6 +2039,46 @@ where
     }
 }
 
+/// How the m/z of a peak is computed from the profile signal when picking peaks
+#[cfg(feature = "mzsignal")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CentroidingMethod {
+    /// Use the m/z fitted by the [`PeakPicker`], a three point parabola around the
+    /// apex for [`PeakFitType::Quadratic`]
+    #[default]
+    PeakFit,
+    /// Use the intensity-weighted mean m/z of all the contiguous points around the
+    /// apex whose intensity is at least half of the apex's intensity. This is more
+    /// robust for asymmetric peaks.
+    WeightedMean,
+}
+
+/// Compute the intensity-weighted mean m/z of the region around `apex` that is above
+/// half of the apex's intensity
+#[cfg(feature = "mzsignal")]
+fn weighted_centroid_mz(mz_array: &[f64], intensity_array: &[f32], apex: usize) -> f64 {
+    let half_max = intensity_array[apex] / 2.0;
+    let mut start = apex;
+    while start > 0 && intensity_array[start - 1] >= half_max {
+        start -= 1;
+    }
+    let mut end = apex;
+    while end + 1 < intensity_array.len() && intensity_array[end + 1] >= half_max {
+        end += 1;
+    }
+    let (weighted, total) = mz_array[start..=end]
+        .iter()
+        .zip(&intensity_array[start..=end])
+        .fold((0.0, 0.0), |(weighted, total), (mz, int)| {
+            (weighted + mz * *int as f64, total + *int as f64)
+        });
+    if total > 0.0 {
+        weighted / total
+    } else {
+        mz_array[apex]
+    }
+}
+
 /// When [`mzsignal`] is available, [`MultiLayerSpectrum`] supports in-place signal processing operations.
 ///
 /// The peak picking steps need to convert an [`mzsignal::FittedPeak`] into `C`. This is trivial for [`CentroidPeak`]
@@ -1120,26 +2099,58 @@ impl<C: CentroidLike + Default + From<FittedPeak>, D: DeconvolutedCentroidLike +
         &mut self,
         peak_picker: &PeakPicker,
     ) -> Result<(), SpectrumProcessingError> {
+        self.pick_peaks_with_apex_indices(peak_picker).map(|_| ())
+    }
+
+    /// Pick peaks exactly like [`MultiLayerSpectrum::pick_peaks_with`], and return the index
+    /// of the raw data point at the apex of each picked peak.
+    ///
+    /// The returned indices run parallel to the new [`MultiLayerSpectrum::peaks`], so the `i`th
+    /// peak was picked from the point at `indices[i]` in the m/z and intensity arrays. The peaks'
+    /// own `index` values refer to their position in the peak list instead.
+    pub fn pick_peaks_with_apex_indices(
+        &mut self,
+        peak_picker: &PeakPicker,
+    ) -> Result<Vec<usize>, SpectrumProcessingError> {
+        self.pick_peaks_with_method_and_apex_indices(peak_picker, CentroidingMethod::PeakFit)
+    }
+
+    fn pick_peaks_with_method_and_apex_indices(
+        &mut self,
+        peak_picker: &PeakPicker,
+        method: CentroidingMethod,
+    ) -> Result<Vec<usize>, SpectrumProcessingError> {
         if let Some(arrays) = &self.arrays {
             let mz_array = arrays.mzs()?;
             let intensity_array = arrays.intensities()?;
 
             if matches!(self.signal_continuity(), SignalContinuity::Centroid) {
-                let mut peaks: MZPeakSetType<C> = mz_array
+                let mut indices: Vec<usize> = (0..mz_array.len()).collect();
+                indices.sort_by(|a, b| mz_array[*a].total_cmp(&mz_array[*b]));
+                let peaks: MZPeakSetType<C> = indices
                     .iter()
-                    .zip(intensity_array.iter())
-                    .map(|(mz, inten)| FittedPeak::new(*mz, *inten, 0, 0.0, 0.0).into())
+                    .map(|i| FittedPeak::new(mz_array[*i], intensity_array[*i], 0, 0.0, 0.0).into())
                     .collect();
-                peaks.sort();
                 self.peaks = Some(peaks);
-                Ok(())
+                Ok(indices)
             } else {
                 let mut acc = Vec::new();
                 match peak_picker.discover_peaks(&mz_array, &intensity_array, &mut acc) {
                     Ok(_) => {
+                        if matches!(method, CentroidingMethod::WeightedMean) {
+                            for peak in acc.iter_mut() {
+                                peak.mz = weighted_centroid_mz(
+                                    &mz_array,
+                                    &intensity_array,
+                                    peak.get_index() as usize,
+                                );
+                            }
+                        }
+                        acc.sort_by(|a, b| a.mz.total_cmp(&b.mz));
+                        let indices = acc.iter().map(|p| p.get_index() as usize).collect();
                         let peaks: MZPeakSetType<C> = acc.into_iter().map(|p| C::from(p)).collect();
                         self.peaks = Some(peaks);
-                        Ok(())
+                        Ok(indices)
                     }
                     Err(err) => Err(SpectrumProcessingError::PeakPickerError(err)),
                 }
@@ -1168,6 +2179,25 @@ impl<C: CentroidLike + Default + From<FittedPeak>, D: DeconvolutedCentroidLike +
         self.pick_peaks_with(&peak_picker)
     }
 
+    /// Pick peaks as in [`MultiLayerSpectrum::pick_peaks`], computing the m/z of each peak
+    /// with `method`.
+    ///
+    /// If [`SpectrumLike::signal_continuity`] returns [`SignalContinuity::Centroid`], the
+    /// points are already centroids and `method` has no effect.
+    pub fn pick_peaks_with_method(
+        &mut self,
+        signal_to_noise_threshold: f32,
+        method: CentroidingMethod,
+    ) -> Result<(), SpectrumProcessingError> {
+        let peak_picker = PeakPicker {
+            fit_type: PeakFitType::Quadratic,
+            signal_to_noise_threshold,
+            ..Default::default()
+        };
+        self.pick_peaks_with_method_and_apex_indices(&peak_picker, method)
+            .map(|_| ())
+    }
+
     /// Pick peaks in the specified m/z intervals with a minimum signal-to-noise threshold,
     /// populating [`MultiLayerSpectrum::peaks`] with an [`mzpeaks::MZPeakSetType`] over `C`
     /// from those intervals.
@@ -1230,6 +2260,8 @@ impl<C: CentroidLike + Default + From<FittedPeak>, D: DeconvolutedCentroidLike +
     }
 }
 
+/// Fails with [`SpectrumConversionError::NotCentroided`] if the spectrum has neither
+/// centroided peaks nor centroid-mode signal arrays. See [`MultiLayerSpectrum::into_centroid`].
 impl<C: CentroidLike + Default, D: DeconvolutedCentroidLike + Default>
     TryFrom<MultiLayerSpectrum<C, D>> for CentroidSpectrumType<C>
 where
@@ -1249,6 +2281,7 @@ where
 /// in multiple overlapping layers.
 pub type Spectrum = MultiLayerSpectrum<CentroidPeak, DeconvolutedPeak>;
 
+/// Lossless, the peaks are stored in [`MultiLayerSpectrum::peaks`]
 impl<C: CentroidLike + Default, D: DeconvolutedCentroidLike + Default> From<CentroidSpectrumType<C>>
     for MultiLayerSpectrum<C, D>
 {
@@ -1257,6 +2290,7 @@ impl<C: CentroidLike + Default, D: DeconvolutedCentroidLike + Default> From<Cent
     }
 }
 
+/// Lossless, the arrays are stored in [`MultiLayerSpectrum::arrays`]
 impl<C: CentroidLike + Default, D: DeconvolutedCentroidLike + Default> From<RawSpectrum>
     for MultiLayerSpectrum<C, D>
 where
@@ -1268,6 +2302,8 @@ where
     }
 }
 
+/// Uses the spectrum's arrays if present, otherwise encodes its peaks as arrays.
+/// See [`MultiLayerSpectrum::into_raw`].
 impl<C: CentroidLike + Default, D: DeconvolutedCentroidLike + Default>
     From<MultiLayerSpectrum<C, D>> for RawSpectrum
 where
@@ -1279,6 +2315,18 @@ where
     }
 }
 
+/// Lossless, the peaks are encoded as m/z and intensity arrays
+impl<C: CentroidLike + Default> From<CentroidSpectrumType<C>> for RawSpectrum
+where
+    C: BuildArrayMapFrom,
+{
+    fn from(spectrum: CentroidSpectrumType<C>) -> RawSpectrum {
+        spectrum.into_raw()
+    }
+}
+
+/// Fails with [`SpectrumConversionError::NotCentroided`] for profile spectra, which must
+/// be peak picked instead. See [`RawSpectrum::into_centroid`].
 impl<C: CentroidLike + Default> TryFrom<RawSpectrum> for CentroidSpectrumType<C>
 where
     C: BuildFromArrayMap,
@@ -1330,6 +2378,8 @@ mod test {
     use crate::io::mzml::MzMLReader;
     use crate::io::DetailLevel;
     use crate::prelude::*;
+    use crate::spectrum::bindata::mz_intensity_arrays;
+    use crate::spectrum::scan_properties::SelectedIon;
 
     #[test_log::test]
     fn test_peakdata_lazy() -> io::Result<()> {
@@ -1374,6 +2424,1087 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_deconvoluted_spectrum() {
+        let peaks = PeakSetVec::new(vec![
+            DeconvolutedPeak {
+                neutral_mass: 1500.75,
+                intensity: 80.0,
+                charge: 2,
+                ..Default::default()
+            },
+            DeconvolutedPeak {
+                neutral_mass: 742.4,
+                intensity: 200.0,
+                charge: 1,
+                ..Default::default()
+            },
+            DeconvolutedPeak {
+                neutral_mass: 2210.1,
+                intensity: 35.0,
+                charge: 3,
+                ..Default::default()
+            },
+        ]);
+        let spectrum = DeconvolutedSpectrum::new(SpectrumDescription::default(), peaks);
+        let base_peak = spectrum.base_peak().unwrap();
+        assert_eq!(base_peak.neutral_mass, 742.4);
+        assert_eq!(base_peak.charge, 1);
+        assert!(DeconvolutedSpectrum::default().base_peak().is_none());
+
+        let centroids = PeakSet::new(vec![
+            CentroidPeak::new(200.0, 10.0, 0),
+            CentroidPeak::new(350.5, 40.0, 0),
+        ]);
+        let deconvoluted =
+            CentroidSpectrum::new(SpectrumDescription::default(), centroids).into_deconvoluted(1);
+        assert_eq!(deconvoluted.deconvoluted_peaks.len(), 2);
+        let base_peak = deconvoluted.base_peak().unwrap();
+        assert!((base_peak.neutral_mass - neutral_mass(350.5, 1)).abs() < 1e-9);
+        assert_eq!(base_peak.charge, 1);
+    }
+
+    #[test]
+    fn test_deconvoluted_mz_multiply_charged() -> Result<(), ArrayRetrievalError> {
+        use mzpeaks::feature::ChargedFeature;
+        use mzpeaks::IonMobility;
+
+        let centroids = PeakSet::new(vec![
+            CentroidPeak::new(500.5, 10.0, 0),
+            CentroidPeak::new(812.4, 40.0, 0),
+        ]);
+        let spectrum =
+            CentroidSpectrum::new(SpectrumDescription::default(), centroids).into_deconvoluted(3);
+        let peaks = SpectrumLike::peaks(&spectrum);
+        assert!((peaks.base_peak().mz - 812.4).abs() < 1e-9);
+        let (low, high) = peaks.mz_range();
+        assert!((low - 500.5).abs() < 1e-9);
+        assert!((high - 812.4).abs() < 1e-9);
+        assert!((peaks.get(0).unwrap().mz - 500.5).abs() < 1e-9);
+        let summary = peaks.fetch_summaries();
+        assert!((summary.base_peak.mz - 812.4).abs() < 1e-9);
+        assert!((summary.mz_range.0 - 500.5).abs() < 1e-9);
+
+        let mut feature: ChargedFeature<Mass, IonMobility> = ChargedFeature::default();
+        feature.charge = 2;
+        feature.push_raw(neutral_mass(812.4, 2), 0.9, 100.0);
+        feature.push_raw(neutral_mass(812.4, 2), 1.0, 80.0);
+        let arrays = ChargedFeature::<Mass, IonMobility>::as_arrays(&[feature]);
+        let mzs = arrays.mzs()?;
+        assert_eq!(mzs.len(), 2);
+        for mz in mzs.iter() {
+            assert!((mz - 812.4).abs() < 1e-9);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_with() -> Result<(), SpectrumConversionError> {
+        let make_ms2 = |precursor_mz: f64, peaks: Vec<CentroidPeak>| -> MultiLayerSpectrum {
+            let mut precursor = Precursor::default();
+            precursor.ions.push(SelectedIon {
+                mz: precursor_mz,
+                charge: Some(2),
+                ..Default::default()
+            });
+            precursor.isolation_window.target = precursor_mz as f32;
+            let description = SpectrumDescription {
+                ms_level: 2,
+                precursor: Some(precursor),
+                ..Default::default()
+            };
+            description.with_peaks(peaks).into()
+        };
+
+        let mut spectrum = make_ms2(
+            500.25,
+            vec![
+                CentroidPeak::new(150.0, 10.0, 0),
+                CentroidPeak::new(300.0, 20.0, 1),
+            ],
+        );
+        let other = make_ms2(
+            502.75,
+            vec![
+                CentroidPeak::new(300.001, 5.0, 0),
+                CentroidPeak::new(450.0, 1.0, 1),
+            ],
+        );
+        spectrum.merge_with(&other, Tolerance::PPM(10.0))?;
+
+        let peaks = spectrum.peaks.as_ref().unwrap();
+        let mzs: Vec<f64> = peaks.iter().map(|p| p.mz).collect();
+        assert_eq!(mzs, vec![150.0, 300.0, 450.0]);
+        let intensities: Vec<f32> = peaks.iter().map(|p| p.intensity).collect();
+        assert_eq!(intensities, vec![10.0, 25.0, 1.0]);
+
+        let ions: Vec<f64> = spectrum
+            .precursor()
+            .unwrap()
+            .ions
+            .iter()
+            .map(|i| i.mz)
+            .collect();
+        assert_eq!(ions, vec![500.25, 502.75]);
+        // Only one isolation window can be kept, which is this spectrum's
+        assert_eq!(
+            spectrum.precursor().unwrap().isolation_window.target,
+            500.25
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_peaks() {
+        let description = SpectrumDescription {
+            id: "scan=7".to_string(),
+            ms_level: 2,
+            ..Default::default()
+        };
+        let spectrum = description.with_peaks(vec![
+            CentroidPeak::new(150.25, 100.0, 0),
+            CentroidPeak::new(301.5, 20.0, 1),
+        ]);
+        assert_eq!(spectrum.signal_continuity(), SignalContinuity::Centroid);
+
+        let scaled = spectrum.map_peaks(|mut p| {
+            p.intensity *= 0.5;
+            p
+        });
+        assert_eq!(scaled.id(), "scan=7");
+        assert_eq!(scaled.ms_level(), 2);
+        assert_eq!(scaled.intensity_array(), vec![50.0, 10.0]);
+        assert_eq!(scaled.mz_array(), vec![150.25, 301.5]);
+    }
+
+    #[test]
+    fn test_raw_array_slices() -> io::Result<()> {
+        let mut reader = MzMLReader::open_path("./test/data/small.mzML")?;
+        let mut spec: RawSpectrum = reader.get_spectrum_by_index(0).unwrap().into();
+        spec.mzs_mut()?;
+        spec.intensities_mut()?;
+
+        let mzs = spec.mz_slice()?;
+        let intensities = spec.intensity_slice()?;
+        assert_eq!(mzs.len(), 19913);
+        assert_eq!(intensities.len(), 19913);
+        assert_eq!(mzs, &spec.mzs()[..]);
+        assert_eq!(intensities, &spec.intensities()[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_centroid_arrays() {
+        let peaks = PeakSet::new(vec![
+            CentroidPeak::new(301.5, 20.0, 0),
+            CentroidPeak::new(150.25, 100.0, 0),
+            CentroidPeak::new(420.0, 5.0, 0),
+        ]);
+        let spectrum = CentroidSpectrum::new(SpectrumDescription::default(), peaks);
+
+        let mzs = spectrum.mz_array();
+        let intensities = spectrum.intensity_array();
+        assert_eq!(mzs, vec![150.25, 301.5, 420.0]);
+        assert_eq!(intensities, vec![100.0, 20.0, 5.0]);
+        for (i, peak) in spectrum.peaks.iter().enumerate() {
+            assert_eq!(mzs[i], peak.mz);
+            assert_eq!(intensities[i], peak.intensity);
+        }
+    }
+
+    #[test]
+    fn test_spectrum_conversions() {
+        let centroid: CentroidSpectrum = vec![(150.25, 100.0), (301.5, 20.0)].into_iter().collect();
+
+        // centroid -> raw -> centroid is lossless
+        let raw: RawSpectrum = centroid.clone().into();
+        assert_eq!(
+            raw.description.signal_continuity,
+            SignalContinuity::Centroid
+        );
+        assert_eq!(raw.mzs().as_ref(), &[150.25, 301.5]);
+        let back = CentroidSpectrum::try_from(raw.clone()).unwrap();
+        assert_eq!(back.into_tuples(), centroid.clone().into_tuples());
+
+        // centroid -> spectrum -> centroid
+        let spectrum: Spectrum = centroid.clone().into();
+        assert!(spectrum.peaks.is_some());
+        let back = CentroidSpectrum::try_from(spectrum).unwrap();
+        assert_eq!(back.into_tuples(), centroid.clone().into_tuples());
+
+        // raw -> spectrum -> raw
+        let spectrum: Spectrum = raw.into();
+        assert!(spectrum.arrays.is_some());
+        let raw: RawSpectrum = spectrum.into();
+        assert_eq!(raw.intensities().as_ref(), &[100.0, 20.0]);
+
+        // profile -> centroid requires peak picking
+        let mut profile = raw;
+        profile.description.signal_continuity = SignalContinuity::Profile;
+        let spectrum: Spectrum = profile.clone().into();
+        assert!(matches!(
+            CentroidSpectrum::try_from(profile),
+            Err(SpectrumConversionError::NotCentroided)
+        ));
+        assert!(matches!(
+            CentroidSpectrum::try_from(spectrum),
+            Err(SpectrumConversionError::NotCentroided)
+        ));
+    }
+
+    #[test]
+    fn test_centroid_from_tuples() {
+        let spectrum: CentroidSpectrum = vec![(420.0, 5.0), (150.25, 100.0), (301.5, 20.0)]
+            .into_iter()
+            .collect();
+        assert_eq!(spectrum.signal_continuity(), SignalContinuity::Centroid);
+        assert_eq!(spectrum.mz_array(), vec![150.25, 301.5, 420.0]);
+        assert!(spectrum
+            .peaks
+            .has_peak(301.5, Tolerance::PPM(10.0))
+            .is_some());
+        assert_eq!(
+            spectrum.into_tuples(),
+            vec![(150.25, 100.0), (301.5, 20.0), (420.0, 5.0)]
+        );
+    }
+
+    #[test]
+    fn test_deconvolute_params() {
+        use crate::spectrum::SODIUM;
+        use crate::utils::PROTON;
+
+        // A doubly deprotonated envelope of a 1000 Da molecule plus an isolated peak
+        let mono = 1000.0 / 2.0 - PROTON;
+        let spacing = 1.00286864 / 2.0;
+        let spectrum: CentroidSpectrum = vec![
+            (mono, 100.0),
+            (mono + spacing, 80.0),
+            (mono + 2.0 * spacing, 40.0),
+            (700.0, 10.0),
+        ]
+        .into_iter()
+        .collect();
+        let params = DeconvolutionParams {
+            polarity: ScanPolarity::Negative,
+            ..Default::default()
+        };
+        let deconvoluted = spectrum.deconvolute(&params);
+        assert_eq!(deconvoluted.deconvoluted_peaks.len(), 2);
+        let peak = deconvoluted
+            .deconvoluted_peaks
+            .iter()
+            .find(|p| p.charge == -2)
+            .unwrap();
+        assert!((peak.neutral_mass - 1000.0).abs() < 1e-6);
+        assert_eq!(peak.intensity, 220.0);
+
+        // A sodiated, singly charged envelope in positive mode
+        let mono = 500.0 + SODIUM;
+        let spectrum: CentroidSpectrum = vec![(mono, 50.0), (mono + 1.00286864, 30.0)]
+            .into_iter()
+            .collect();
+        let params =
+            DeconvolutionParams::new((1, 3), SODIUM, ScanPolarity::Positive, Tolerance::PPM(5.0));
+        let deconvoluted = spectrum.deconvolute(&params);
+        assert_eq!(deconvoluted.deconvoluted_peaks.len(), 1);
+        let peak = &deconvoluted.deconvoluted_peaks[0];
+        assert_eq!(peak.charge, 1);
+        assert!((peak.neutral_mass - 500.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_annotate_peaks() {
+        let mut spectrum: CentroidSpectrum = vec![
+            (175.119, 30.0),
+            (200.0, 0.0),
+            (276.155, 100.0),
+            (389.239, 60.0),
+        ]
+        .into_iter()
+        .collect();
+        spectrum.annotate(vec![
+            (3, "b3".to_string()),
+            (1, "noise".to_string()),
+            (0, "y1".to_string()),
+            (10, "out of range".to_string()),
+        ]);
+        spectrum.annotate(vec![(3, "y3".to_string())]);
+        assert_eq!(spectrum.annotations.len(), 4);
+
+        assert_eq!(spectrum.annotations_of(0).collect::<Vec<_>>(), vec!["y1"]);
+        assert_eq!(spectrum.annotations_of(2).count(), 0);
+        assert_eq!(
+            spectrum.annotations_of(3).collect::<Vec<_>>(),
+            vec!["b3", "y3"]
+        );
+        let labelled: Vec<_> = spectrum
+            .annotated_peaks()
+            .map(|(p, label)| (p.mz, label))
+            .collect();
+        assert_eq!(labelled[0], (175.119, "y1"));
+        assert_eq!(labelled[3], (389.239, "y3"));
+
+        // Annotations follow their peaks when others are removed
+        spectrum.remove_zero_intensity();
+        assert_eq!(spectrum.annotations_of(0).collect::<Vec<_>>(), vec!["y1"]);
+        assert_eq!(
+            spectrum.annotations_of(2).collect::<Vec<_>>(),
+            vec!["b3", "y3"]
+        );
+        assert_eq!(spectrum.annotations.len(), 3);
+
+        // Annotations take part in equality
+        let mut other = spectrum.clone();
+        assert_eq!(spectrum, other);
+        other.clear_annotations();
+        assert_ne!(spectrum, other);
+
+        // Removing peaks directly leaves annotations dangling, which are skipped
+        spectrum.peaks_mut().peaks.truncate(1);
+        let labelled: Vec<_> = spectrum.annotated_peaks().map(|(_, label)| label).collect();
+        assert_eq!(labelled, vec!["y1"]);
+
+        // and dropped, rather than panicking, when zero intensity peaks are removed
+        spectrum.remove_zero_intensity();
+        assert_eq!(spectrum.annotations.len(), 1);
+        spectrum.peaks_mut().peaks.clear();
+        spectrum.remove_zero_intensity();
+        assert!(spectrum.annotations.is_empty());
+        assert_eq!(spectrum.annotated_peaks().count(), 0);
+    }
+
+    #[test]
+    fn test_is_calibration() {
+        let mut spectrum = CentroidSpectrum::default();
+        spectrum.description.id = "function=3 process=0 scan=12".to_string();
+        assert!(!spectrum.is_calibration());
+        assert!(spectrum.is_calibration_by(|s| s.id().starts_with("function=3 ")));
+        assert!(spectrum.is_calibration_by(waters_lock_mass_function(3)));
+        assert!(!spectrum.is_calibration_by(waters_lock_mass_function(1)));
+        let multi: MultiLayerSpectrum = spectrum.into();
+        assert!(multi.is_calibration_by(waters_lock_mass_function(3)));
+
+        // A lock mass scan described in free text
+        let mut spectrum = CentroidSpectrum::default();
+        spectrum.description.add_param(Param::new_key_value(
+            "scan description",
+            "LockSpray reference",
+        ));
+        assert!(spectrum.is_calibration());
+
+        // A lock mass scan marked by the calibration spectrum term on its scan event
+        let mut spectrum = CentroidSpectrum::default();
+        spectrum
+            .description
+            .acquisition
+            .first_scan_mut()
+            .unwrap()
+            .add_param(
+                ControlledVocabulary::MS
+                    .const_param_ident("calibration spectrum", 1000928)
+                    .into(),
+            );
+        assert!(spectrum.is_calibration());
+
+        let mut spectrum = CentroidSpectrum::default();
+        spectrum.description.add_param(
+            ControlledVocabulary::MS
+                .const_param_ident("calibration spectrum", 1000928)
+                .into(),
+        );
+        assert!(spectrum.is_calibration());
+
+        // Free text that happens to mention calibration doesn't mark the scan
+        let mut spectrum = CentroidSpectrum::default();
+        spectrum
+            .description
+            .add_param(Param::new_key_value("scan description", "MS1 survey"));
+        spectrum
+            .description
+            .add_param(Param::new_key_value("last calibration", "2024-01-05"));
+        assert!(!spectrum.is_calibration());
+        assert!(!spectrum.is_calibration_by(|s| s.ms_level() > 2));
+    }
+
+    #[test]
+    fn test_spectral_entropy() {
+        let spectrum: CentroidSpectrum = vec![(100.0, 50.0), (200.0, 25.0), (300.0, 25.0)]
+            .into_iter()
+            .collect();
+        // -(0.5 ln 0.5 + 2 * 0.25 ln 0.25) = 1.5 ln 2
+        let expected = 1.5 * 2.0f32.ln();
+        assert!((spectrum.spectral_entropy() - expected).abs() < 1e-6);
+        assert!((spectrum.normalized_entropy() - expected / 3.0f32.ln()).abs() < 1e-6);
+
+        let uniform: CentroidSpectrum = vec![(100.0, 10.0), (200.0, 10.0)].into_iter().collect();
+        assert!((uniform.spectral_entropy() - 2.0f32.ln()).abs() < 1e-6);
+        assert!((uniform.normalized_entropy() - 1.0).abs() < 1e-6);
+
+        let single: CentroidSpectrum = vec![(100.0, 10.0)].into_iter().collect();
+        assert_eq!(single.spectral_entropy(), 0.0);
+        assert_eq!(single.normalized_entropy(), 0.0);
+
+        let empty: CentroidSpectrum = Vec::<(f64, f32)>::new().into_iter().collect();
+        assert_eq!(empty.spectral_entropy(), 0.0);
+        assert_eq!(empty.normalized_entropy(), 0.0);
+    }
+
+    #[test]
+    fn test_intensities_f64() {
+        let spectrum: CentroidSpectrum = vec![(200.0, 2.5), (100.0, 1e6)].into_iter().collect();
+        assert_eq!(spectrum.intensities_f64(), vec![1e6, 2.5]);
+    }
+
+    #[test]
+    fn test_normalize_tic() {
+        let mut centroid: CentroidSpectrum =
+            vec![(100.0, 30.0), (200.0, 10.0)].into_iter().collect();
+        assert!(!centroid.is_normalized());
+        let mut raw = centroid.clone().into_raw();
+
+        centroid.normalize_tic();
+        assert!(centroid.is_normalized());
+        assert_eq!(centroid.intensity_array(), vec![0.75, 0.25]);
+
+        assert!(!<RawSpectrum as SpectrumLike>::is_normalized(&raw));
+        raw.normalize_tic().unwrap();
+        assert!(<RawSpectrum as SpectrumLike>::is_normalized(&raw));
+        assert_eq!(&*raw.intensities(), &[0.75, 0.25]);
+
+        let mut multi: MultiLayerSpectrum = raw.into();
+        multi.normalize_tic().unwrap();
+        assert_eq!(
+            multi
+                .description
+                .params
+                .iter()
+                .filter(|p| p.name == "normalized intensities")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_is_empty() {
+        let centroid: CentroidSpectrum = vec![(300.0, 5.0), (150.0, 10.0)].into_iter().collect();
+        assert!(!centroid.is_empty());
+        assert!(!SpectrumLike::is_empty(&centroid));
+        let raw = centroid.clone().into_raw();
+        assert!(!<RawSpectrum as SpectrumLike>::is_empty(&raw));
+        let deconvoluted = centroid.clone().into_deconvoluted(1);
+        assert!(!deconvoluted.is_empty());
+        let multi: MultiLayerSpectrum = centroid.into();
+        assert!(!multi.is_empty());
+
+        let empty: CentroidSpectrum = Vec::<(f64, f32)>::new().into_iter().collect();
+        assert!(empty.is_empty());
+        assert!(SpectrumLike::is_empty(&empty));
+        assert!(<RawSpectrum as SpectrumLike>::is_empty(
+            &empty.clone().into_raw()
+        ));
+        assert!(empty.clone().into_deconvoluted(1).is_empty());
+
+        let missing = MultiLayerSpectrum::<CentroidPeak, DeconvolutedPeak>::default();
+        assert!(missing.is_empty());
+        assert!(<RawSpectrum as SpectrumLike>::is_empty(
+            &RawSpectrum::default()
+        ));
+    }
+
+    #[test]
+    fn test_peaks_mut() {
+        let mut centroid: CentroidSpectrum = vec![(150.0, 10.0), (300.0, 5.0), (450.5, 1.0)]
+            .into_iter()
+            .collect();
+        for peak in centroid.peaks_mut().peaks.iter_mut() {
+            peak.intensity *= 2.0;
+        }
+        let intensities: Vec<f32> = centroid.iter().map(|p| p.intensity).collect();
+        assert_eq!(intensities, vec![20.0, 10.0, 2.0]);
+
+        centroid.annotate(vec![(0, "a".to_string())]);
+        centroid.peaks_mut().peaks[0].mz = 500.0;
+        centroid.resort();
+        let mzs: Vec<f64> = centroid.iter().map(|p| p.mz).collect();
+        assert_eq!(mzs, vec![300.0, 450.5, 500.0]);
+        assert!(centroid
+            .iter()
+            .enumerate()
+            .all(|(i, p)| p.index as usize == i));
+        assert_eq!(centroid.annotations_of(2).collect::<Vec<_>>(), vec!["a"]);
+        assert!(centroid.has_peak(500.0, Tolerance::Da(0.01)).is_some());
+    }
+
+    #[test]
+    fn test_peak_count_and_mz_range() {
+        let centroid: CentroidSpectrum = vec![(300.0, 5.0), (150.0, 10.0), (450.5, 1.0)]
+            .into_iter()
+            .collect();
+        assert_eq!(centroid.peak_count(), 3);
+        assert_eq!(centroid.mz_range(), Some((150.0, 450.5)));
+
+        let raw = centroid.clone().into_raw();
+        assert_eq!(<RawSpectrum as SpectrumLike>::peak_count(&raw), 3);
+        assert_eq!(
+            <RawSpectrum as SpectrumLike>::mz_range(&raw),
+            Some((150.0, 450.5))
+        );
+
+        let deconvoluted = centroid.clone().into_deconvoluted(2);
+        assert_eq!(deconvoluted.peak_count(), 3);
+        let (low, high) = deconvoluted.mz_range().unwrap();
+        assert!((low - 150.0).abs() < 1e-6);
+        assert!((high - 450.5).abs() < 1e-6);
+
+        let spectrum: Spectrum = centroid.into();
+        assert_eq!(spectrum.peak_count(), 3);
+        assert_eq!(spectrum.mz_range(), Some((150.0, 450.5)));
+
+        let empty: CentroidSpectrum = Vec::<(f64, f32)>::new().into_iter().collect();
+        assert_eq!(empty.peak_count(), 0);
+        assert_eq!(empty.mz_range(), None);
+        assert_eq!(
+            <RawSpectrum as SpectrumLike>::mz_range(&empty.clone().into_raw()),
+            None
+        );
+        assert_eq!(empty.clone().into_deconvoluted(1).mz_range(), None);
+
+        let spectrum = Spectrum::default();
+        assert_eq!(spectrum.peak_count(), 0);
+        assert_eq!(spectrum.mz_range(), None);
+    }
+
+    #[test]
+    fn test_centroid_peaks_from_arrays() {
+        let raw: CentroidSpectrum = vec![(250.5, 4.0), (100.25, 8.0)].into_iter().collect();
+        let raw = raw.into_raw();
+        let peaks = CentroidPeak::try_from_arrays(&raw.arrays).unwrap();
+        assert_eq!(
+            peaks,
+            vec![
+                CentroidPeak::new(100.25, 8.0, 0),
+                CentroidPeak::new(250.5, 4.0, 1)
+            ]
+        );
+        let peaks: MZPeakSetType<CentroidPeak> = (&raw.arrays).into();
+        assert_eq!(peaks[1], CentroidPeak::new(250.5, 4.0, 1));
+    }
+
+    #[test]
+    fn test_centroid_peak_builder() {
+        use crate::spectrum::CentroidPeakBuilder;
+
+        let peak = CentroidPeakBuilder::new()
+            .mz(250.5)
+            .intensity(4.0)
+            .index(3)
+            .build();
+        assert_eq!(peak, CentroidPeak::new(250.5, 4.0, 3));
+        let peak: CentroidPeak = CentroidPeakBuilder::new().mz(100.0).into();
+        assert_eq!(peak, CentroidPeak::new(100.0, 0.0, 0));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic]
+    fn test_centroid_peak_builder_negative_mz() {
+        crate::spectrum::CentroidPeakBuilder::new().mz(-1.0).build();
+    }
+
+    #[test]
+    fn test_downsample_for_display() {
+        let mzs: Vec<f64> = (0..10000).map(|i| 200.0 + i as f64 * 0.01).collect();
+        let mut intensities: Vec<f32> = (0..10000).map(|i| (i % 7) as f32).collect();
+        intensities[4321] = 1000.0;
+        let arrays = mz_intensity_arrays(&mzs, &intensities);
+        let spectrum = RawSpectrum::new(SpectrumDescription::default(), arrays);
+
+        let points = spectrum.downsample_for_display(500);
+        assert!(points.len() <= 500 && points.len() >= 450);
+        assert!(points.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(points.contains(&(mzs[4321], 1000.0)));
+
+        assert_eq!(spectrum.downsample_for_display(20000).len(), 10000);
+    }
+
+    #[test]
+    fn test_mean_mz_spacing() {
+        let make_spectrum = |mzs: Vec<f64>| {
+            let intensities = vec![1.0f32; mzs.len()];
+            let arrays = mz_intensity_arrays(&mzs, &intensities);
+            RawSpectrum::new(SpectrumDescription::default(), arrays)
+        };
+
+        let spectrum = make_spectrum((0..200).map(|i| 400.0 + i as f64 * 0.025).collect());
+        let spacing = spectrum.mean_mz_spacing().unwrap();
+        assert!((spacing - 0.025).abs() < 1e-9);
+
+        assert_eq!(make_spectrum(vec![400.0]).mean_mz_spacing(), None);
+        assert_eq!(make_spectrum(Vec::new()).mean_mz_spacing(), None);
+        assert_eq!(
+            RawSpectrum::new(SpectrumDescription::default(), BinaryArrayMap::new())
+                .mean_mz_spacing(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_resample() {
+        let arrays = mz_intensity_arrays(
+            &[100.0, 100.5, 101.0, 101.5, 102.0],
+            &[0.0, 10.0, 40.0, 10.0, 0.0],
+        );
+        let description = SpectrumDescription {
+            signal_continuity: SignalContinuity::Profile,
+            ..Default::default()
+        };
+        let spectrum = RawSpectrum::new(description, arrays);
+
+        let axis = [99.0, 100.0, 100.25, 101.0, 101.75, 102.0, 103.0];
+        let resampled = spectrum.resample(&axis);
+        assert_eq!(resampled, vec![0.0, 0.0, 5.0, 40.0, 5.0, 0.0, 0.0]);
+
+        let coarse: Vec<f64> = (0..5).map(|i| 99.5 + i as f64).collect();
+        let resampled = spectrum.resample(&coarse);
+        assert_eq!(resampled, vec![0.0, 10.0, 10.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_find_closest() {
+        let spectrum: CentroidSpectrum = vec![(100.0, 1.0), (200.0, 2.0), (300.0, 3.0)]
+            .into_iter()
+            .collect();
+        assert_eq!(spectrum.find_closest(140.0).unwrap().mz, 100.0);
+        assert_eq!(spectrum.find_closest(160.0).unwrap().mz, 200.0);
+        assert_eq!(spectrum.find_closest(150.0).unwrap().mz, 100.0);
+        assert_eq!(spectrum.find_closest(200.0).unwrap().mz, 200.0);
+        assert_eq!(spectrum.find_closest(0.0).unwrap().mz, 100.0);
+        assert_eq!(spectrum.find_closest(100.0).unwrap().mz, 100.0);
+        assert_eq!(spectrum.find_closest(300.0).unwrap().mz, 300.0);
+        assert_eq!(spectrum.find_closest(1e6).unwrap().mz, 300.0);
+
+        let empty = CentroidSpectrum::default();
+        assert!(empty.find_closest(100.0).is_none());
+    }
+
+    #[test]
+    fn test_estimate_charge_at() {
+        let mz = 634.3;
+        let spacing = 1.00286864 / 3.0;
+        let spectrum: CentroidSpectrum = vec![
+            (mz - 0.9, 15.0),
+            (mz, 100.0),
+            (mz + spacing, 80.0),
+            (mz + 2.0 * spacing, 40.0),
+            (mz + 3.0 * spacing, 15.0),
+            (mz + 1.7, 25.0),
+        ]
+        .into_iter()
+        .collect();
+
+        let charge = spectrum.estimate_charge_at(mz, Tolerance::PPM(10.0), 8);
+        assert_eq!(charge, Some(3));
+        let charge = spectrum.estimate_charge_at(mz + spacing, Tolerance::PPM(10.0), 8);
+        assert_eq!(charge, Some(3));
+        assert_eq!(
+            spectrum.estimate_charge_at(mz + 1.7, Tolerance::PPM(10.0), 8),
+            None
+        );
+        assert_eq!(
+            spectrum.estimate_charge_at(mz - 0.9, Tolerance::PPM(10.0), 8),
+            None
+        );
+        assert_eq!(
+            spectrum.estimate_charge_at(500.0, Tolerance::PPM(10.0), 8),
+            None
+        );
+    }
+
+    #[test]
+    fn test_peaks_for_mass() {
+        use crate::utils::PROTON;
+
+        let mass = 1500.0;
+        let spectrum: CentroidSpectrum = vec![
+            (mass / 2.0 + PROTON - 0.5, 20.0),
+            (mass / 2.0 + PROTON, 100.0),
+            (mass / 2.0 + PROTON + 0.005, 10.0),
+            (mass + PROTON, 50.0),
+        ]
+        .into_iter()
+        .collect();
+
+        let peaks = spectrum.peaks_for_mass(mass, 2, Tolerance::PPM(10.0));
+        assert_eq!(peaks.len(), 2);
+        assert!(peaks.iter().all(|p| (p.mz - 751.00727646677).abs() < 1e-2));
+
+        let peaks = spectrum.peaks_for_mass(mass, 2, Tolerance::PPM(1.0));
+        assert_eq!(peaks.len(), 1);
+        assert_eq!(peaks[0].intensity, 100.0);
+
+        let peaks = spectrum.peaks_for_mass(mass, 1, Tolerance::Da(0.01));
+        assert_eq!(peaks.len(), 1);
+        assert_eq!(peaks[0].intensity, 50.0);
+
+        assert!(spectrum
+            .peaks_for_mass(mass, 3, Tolerance::PPM(10.0))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_custom_isotope_model() {
+        use crate::spectrum::{IsotopeModel, PeptideAveragine};
+        use crate::utils::PROTON;
+        use std::sync::Arc;
+
+        /// Evenly spaced isotopic peaks, like the 35Cl/37Cl pattern of a polychlorinated compound
+        #[derive(Debug, Clone)]
+        struct ConstantSpacing {
+            spacing: f64,
+            count: usize,
+        }
+
+        impl IsotopeModel for ConstantSpacing {
+            fn isotopic_pattern(&self, _neutral_mass: f64) -> Vec<(f64, f64)> {
+                (0..self.count)
+                    .map(|i| (i as f64 * self.spacing, 1.0 / self.count as f64))
+                    .collect()
+            }
+        }
+
+        const SPACING: f64 = 1.99705;
+        let six_peaks = ConstantSpacing {
+            spacing: SPACING,
+            count: 6,
+        };
+        let two_peaks = ConstantSpacing {
+            spacing: SPACING,
+            count: 2,
+        };
+        assert_eq!(six_peaks.isotopic_spacing(900.0, 4), Some(SPACING));
+        assert_eq!(six_peaks.isotopic_spacing(900.0, 5), None);
+
+        assert_eq!(
+            PeptideAveragine.isotopic_spacing(1000.0, 100),
+            Some(ISOTOPIC_SPACING)
+        );
+        let pattern = PeptideAveragine.isotopic_pattern(1000.0);
+        assert_eq!(pattern[0].0, 0.0);
+        assert!((pattern.iter().map(|(_, a)| a).sum::<f64>() - 1.0).abs() < 1e-9);
+        // A 1 kDa peptide's monoisotopic peak is its most abundant
+        assert!(pattern.iter().all(|(_, a)| *a <= pattern[0].1));
+        assert!(PeptideAveragine.isotopic_pattern(5000.0).len() > pattern.len());
+
+        let z = 3;
+        let mono = 900.0 / z as f64 + PROTON;
+        let envelope: Vec<(f64, f32)> = (0..4)
+            .map(|i| {
+                (
+                    mono + i as f64 * SPACING / z as f64,
+                    100.0 - 10.0 * i as f32,
+                )
+            })
+            .collect();
+        let spectrum: CentroidSpectrum = envelope.into_iter().collect();
+
+        let deconvoluted = spectrum
+            .clone()
+            .deconvolute(&DeconvolutionParams::default());
+        assert_eq!(deconvoluted.deconvoluted_peaks.len(), 4);
+
+        let params = DeconvolutionParams::default().with_isotope_model(Arc::new(six_peaks));
+        let deconvoluted = spectrum.clone().deconvolute(&params);
+        assert_eq!(deconvoluted.deconvoluted_peaks.len(), 1);
+        let peak = &deconvoluted.deconvoluted_peaks[0];
+        assert_eq!(peak.charge, z);
+        assert!((peak.neutral_mass - 900.0).abs() < 1e-6);
+        assert_eq!(peak.intensity, 340.0);
+
+        // The envelope is truncated to the length of the model's pattern
+        let params = params.with_isotope_model(Arc::new(two_peaks));
+        let deconvoluted = spectrum.deconvolute(&params);
+        assert_eq!(deconvoluted.deconvoluted_peaks.len(), 2);
+    }
+
+    #[test]
+    fn test_deconvolving_iter() -> io::Result<()> {
+        use crate::spectrum::DeconvolvingIter;
+
+        let mut reader = MzMLReader::open_path("./test/data/small.mzML")?;
+        let msn_params = DeconvolutionParams {
+            charge_range: (1, 4),
+            ..Default::default()
+        };
+        let ms2 = reader.iter().filter(|s| s.ms_level() == 2);
+        let mut n = 0;
+        for result in DeconvolvingIter::new(ms2, DeconvolutionParams::default(), msn_params) {
+            let deconvoluted = result.unwrap();
+            assert_eq!(deconvoluted.ms_level(), 2);
+            assert!(!deconvoluted.deconvoluted_peaks.is_empty());
+            assert!(deconvoluted
+                .deconvoluted_peaks
+                .iter()
+                .all(|p| (1..=4).contains(&p.charge)));
+            n += 1;
+        }
+        assert_eq!(n, 34);
+
+        // The MS1 spectra are in profile mode and must be peak picked first
+        let failures = DeconvolvingIter::with_params(reader.iter(), DeconvolutionParams::default())
+            .filter(|r| r.is_err())
+            .count();
+        assert_eq!(failures, 14);
+        Ok(())
+    }
+
+    #[test]
+    fn test_iterate_by_reference() {
+        let mut spectrum: CentroidSpectrum =
+            vec![(150.25, 100.0), (301.5, 20.0)].into_iter().collect();
+        let mut total = 0.0;
+        for peak in &spectrum {
+            total += peak.intensity;
+        }
+        assert_eq!(total, 120.0);
+
+        for peak in &mut spectrum {
+            peak.intensity *= 2.0;
+        }
+        assert_eq!(spectrum.intensity_array(), vec![200.0, 40.0]);
+        assert_eq!(spectrum.iter().count(), 2);
+
+        let deconvoluted = spectrum.into_deconvoluted(1);
+        for peak in &deconvoluted {
+            assert_eq!(peak.charge, 1);
+        }
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let spectrum: CentroidSpectrum = vec![(150.25, 100.0), (301.5, 20.0)].into_iter().collect();
+        assert_eq!(spectrum, spectrum.clone());
+
+        let perturbed: CentroidSpectrum = vec![(150.2501, 100.5), (301.4999, 19.95)]
+            .into_iter()
+            .collect();
+        assert_ne!(spectrum, perturbed);
+        assert!(spectrum.approx_eq(&perturbed, Tolerance::PPM(5.0), 0.01));
+        assert!(spectrum.approx_eq(&perturbed, Tolerance::Da(0.001), 0.01));
+        assert!(!spectrum.approx_eq(&perturbed, Tolerance::PPM(0.1), 0.01));
+        assert!(!spectrum.approx_eq(&perturbed, Tolerance::PPM(5.0), 0.001));
+
+        let fewer: CentroidSpectrum = vec![(150.25, 100.0)].into_iter().collect();
+        assert!(!spectrum.approx_eq(&fewer, Tolerance::PPM(5.0), 0.01));
+    }
+
+    #[test]
+    fn test_shallow_clone_description() {
+        let mut reader = MzMLReader::open_path("./test/data/small.mzML").unwrap();
+        let spectrum = reader.get_spectrum_by_index(0).unwrap();
+        assert!(spectrum.arrays.is_some());
+
+        let description = spectrum.shallow_clone_description();
+        assert_eq!(&description, spectrum.description());
+
+        let copy = Spectrum::from_description(description);
+        assert_eq!(copy.id(), spectrum.id());
+        assert!(copy.arrays.is_none());
+        assert!(copy.peaks.is_none());
+        assert!(copy.deconvoluted_peaks.is_none());
+    }
+
+    #[test]
+    fn test_remove_zero_intensity() {
+        let points: Vec<(f64, f32)> = vec![
+            (100.0, 0.0),
+            (100.1, 0.0),
+            (100.2, 5.0),
+            (100.3, 10.0),
+            (100.4, 0.0),
+            (100.5, 0.0),
+            (100.6, 0.0),
+            (100.7, 3.0),
+        ];
+        let mut spectrum: CentroidSpectrum = points.iter().copied().collect();
+        spectrum.remove_zero_intensity();
+        assert_eq!(
+            spectrum.clone().into_tuples(),
+            vec![(100.2, 5.0), (100.3, 10.0), (100.7, 3.0)]
+        );
+        assert!(spectrum
+            .peaks
+            .iter()
+            .enumerate()
+            .all(|(i, p)| p.get_index() as usize == i));
+        assert!(spectrum.mz_array().windows(2).all(|w| w[0] <= w[1]));
+
+        let spectrum: CentroidSpectrum = points.iter().copied().collect();
+        let mut raw = spectrum.into_raw();
+        raw.remove_zero_intensity(false).unwrap();
+        assert_eq!(raw.mzs().to_vec(), vec![100.2, 100.3, 100.7]);
+        assert_eq!(raw.intensities().to_vec(), vec![5.0, 10.0, 3.0]);
+
+        let spectrum: CentroidSpectrum = points.iter().copied().collect();
+        let mut raw = spectrum.into_raw();
+        raw.remove_zero_intensity(true).unwrap();
+        assert_eq!(
+            raw.mzs().to_vec(),
+            vec![100.1, 100.2, 100.3, 100.4, 100.6, 100.7]
+        );
+        assert_eq!(raw.intensities().len(), raw.mzs().len());
+    }
+
+    #[test]
+    fn test_intensity_quantile() {
+        let peaks = PeakSet::new(vec![
+            CentroidPeak::new(100.0, 40.0, 0),
+            CentroidPeak::new(200.0, 10.0, 0),
+            CentroidPeak::new(300.0, 30.0, 0),
+            CentroidPeak::new(400.0, 20.0, 0),
+            CentroidPeak::new(500.0, 50.0, 0),
+        ]);
+        let spectrum = CentroidSpectrum::new(SpectrumDescription::default(), peaks);
+        assert_eq!(spectrum.intensity_quantile(0.5), Some(30.0));
+        assert_eq!(spectrum.intensity_quantile(0.0), Some(10.0));
+        assert_eq!(spectrum.intensity_quantile(1.0), Some(50.0));
+        assert_eq!(spectrum.intensity_quantile(0.125), Some(15.0));
+        assert_eq!(spectrum.intensity_quantile(1.5), None);
+
+        let raw = spectrum
+            .clone()
+            .into_spectrum::<DeconvolutedPeak>()
+            .unwrap()
+            .into_raw()
+            .unwrap();
+        assert_eq!(raw.intensity_quantile(0.5), Some(30.0));
+
+        let empty = CentroidSpectrum::new(SpectrumDescription::default(), PeakSet::empty());
+        assert_eq!(empty.intensity_quantile(0.5), None);
+        let raw = RawSpectrum::new(SpectrumDescription::default(), BinaryArrayMap::new());
+        assert_eq!(raw.intensity_quantile(0.5), None);
+    }
+
+    #[test]
+    fn test_bounds_checked_peak_access() {
+        let peaks = PeakSet::new(vec![
+            CentroidPeak::new(301.5, 20.0, 0),
+            CentroidPeak::new(150.25, 100.0, 0),
+        ]);
+        let spectrum = CentroidSpectrum::new(SpectrumDescription::default(), peaks);
+        assert_eq!(spectrum.get(0).unwrap().mz, 150.25);
+        assert_eq!(spectrum.get(1).unwrap().mz, 301.5);
+        assert!(spectrum.get(2).is_none());
+        assert_eq!(spectrum.first().unwrap().mz, 150.25);
+        assert_eq!(spectrum.last().unwrap().mz, 301.5);
+
+        let deconvoluted = spectrum.into_deconvoluted(1);
+        assert!(deconvoluted.get(1).is_some());
+        assert!(deconvoluted.get(2).is_none());
+        assert!(
+            deconvoluted.first().unwrap().neutral_mass < deconvoluted.last().unwrap().neutral_mass
+        );
+
+        let empty = CentroidSpectrum::new(SpectrumDescription::default(), PeakSet::empty());
+        assert!(empty.get(0).is_none());
+        assert!(empty.first().is_none());
+        assert!(empty.last().is_none());
+
+        let empty = DeconvolutedSpectrum::default();
+        assert!(empty.get(0).is_none());
+        assert!(empty.first().is_none());
+        assert!(empty.last().is_none());
+    }
+
+    #[cfg(feature = "mzsignal")]
+    #[test]
+    fn test_pick_peaks_apex_indices() {
+        let mzs: Vec<f64> = (0..1000).map(|i| 500.0 + i as f64 * 0.001).collect();
+        let intensities: Vec<f32> = mzs
+            .iter()
+            .map(|mz| {
+                let a = 1000.0 * (-(mz - 500.2f64).powi(2) / (2.0 * 0.01f64.powi(2))).exp();
+                let b = 400.0 * (-(mz - 500.7f64).powi(2) / (2.0 * 0.01f64.powi(2))).exp();
+                (a + b + 1.0) as f32
+            })
+            .collect();
+        let arrays = mz_intensity_arrays(&mzs, &intensities);
+        let mut description = SpectrumDescription::default();
+        description.signal_continuity = SignalContinuity::Profile;
+        let mut spectrum: MultiLayerSpectrum = RawSpectrum::new(description, arrays)
+            .into_spectrum()
+            .unwrap();
+
+        let picker = PeakPicker {
+            fit_type: PeakFitType::Quadratic,
+            signal_to_noise_threshold: 3.0,
+            ..Default::default()
+        };
+        let apexes = spectrum.pick_peaks_with_apex_indices(&picker).unwrap();
+        let peaks = spectrum.peaks.as_ref().unwrap();
+        assert_eq!(apexes.len(), peaks.len());
+        assert_eq!(apexes.len(), 2);
+
+        for (peak, apex) in peaks.iter().zip(apexes.iter().copied()) {
+            // The true maximum of the synthetic peak within 0.05 m/z of the fitted centroid
+            let true_max = (0..intensities.len())
+                .filter(|i| (500.0 + *i as f64 * 0.001 - peak.mz).abs() < 0.05)
+                .max_by(|a, b| intensities[*a].total_cmp(&intensities[*b]))
+                .unwrap();
+            assert_eq!(apex, true_max);
+        }
+    }
+
+    #[cfg(feature = "mzsignal")]
+    #[test]
+    fn test_pick_peaks_weighted_mean() {
+        // A peak with a steep leading edge and a long tail towards higher m/z
+        let mzs: Vec<f64> = (0..1000).map(|i| 500.0 + i as f64 * 0.001).collect();
+        let intensities: Vec<f32> = mzs
+            .iter()
+            .map(|mz| {
+                let width = if *mz < 500.5 { 0.005 } else { 0.03 };
+                let a = 1000.0 * (-(mz - 500.5f64).powi(2) / (2.0 * width * width)).exp();
+                (a + 1.0) as f32
+            })
+            .collect();
+        let weighted_mean = {
+            let half_max = intensities.iter().copied().fold(0.0f32, f32::max) / 2.0;
+            let (weighted, total) = mzs
+                .iter()
+                .zip(intensities.iter())
+                .filter(|(_, int)| **int >= half_max)
+                .fold((0.0, 0.0), |(w, t), (mz, int)| {
+                    (w + mz * *int as f64, t + *int as f64)
+                });
+            weighted / total
+        };
+        let arrays = mz_intensity_arrays(&mzs, &intensities);
+        let mut description = SpectrumDescription::default();
+        description.signal_continuity = SignalContinuity::Profile;
+        let raw = RawSpectrum::new(description, arrays);
+
+        let mut fitted: MultiLayerSpectrum = raw.clone().into_spectrum().unwrap();
+        fitted
+            .pick_peaks_with_method(3.0, CentroidingMethod::PeakFit)
+            .unwrap();
+        let mut weighted: MultiLayerSpectrum = raw.into_spectrum().unwrap();
+        weighted
+            .pick_peaks_with_method(3.0, CentroidingMethod::WeightedMean)
+            .unwrap();
+
+        let fitted = fitted.peaks.as_ref().unwrap();
+        let weighted = weighted.peaks.as_ref().unwrap();
+        assert_eq!(fitted.len(), 1);
+        assert_eq!(weighted.len(), 1);
+
+        // The parabola stays close to the apex while the weighted mean is pulled
+        // towards the tail
+        assert!((fitted[0].mz - 500.5).abs() < 0.002);
+        assert!((weighted[0].mz - weighted_mean).abs() < 1e-9);
+        assert!(weighted[0].mz > fitted[0].mz + 0.005);
+    }
+
     #[cfg(feature = "mzsignal")]
     #[test_log::test]
     fn test_profile_read() {
@@ -1,5 +1,6 @@
 use std::{
-    collections::HashMap,
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
     io,
     sync::mpsc::{Receiver, Sender, SyncSender, TryRecvError},
     time::Duration,
@@ -9,7 +10,7 @@ use mzpeaks::{CentroidLike, DeconvolutedCentroidLike};
 
 use crate::prelude::*;
 
-use super::MultiLayerSpectrum;
+use super::{CentroidSpectrumType, MultiLayerSpectrum};
 
 /// A helper for consuming parallel iteration in the original ordering sequentially later.
 /// Useful for things like splitting work up with `rayon` and then merging it back together
@@ -333,4 +334,116 @@ impl<
     fn close(&mut self) -> std::io::Result<()> {
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// The next unconsumed peak of one spectrum in a [`MergeSortedPeaks`] heap, ordered so that
+/// the lowest m/z is popped first from the max-heap.
+#[derive(Debug, Clone, Copy)]
+struct MergeCursor {
+    mz: f64,
+    spectrum_index: usize,
+    peak_index: usize,
+}
+
+impl PartialEq for MergeCursor {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MergeCursor {}
+
+impl PartialOrd for MergeCursor {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MergeCursor {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .mz
+            .total_cmp(&self.mz)
+            .then_with(|| other.spectrum_index.cmp(&self.spectrum_index))
+    }
+}
+
+/// An iterator over the peaks of several centroid spectra in ascending m/z order,
+/// created by [`merge_sorted_peaks`].
+#[derive(Debug)]
+pub struct MergeSortedPeaks<'a, C: CentroidLike + Default> {
+    spectra: Vec<&'a CentroidSpectrumType<C>>,
+    heap: BinaryHeap<MergeCursor>,
+}
+
+impl<'a, C: CentroidLike + Default> MergeSortedPeaks<'a, C> {
+    fn new(spectra: &[&'a CentroidSpectrumType<C>]) -> Self {
+        let spectra = spectra.to_vec();
+        let heap = spectra
+            .iter()
+            .enumerate()
+            .filter_map(|(spectrum_index, spectrum)| {
+                spectrum.peaks.peaks.first().map(|peak| MergeCursor {
+                    mz: peak.mz(),
+                    spectrum_index,
+                    peak_index: 0,
+                })
+            })
+            .collect();
+        Self { spectra, heap }
+    }
+}
+
+impl<'a, C: CentroidLike + Default> Iterator for MergeSortedPeaks<'a, C> {
+    type Item = &'a C;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cursor = self.heap.pop()?;
+        let peaks = &self.spectra[cursor.spectrum_index].peaks.peaks;
+        let peak_index = cursor.peak_index + 1;
+        if let Some(peak) = peaks.get(peak_index) {
+            self.heap.push(MergeCursor {
+                mz: peak.mz(),
+                peak_index,
+                ..cursor
+            });
+        }
+        peaks.get(cursor.peak_index)
+    }
+}
+
+/// Perform a k-way merge over the already m/z-sorted peak lists of `spectra`, yielding
+/// every peak in global m/z order without building a combined peak list.
+///
+/// Peaks with the same m/z are yielded in the order their spectra were given.
+pub fn merge_sorted_peaks<'a, C: CentroidLike + Default>(
+    spectra: &[&'a CentroidSpectrumType<C>],
+) -> MergeSortedPeaks<'a, C> {
+    MergeSortedPeaks::new(spectra)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::spectrum::CentroidSpectrum;
+
+    #[test]
+    fn test_merge_sorted_peaks() {
+        let a: CentroidSpectrum = vec![(100.0, 1.0), (250.0, 2.0), (400.0, 3.0)]
+            .into_iter()
+            .collect();
+        let b: CentroidSpectrum = vec![(50.0, 4.0), (250.0, 5.0), (300.0, 6.0), (900.0, 7.0)]
+            .into_iter()
+            .collect();
+
+        let merged: Vec<_> = merge_sorted_peaks(&[&a, &b]).collect();
+        assert_eq!(merged.len(), a.peaks.len() + b.peaks.len());
+        assert!(merged.windows(2).all(|w| w[0].mz <= w[1].mz));
+
+        let intensities: Vec<f32> = merged.iter().map(|p| p.intensity).collect();
+        assert_eq!(intensities, vec![4.0, 1.0, 2.0, 5.0, 6.0, 3.0, 7.0]);
+
+        let empty: CentroidSpectrum = Vec::<(f64, f32)>::new().into_iter().collect();
+        assert_eq!(merge_sorted_peaks(&[&empty, &a]).count(), a.peaks.len());
+    }
+}
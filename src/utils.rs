@@ -1,11 +1,89 @@
-const PROTON: f64 = 1.00727646677;
+use mzpeaks::{CentroidLike, MZLocated};
 
+/// The mass of a proton, the default charge carrier
+pub const PROTON: f64 = 1.00727646677;
+
+/// Compute the m/z of an ion of neutral `mass` carrying `z` protons, or losing them
+/// if `z` is negative
 #[inline]
 pub fn mass_charge_ratio(mass: f64, z: i32) -> f64 {
-    (mass / (z.abs() as f64)) + z as f64 * PROTON
+    (mass + z as f64 * PROTON) / (z.abs() as f64)
 }
 
+/// Compute the neutral mass of an ion observed at `mz` with charge `z`, e.g. to convert
+/// a centroid peak's m/z into the neutral mass of a deconvoluted peak
 #[inline]
 pub fn neutral_mass(mz: f64, z: i32) -> f64 {
     (mz * z.abs() as f64) - z as f64 * PROTON
 }
+
+/// Convert a centroid peak's m/z into a neutral mass, since [`CentroidLike`] types are
+/// defined in `mzpeaks` and cannot carry this method themselves
+pub trait CentroidPeakMassExt {
+    /// Compute the neutral mass of this peak if it carries `charge`, see [`neutral_mass`]
+    fn to_neutral_mass(&self, charge: i32) -> f64;
+}
+
+impl<C: CentroidLike> CentroidPeakMassExt for C {
+    #[inline]
+    fn to_neutral_mass(&self, charge: i32) -> f64 {
+        neutral_mass(self.mz(), charge)
+    }
+}
+
+/// Convert a time in minutes, like a retention time, to seconds
+#[inline]
+pub fn minutes_to_seconds(minutes: f64) -> f64 {
+    minutes * 60.0
+}
+
+/// Convert a time in seconds to minutes, the unit retention times are stored in
+#[inline]
+pub fn seconds_to_minutes(seconds: f64) -> f64 {
+    seconds / 60.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_neutral_mass() {
+        use mzpeaks::prelude::*;
+        use mzpeaks::{CentroidPeak, DeconvolutedPeak};
+
+        let peak = CentroidPeak::new(501.0, 100.0, 0);
+        let mass = neutral_mass(peak.mz(), 2);
+        assert!((mass - (1002.0 - 2.0 * PROTON)).abs() < 1e-9);
+        assert!((mass_charge_ratio(mass, 2) - peak.mz()).abs() < 1e-9);
+
+        // mzpeaks rounds the proton mass to six decimal places
+        let deconvoluted = DeconvolutedPeak::new(mass, peak.intensity, 2, 0);
+        assert!((deconvoluted.mz() - peak.mz()).abs() < 1e-6);
+        assert_eq!(deconvoluted.neutral_mass, mass);
+
+        let mass = neutral_mass(peak.mz(), -1);
+        assert!((mass - (501.0 + PROTON)).abs() < 1e-9);
+        assert!((mass_charge_ratio(mass, -1) - peak.mz()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_to_neutral_mass() {
+        use crate::prelude::*;
+        use mzpeaks::CentroidPeak;
+
+        let peak = CentroidPeak::new(501.0, 100.0, 0);
+        assert_eq!(peak.to_neutral_mass(2), neutral_mass(501.0, 2));
+        assert_eq!(peak.to_neutral_mass(-1), neutral_mass(501.0, -1));
+    }
+
+    #[test]
+    fn test_time_conversion() {
+        assert_eq!(minutes_to_seconds(1.5), 90.0);
+        assert_eq!(seconds_to_minutes(90.0), 1.5);
+        assert_eq!(seconds_to_minutes(3600.0), 60.0);
+        assert_eq!(minutes_to_seconds(0.0), 0.0);
+        let time = 12.3456;
+        assert!((seconds_to_minutes(minutes_to_seconds(time)) - time).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,66 @@
+//! These tests install a global logger, so they live in their own test binary
+//! rather than alongside the unit tests which may initialize one of their own.
+use std::fs;
+use std::io;
+use std::sync::Mutex;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use mzdata::MzMLReader;
+
+struct CapturingLogger {
+    records: Mutex<Vec<(Level, String)>>,
+}
+
+impl Log for CapturingLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.records
+            .lock()
+            .unwrap()
+            .push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger {
+    records: Mutex::new(Vec::new()),
+};
+
+#[test]
+fn test_unknown_accession_warning() -> io::Result<()> {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+
+    let text = fs::read_to_string("./test/data/small.mzML")?;
+    let text = text.replace(
+        r#"cvRef="MS" accession="MS:1000511""#,
+        r#"cvRef="XX" accession="XX:1000511""#,
+    );
+    let count_warnings = || {
+        LOGGER
+            .records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(level, message)| {
+                *level == Level::Warn
+                    && message.contains("Unrecognized CV param accession XX:1000511")
+            })
+            .count()
+    };
+
+    // The accession is used by every spectrum, but is only reported once
+    let reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+    assert_eq!(reader.count(), 48);
+    assert_eq!(count_warnings(), 1);
+
+    // Each reader keeps track of what it has reported on its own
+    let reader = MzMLReader::new(io::Cursor::new(text.as_bytes()));
+    assert_eq!(reader.count(), 48);
+    assert_eq!(count_warnings(), 2);
+    Ok(())
+}